@@ -0,0 +1,109 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rrt::bvh::Bvh;
+use rrt::hit::{Hittable, HittableList};
+use rrt::material::{Lambertian, MaterialKind};
+use rrt::model::load_obj;
+use rrt::obj::Obj;
+use rrt::random::rand_uniform;
+use rrt::ray::Ray;
+use rrt::sphere::Sphere;
+use rrt::triangle::Triangle;
+use rrt::vec3::Vec3;
+
+const RAY_NUM: usize = 1000;
+
+fn random_spheres() -> HittableList {
+    fastrand::seed(0);
+    let mat = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+    let mut world = HittableList::new();
+    for a in -11..11 {
+        for b in -11..11 {
+            let center = Vec3::new(
+                a as f32 + 0.9 * rand_uniform(),
+                0.2,
+                b as f32 + 0.9 * rand_uniform(),
+            );
+            world
+                .list
+                .push(Box::new(Sphere::new(center, 0.2, mat.clone())));
+        }
+    }
+    world
+}
+
+// the teapot as separately boxed triangles
+fn teapot() -> HittableList {
+    let mat = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+    let obj = Obj::load("model/teapot.obj").unwrap();
+    let mut world = HittableList::new();
+    for f in obj.faces.iter() {
+        let [a, b, c] = f.vertices.map(|v| obj.positions[v.position]);
+        world
+            .list
+            .push(Box::new(Triangle::new(a, b, c, mat.clone())));
+    }
+    world
+}
+
+fn random_vec3() -> Vec3 {
+    Vec3::new(
+        rand_uniform() - 0.5,
+        rand_uniform() - 0.5,
+        rand_uniform() - 0.5,
+    )
+}
+
+// rays from around the scene towards its center
+fn rays(world: &HittableList) -> Vec<Ray> {
+    fastrand::seed(1);
+    let bbox = world.bounding_box().unwrap();
+    let center = bbox.centroid();
+    let radius = bbox.extent().length();
+    (0..RAY_NUM)
+        .map(|_| {
+            let origin = center + radius * random_vec3().unit_vector();
+            let target = center + 0.5 * radius * random_vec3();
+            Ray::new(origin, target - origin)
+        })
+        .collect()
+}
+
+fn trace(world: &dyn Hittable, rays: &[Ray]) -> usize {
+    rays.iter()
+        .filter(|r| world.hit(r, 0.001, f32::MAX).is_some())
+        .count()
+}
+
+fn bench_scene(c: &mut Criterion, name: &str, scene: fn() -> HittableList) {
+    let list = scene();
+    let rays = rays(&list);
+    let bvh = Bvh::from(scene());
+
+    let mut group = c.benchmark_group(name);
+    group.bench_function("list", |b| b.iter(|| trace(&list, black_box(&rays))));
+    group.bench_function("bvh", |b| b.iter(|| trace(&bvh, black_box(&rays))));
+    group.bench_function("build", |b| b.iter(|| Bvh::from(scene())));
+    group.finish();
+}
+
+fn spheres(c: &mut Criterion) {
+    bench_scene(c, "spheres", random_spheres);
+}
+
+fn teapot_mesh(c: &mut Criterion) {
+    bench_scene(c, "teapot", teapot);
+
+    let load = || {
+        let mat = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        load_obj("model/teapot.obj", mat, 60.0).unwrap()
+    };
+    let rays = rays(&teapot());
+    let mesh = load();
+    let mut group = c.benchmark_group("teapot");
+    group.bench_function("mesh", |b| b.iter(|| trace(&mesh, black_box(&rays))));
+    group.bench_function("mesh build", |b| b.iter(load));
+    group.finish();
+}
+
+criterion_group!(benches, spheres, teapot_mesh);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use once_cell::sync::Lazy;
+use rrt::bvh::Bvh;
+use rrt::camera::Camera;
+use rrt::hit::HittableList;
+use rrt::material::{Dielectric, MaterialKind, Metal};
+use rrt::model::cornell_box;
+use rrt::renderer::{rendering, Background};
+use rrt::sphere::Sphere;
+use rrt::vec3::Vec3;
+
+const NX: u32 = 600;
+const NY: u32 = 600;
+const NS: u32 = 200;
+
+static SCENE: Lazy<Bvh> = Lazy::new(|| Bvh::from(test_scene()));
+static CAM: Lazy<Camera> = Lazy::new(|| {
+    let lookfrom = Vec3::new(278.0, 278.0, -800.0);
+    let lookat = Vec3::new(278.0, 278.0, 0.0);
+    let focus_dist = (lookfrom - lookat).length();
+    let aperture = 0.0;
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let vfov = 40.0;
+    let aspect = NX as f32 / NY as f32;
+
+    Camera::new(lookfrom, lookat, vup, vfov, aspect, aperture, focus_dist)
+});
+
+fn test_scene() -> HittableList {
+    let mut world = HittableList::new();
+
+    for t in cornell_box() {
+        world.list.push(t);
+    }
+
+    world.list.push(Box::new(Sphere::new(
+        Vec3::new(190.0, 90.0, 190.0),
+        90.0,
+        MaterialKind::Dielectric(Dielectric::new(1.5)),
+    )));
+    world.list.push(Box::new(Sphere::new(
+        Vec3::new(370.0, 120.0, 370.0),
+        120.0,
+        MaterialKind::Metal(Metal::new(Vec3::new(0.8, 0.85, 0.88), 0.0)),
+    )));
+
+    world
+}
+
+fn main() {
+    let start = std::time::SystemTime::now();
+    rendering(
+        NX,
+        NY,
+        NS,
+        &CAM,
+        &*SCENE,
+        Background::Color(Vec3::zero()),
+        0,
+        "cornell.png",
+        false,
+    );
+    println!("{:?}", start.elapsed().unwrap());
+}
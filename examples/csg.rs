@@ -0,0 +1,84 @@
+use once_cell::sync::Lazy;
+use rrt::box_shape::BoxShape;
+use rrt::bvh::Bvh;
+use rrt::camera::Camera;
+use rrt::csg::Csg;
+use rrt::cylinder::Cylinder;
+use rrt::hit::HittableList;
+use rrt::material::{Dielectric, Lambertian, MaterialKind, Metal};
+use rrt::plane::Plane;
+use rrt::renderer::{rendering, Background};
+use rrt::sphere::Sphere;
+use rrt::vec3::Vec3;
+
+const NX: u32 = 600;
+const NY: u32 = 400;
+const NS: u32 = 100;
+
+static SCENE: Lazy<Bvh> = Lazy::new(|| Bvh::from(test_scene()));
+static CAM: Lazy<Camera> = Lazy::new(|| {
+    let lookfrom = Vec3::new(0.0, 3.0, 8.0);
+    let lookat = Vec3::new(0.0, 0.8, 0.0);
+    let focus_dist = (lookfrom - lookat).length();
+    let aperture = 0.0;
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let vfov = 35.0;
+    let aspect = NX as f32 / NY as f32;
+
+    Camera::new(lookfrom, lookat, vup, vfov, aspect, aperture, focus_dist)
+});
+
+fn test_scene() -> HittableList {
+    let mut world = HittableList::new();
+    let glass = MaterialKind::Dielectric(Dielectric::new(1.5));
+    let steel = MaterialKind::Metal(Metal::new(Vec3::new(0.8, 0.8, 0.85), 0.2));
+
+    world.list.push(Box::new(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+    )));
+
+    // a biconvex lens standing on its rim
+    let lens = Csg::intersection(
+        Box::new(Sphere::new(Vec3::new(-1.5, 1.0, -2.0), 2.0, glass.clone())),
+        Box::new(Sphere::new(Vec3::new(-1.5, 1.0, 1.4), 2.0, glass)),
+    );
+    world.list.push(Box::new(lens));
+
+    // a block with a hole drilled through it and a round pocket
+    let block = Box::new(BoxShape::new(
+        Vec3::new(0.5, 0.0, -1.0),
+        Vec3::new(2.5, 1.0, 1.0),
+        steel.clone(),
+    ));
+    let hole = Box::new(Cylinder::new(
+        Vec3::new(1.5, -1.0, 0.0),
+        Vec3::new(1.5, 2.0, 0.0),
+        0.4,
+        steel.clone(),
+    ));
+    let pocket = Box::new(Sphere::new(Vec3::new(1.5, 1.0, 0.6), 0.5, steel));
+    world.list.push(Box::new(Csg::difference(
+        Csg::difference(block, hole).into(),
+        pocket,
+    )));
+
+    world
+}
+
+fn main() {
+    let start = std::time::SystemTime::now();
+    rendering(
+        NX,
+        NY,
+        NS,
+        &CAM,
+        &*SCENE,
+        Background::Sky,
+        0,
+        "csg.png",
+        false,
+    );
+    println!("{:?}", start.elapsed().unwrap());
+}
@@ -0,0 +1,83 @@
+use once_cell::sync::Lazy;
+use rrt::aabb::Aabb;
+use rrt::bvh::Bvh;
+use rrt::camera::Camera;
+use rrt::hit::HittableList;
+use rrt::material::{Dielectric, Lambertian, MaterialKind, Metal};
+use rrt::plane::Plane;
+use rrt::renderer::{rendering, Background};
+use rrt::sdf::{Sdf, SdfNode};
+use rrt::vec3::Vec3;
+
+const NX: u32 = 600;
+const NY: u32 = 400;
+const NS: u32 = 100;
+
+static SCENE: Lazy<Bvh> = Lazy::new(|| Bvh::from(test_scene()));
+static CAM: Lazy<Camera> = Lazy::new(|| {
+    let lookfrom = Vec3::new(0.0, 3.0, 8.0);
+    let lookat = Vec3::new(0.0, 0.8, 0.0);
+    let focus_dist = (lookfrom - lookat).length();
+    let aperture = 0.0;
+    let vup = Vec3::new(0.0, 1.0, 0.0);
+    let vfov = 35.0;
+    let aspect = NX as f32 / NY as f32;
+
+    Camera::new(lookfrom, lookat, vup, vfov, aspect, aperture, focus_dist)
+});
+
+fn test_scene() -> HittableList {
+    let mut world = HittableList::new();
+    world.list.push(Box::new(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
+        MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+    )));
+
+    // three blobs melted together
+    let blob = SdfNode::sphere(Vec3::new(-2.2, 0.7, 0.0), 0.7)
+        .smooth_union(SdfNode::sphere(Vec3::new(-1.4, 1.2, -0.3), 0.5), 0.4)
+        .smooth_union(SdfNode::sphere(Vec3::new(-1.6, 0.4, 0.6), 0.4), 0.4);
+    let red = MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.8, 0.2, 0.15)));
+    world.list.push(Box::new(Sdf::new(blob, red)));
+
+    // a rounded box with a ring cut into it
+    let block = SdfNode::cuboid(Vec3::new(0.0, 0.75, 0.0), Vec3::new(0.6, 0.6, 0.6))
+        .round(0.15)
+        .smooth_difference(SdfNode::torus(Vec3::new(0.0, 1.5, 0.0), 0.5, 0.2), 0.1);
+    let steel = MaterialKind::Metal(Metal::new(Vec3::new(0.8, 0.8, 0.85), 0.1));
+    world.list.push(Box::new(Sdf::new(block, steel)));
+
+    // a glass gyroid shell cut to a ball, from a closure; the factor keeps
+    // the gyroid from overestimating the distance
+    let gyroid = |p: Vec3| {
+        let q = 6.0 * (p - Vec3::new(2.0, 0.8, 0.0));
+        let g = q.x().sin() * q.y().cos() + q.y().sin() * q.z().cos() + q.z().sin() * q.x().cos();
+        let shell = g.abs() / 6.0 * 0.5 - 0.03;
+        let ball = (p - Vec3::new(2.0, 0.8, 0.0)).length() - 0.8;
+        shell.max(ball)
+    };
+    let bbox = Aabb::new(Vec3::new(1.2, 0.0, -0.8), Vec3::new(2.8, 1.6, 0.8));
+    let glass = MaterialKind::Dielectric(Dielectric::new(1.5));
+    world
+        .list
+        .push(Box::new(Sdf::from_fn(gyroid, bbox, glass).max_steps(512)));
+
+    world
+}
+
+fn main() {
+    let start = std::time::SystemTime::now();
+    rendering(
+        NX,
+        NY,
+        NS,
+        &CAM,
+        &*SCENE,
+        Background::Sky,
+        0,
+        "sdf.png",
+        false,
+    );
+    println!("{:?}", start.elapsed().unwrap());
+}
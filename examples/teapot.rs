@@ -1,15 +1,17 @@
 use once_cell::sync::Lazy;
+use rrt::bvh::Bvh;
 use rrt::camera::Camera;
 use rrt::hit::HittableList;
+use rrt::material::{MaterialKind, Metal};
 use rrt::model::load_obj;
-use rrt::renderer::rendering;
+use rrt::renderer::{rendering, Background};
 use rrt::vec3::Vec3;
 
 const NX: u32 = 1920 / 2;
 const NY: u32 = 1080 / 2;
 const NS: u32 = 100;
 
-static SCENE: Lazy<HittableList> = Lazy::new(|| test_scene());
+static SCENE: Lazy<Bvh> = Lazy::new(|| Bvh::from(test_scene()));
 static CAM: Lazy<Camera> = Lazy::new(|| {
     let lookfrom = Vec3::new(10.0, 5.0, 10.0);
     let lookat = Vec3::new(0.0, 1.0, 0.0);
@@ -24,17 +26,25 @@ static CAM: Lazy<Camera> = Lazy::new(|| {
 
 fn test_scene() -> HittableList {
     let mut world = HittableList::new();
-    let teapot = load_obj("model/teapot.obj").unwrap();
-
-    for t in teapot {
-        world.list.push(t);
-    }
-    dbg!(world.list.len());
+    let mat = MaterialKind::Metal(Metal::new(Vec3::new(0.9, 0.9, 0.9), 1.0));
+    let teapot = load_obj("model/teapot.obj", mat, 60.0).unwrap();
+    dbg!(teapot.len());
+    world.list.push(Box::new(teapot));
     world
 }
 
 fn main() {
     let start = std::time::SystemTime::now();
-    rendering(NX, NY, NS, &CAM, &SCENE, 6, "teapot.png", false);
+    rendering(
+        NX,
+        NY,
+        NS,
+        &CAM,
+        &*SCENE,
+        Background::Sky,
+        6,
+        "teapot.png",
+        false,
+    );
     println!("{:?}", start.elapsed().unwrap());
 }
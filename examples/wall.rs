@@ -1,10 +1,11 @@
 use once_cell::sync::Lazy;
+use rrt::bvh::Bvh;
 use rrt::camera::Camera;
 use rrt::hit::HittableList;
 use rrt::material::{Lambertian, MaterialKind, Metal};
 use rrt::model::ramiel;
 use rrt::model::wall;
-use rrt::renderer::rendering;
+use rrt::renderer::{rendering, Background};
 use rrt::sphere::Sphere;
 use rrt::vec3::Vec3;
 
@@ -12,7 +13,7 @@ const NX: u32 = 1920 / 2;
 const NY: u32 = 1080 / 2;
 const NS: u32 = 100;
 
-static SCENE: Lazy<HittableList> = Lazy::new(|| test_scene());
+static SCENE: Lazy<Bvh> = Lazy::new(|| Bvh::from(test_scene()));
 static CAM: Lazy<Camera> = Lazy::new(|| {
     let lookfrom = Vec3::new(10.0, 20.0, 50.0);
     let lookat = Vec3::new(0.0, 0.0, 0.0);
@@ -65,7 +66,11 @@ fn test_scene() -> HittableList {
     }
 
     for i in 0..20 {
-        let mat = if i % 2 == 0 { metal } else { lam_g };
+        let mat = if i % 2 == 0 {
+            metal.clone()
+        } else {
+            lam_g.clone()
+        };
         let i = i as f32;
         let d = std::f32::consts::PI * 2.0 / 20.0;
         let x = (d * i).sin() * 5.0;
@@ -77,7 +82,11 @@ fn test_scene() -> HittableList {
     }
 
     for i in 0..20 {
-        let mat = if i % 2 == 1 { metal } else { lam_g };
+        let mat = if i % 2 == 1 {
+            metal.clone()
+        } else {
+            lam_g.clone()
+        };
         let i = i as f32;
         let d = std::f32::consts::PI * 2.0 / 20.0;
         let x = (d * i).sin() * 5.0;
@@ -93,6 +102,16 @@ fn test_scene() -> HittableList {
 
 fn main() {
     let start = std::time::SystemTime::now();
-    rendering(NX, NY, NS, &CAM, &SCENE, 0, "wall.png", false);
+    rendering(
+        NX,
+        NY,
+        NS,
+        &CAM,
+        &*SCENE,
+        Background::Sky,
+        0,
+        "wall.png",
+        false,
+    );
     println!("{:?}", start.elapsed().unwrap());
 }
@@ -0,0 +1,151 @@
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(a: Vec3, b: Vec3) -> Aabb {
+        Aabb {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    // inverted box, so that any union with it yields the other box
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vec3::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Vec3::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Aabb {
+        points.iter().fold(Aabb::empty(), |b, p| {
+            Aabb::new(b.min.min(*p), b.max.max(*p))
+        })
+    }
+
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    // grow flat boxes (e.g. axis aligned triangles) so the slab test never degenerates
+    pub fn padded(&self, delta: f32) -> Aabb {
+        let d = self.extent();
+        let grow = |e: f32| if e < delta { delta / 2.0 } else { 0.0 };
+        let v = Vec3::new(grow(d.x()), grow(d.y()), grow(d.z()));
+        Aabb {
+            min: self.min - v,
+            max: self.max + v,
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.extent();
+        if d.x() < 0.0 || d.y() < 0.0 || d.z() < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.extent();
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> bool {
+        self.clip(r, t_min, t_max).is_some()
+    }
+
+    // the part of (t_min, t_max) where r is inside the box
+    pub fn clip(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let origin = r.origin();
+        let direction = r.direction();
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let inv_d = 1.0 / direction[axis];
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // written so that NaN (0 * inf) keeps the current interval
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aabb::*;
+
+    #[test]
+    fn hit() {
+        let b = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(b.hit(&r, 0.0, f32::MAX));
+        assert!(!b.hit(&r, 0.0, 3.0));
+
+        let r = Ray::new(Vec3::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!b.hit(&r, 0.0, f32::MAX));
+
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!b.hit(&r, 0.0, f32::MAX));
+
+        // origin inside the box
+        let r = Ray::new(Vec3::zero(), Vec3::new(1.0, 2.0, 3.0));
+        assert!(b.hit(&r, 0.0, f32::MAX));
+        let (t0, t1) = b.clip(&r, 0.0, f32::MAX).unwrap();
+        assert_eq!(t0, 0.0);
+        assert_eq!(t1, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn padded() {
+        let b = Aabb::new(Vec3::new(-1.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 1.0)).padded(0.001);
+        assert!(b.extent().y() > 0.0);
+        assert_eq!(b.extent().x(), 2.0);
+        assert_eq!(b.extent().z(), 2.0);
+
+        let r = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(b.hit(&r, 0.0, f32::MAX));
+    }
+
+    #[test]
+    fn surface_area() {
+        let b = Aabb::new(Vec3::zero(), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(b.surface_area(), 22.0);
+        assert_eq!(Aabb::empty().surface_area(), 0.0);
+
+        let u = Aabb::empty().surrounding(&b);
+        assert_eq!(u.surface_area(), 22.0);
+    }
+}
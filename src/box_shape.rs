@@ -0,0 +1,193 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
+use crate::material::MaterialKind;
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+fn along_axis(axis: usize, x: f32) -> Vec3 {
+    match axis {
+        0 => Vec3::new(x, 0.0, 0.0),
+        1 => Vec3::new(0.0, x, 0.0),
+        _ => Vec3::new(0.0, 0.0, x),
+    }
+}
+
+// axis aligned box; each face has (u, v) in [0, 1] along the next two axes
+#[derive(Clone, Debug)]
+pub struct BoxShape {
+    min: Vec3,
+    max: Vec3,
+    material: MaterialKind,
+}
+
+impl BoxShape {
+    pub fn new(a: Vec3, b: Vec3, mat: MaterialKind) -> BoxShape {
+        BoxShape {
+            min: a.min(b),
+            max: a.max(b),
+            material: mat,
+        }
+    }
+
+    // the six faces, ordered -x, +x, -y, +y, -z, +z
+    pub fn faces(&self) -> Vec<Quad> {
+        let d = self.max - self.min;
+        let mut r = vec![];
+        for axis in 0..3 {
+            let u = along_axis((axis + 1) % 3, d[(axis + 1) % 3]);
+            let v = along_axis((axis + 2) % 3, d[(axis + 2) % 3]);
+            let corner = self.min;
+            r.push(Quad::new(corner, u, v, self.material.clone()));
+            let corner = corner + along_axis(axis, d[axis]);
+            r.push(Quad::new(corner, u, v, self.material.clone()));
+        }
+        r
+    }
+
+    // where the ray enters and leaves, with the axis and the sign of the
+    // outward normal of the face
+    fn slabs(&self, r: &Ray) -> Option<[(f32, usize, f32); 2]> {
+        let origin = r.origin();
+        let direction = r.direction();
+        let (mut t_near, mut near_axis) = (f32::MIN, 0);
+        let (mut t_far, mut far_axis) = (f32::MAX, 0);
+        for axis in 0..3 {
+            let inv_d = 1.0 / direction[axis];
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN (0 * inf) leaves the interval as it is
+            if t0 > t_near {
+                t_near = t0;
+                near_axis = axis;
+            }
+            if t1 < t_far {
+                t_far = t1;
+                far_axis = axis;
+            }
+        }
+        if t_far < t_near {
+            return None;
+        }
+        Some([
+            (t_near, near_axis, -direction[near_axis].signum()),
+            (t_far, far_axis, direction[far_axis].signum()),
+        ])
+    }
+
+    fn record(&self, r: &Ray, t: f32, axis: usize, sign: f32) -> HitRecord<'_> {
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        let p = r.point_at_parameter(t);
+        rec.p = p;
+        let d = self.max - self.min;
+        let along = |a: usize| {
+            let a = a % 3;
+            if d[a] > 0.0 {
+                ((p[a] - self.min[a]) / d[a]).clamp(0.0, 1.0)
+            } else {
+                0.0
+            }
+        };
+        rec.u = along(axis + 1);
+        rec.v = along(axis + 2);
+        rec.normal = along_axis(axis, sign);
+        rec
+    }
+}
+
+impl Hittable for BoxShape {
+    // normals point out of the box, also when the ray leaves it from inside
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let [near, far] = self.slabs(r)?;
+        [near, far]
+            .iter()
+            .find(|(t, _, _)| t_min < *t && *t < t_max)
+            .map(|&(t, axis, sign)| self.record(r, t, axis, sign))
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        match self.slabs(r) {
+            Some(crossings) => crossings
+                .iter()
+                .filter(|(t, _, _)| t_min < *t && *t < t_max)
+                .map(|&(t, axis, sign)| self.record(r, t, axis, sign))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max).padded(0.0001))
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        match self.material {
+            MaterialKind::DiffuseLight(_) => self
+                .faces()
+                .into_iter()
+                .map(|q| Box::new(q) as Box<dyn Light + Send + Sync>)
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::box_shape::*;
+    use crate::material::{DiffuseLight, Lambertian};
+    use float_eq::assert_float_eq;
+
+    fn unit_box(mat: MaterialKind) -> BoxShape {
+        BoxShape::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(-1.0, 0.0, 1.0), mat)
+    }
+
+    #[test]
+    fn hit() {
+        let b = unit_box(MaterialKind::Lambertian(Lambertian::new(Vec3::one())));
+
+        let r = Ray::new(Vec3::new(0.5, 1.5, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = b.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 7.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.z(), 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.u, 0.75, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.75, abs <= 1e-6);
+
+        // from inside, the exit face with its outward normal
+        let r = Ray::new(Vec3::new(0.0, 1.0, 2.0), Vec3::new(0.0, -2.0, 0.0));
+        let rec = b.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 0.5, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), -1.0, abs <= 1e-6);
+
+        // parallel to a face, outside its slab
+        let r = Ray::new(Vec3::new(0.0, 3.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(b.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.0, 1.0, 10.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(b.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.5, 1.5, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(b.hit(&r, 0.001, 5.0).is_none());
+    }
+
+    #[test]
+    fn faces() {
+        let b = unit_box(MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one())));
+        let lights = b.lights();
+        assert_eq!(lights.len(), 6);
+        let area: f32 = lights.iter().map(|l| l.area()).sum();
+        assert_float_eq!(area, 24.0, abs <= 1e-5);
+
+        // each face reports the same hit as the box itself
+        let r = Ray::new(Vec3::new(0.5, 1.5, 10.0), Vec3::new(0.01, 0.02, -1.0));
+        let a = b.hit(&r, 0.001, f32::MAX).unwrap();
+        let faces = b.faces();
+        let q = faces[5].hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(a.t, q.t, abs <= 1e-5);
+        assert_float_eq!(a.u, q.u, abs <= 1e-5);
+        assert_float_eq!(a.v, q.v, abs <= 1e-5);
+    }
+}
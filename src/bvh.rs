@@ -0,0 +1,407 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable, HittableList};
+use crate::light::Light;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use std::ops::Range;
+
+const BIN_NUM: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+// relative cost of one ray-box test against one primitive test
+const TRAVERSAL_COST: f32 = 0.125;
+
+#[derive(Debug)]
+enum Node {
+    Leaf {
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        left: usize,
+        right: usize,
+        axis: usize,
+    },
+}
+
+#[derive(Debug)]
+struct BvhNode {
+    bbox: Aabb,
+    node: Node,
+}
+
+struct Primitive {
+    index: usize,
+    bbox: Aabb,
+    centroid: Vec3,
+}
+
+// the hierarchy alone, over primitives given by their boxes
+pub(crate) struct Tree {
+    nodes: Vec<BvhNode>,
+}
+
+impl Tree {
+    // also returns the primitive indices in the order the leaves refer to them
+    pub(crate) fn new(bboxes: &[Aabb]) -> (Tree, Vec<usize>) {
+        let mut prims: Vec<Primitive> = bboxes
+            .iter()
+            .enumerate()
+            .map(|(index, bbox)| Primitive {
+                index,
+                bbox: *bbox,
+                centroid: bbox.centroid(),
+            })
+            .collect();
+
+        let mut nodes = vec![];
+        if !prims.is_empty() {
+            build(&mut nodes, &mut prims, 0);
+        }
+        (Tree { nodes }, prims.iter().map(|p| p.index).collect())
+    }
+
+    pub(crate) fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|n| n.bbox)
+    }
+
+    // calls hit_range with each leaf's range of ordered primitives whose box
+    // the ray enters before the closest hit so far, nearer ones first;
+    // hit_range returns the distance of the closest hit in its range, if any
+    pub(crate) fn traverse<F>(&self, r: &Ray, t_min: f32, t_max: f32, mut hit_range: F)
+    where
+        F: FnMut(Range<usize>, f32) -> Option<f32>,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut closest_so_far = t_max;
+        let direction = r.direction();
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let n = &self.nodes[i];
+            if !n.bbox.hit(r, t_min, closest_so_far) {
+                continue;
+            }
+            match n.node {
+                Node::Leaf { first, count } => {
+                    if let Some(t) = hit_range(first..first + count, closest_so_far) {
+                        closest_so_far = t;
+                    }
+                }
+                Node::Interior { left, right, axis } => {
+                    // visit the near child first so the far one is more likely culled
+                    if direction[axis] < 0.0 {
+                        stack.push(left);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct Bvh {
+    tree: Tree,
+    objects: Vec<Box<dyn Hittable + Send + Sync>>,
+    // objects without a bounding box are tested against every ray
+    unbounded: Vec<Box<dyn Hittable + Send + Sync>>,
+}
+
+impl Bvh {
+    pub fn new(list: Vec<Box<dyn Hittable + Send + Sync>>) -> Bvh {
+        let mut objects = vec![];
+        let mut bboxes = vec![];
+        let mut unbounded = vec![];
+        for h in list {
+            match h.bounding_box() {
+                Some(bbox) => {
+                    bboxes.push(bbox);
+                    objects.push(Some(h));
+                }
+                None => unbounded.push(h),
+            }
+        }
+
+        let (tree, order) = Tree::new(&bboxes);
+        // reorder objects so that each leaf refers to a contiguous range
+        let objects = order.iter().map(|&i| objects[i].take().unwrap()).collect();
+
+        Bvh {
+            tree,
+            objects,
+            unbounded,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<HittableList> for Bvh {
+    fn from(list: HittableList) -> Bvh {
+        Bvh::new(list.list)
+    }
+}
+
+// builds the subtree for prims[..] whose first element is objects[offset],
+// and returns the index of its root node
+fn build(nodes: &mut Vec<BvhNode>, prims: &mut [Primitive], offset: usize) -> usize {
+    let bbox = prims
+        .iter()
+        .fold(Aabb::empty(), |b, p| b.surrounding(&p.bbox));
+    let index = nodes.len();
+    nodes.push(BvhNode {
+        bbox,
+        node: Node::Leaf {
+            first: offset,
+            count: prims.len(),
+        },
+    });
+
+    if prims.len() <= 1 {
+        return index;
+    }
+
+    let split = match find_split(prims, &bbox) {
+        Some(split) => split,
+        None => return index,
+    };
+    let (axis, mid) = split;
+
+    let (l, r) = prims.split_at_mut(mid);
+    let left = build(nodes, l, offset);
+    let right = build(nodes, r, offset + mid);
+    nodes[index].node = Node::Interior { left, right, axis };
+    index
+}
+
+// binned surface area heuristic; returns the split axis and the number of
+// primitives going to the left child (prims is partitioned accordingly),
+// or None if a leaf is cheaper
+fn find_split(prims: &mut [Primitive], bbox: &Aabb) -> Option<(usize, usize)> {
+    let centroid_bounds = prims.iter().fold(Aabb::empty(), |b, p| {
+        b.surrounding(&Aabb::new(p.centroid, p.centroid))
+    });
+    let axis = centroid_bounds.longest_axis();
+    let lo = centroid_bounds.min[axis];
+    let extent = centroid_bounds.max[axis] - lo;
+
+    if extent <= 0.0 {
+        // all centroids coincide, the SAH cannot separate them
+        if prims.len() <= MAX_LEAF_SIZE {
+            return None;
+        }
+        let mid = prims.len() / 2;
+        return Some((axis, mid));
+    }
+
+    let bin_of = |p: &Primitive| {
+        let b = ((p.centroid[axis] - lo) / extent * BIN_NUM as f32) as usize;
+        b.min(BIN_NUM - 1)
+    };
+
+    let mut counts = [0usize; BIN_NUM];
+    let mut bounds = [Aabb::empty(); BIN_NUM];
+    for p in prims.iter() {
+        let b = bin_of(p);
+        counts[b] += 1;
+        bounds[b] = bounds[b].surrounding(&p.bbox);
+    }
+
+    // sweep from the right to get the area/count of every right-hand side
+    let mut right_area = [0.0; BIN_NUM];
+    let mut right_count = [0usize; BIN_NUM];
+    let mut acc_box = Aabb::empty();
+    let mut acc_count = 0;
+    for i in (1..BIN_NUM).rev() {
+        acc_box = acc_box.surrounding(&bounds[i]);
+        acc_count += counts[i];
+        right_area[i] = acc_box.surface_area();
+        right_count[i] = acc_count;
+    }
+
+    let mut best_cost = f32::MAX;
+    let mut best_bin = 0;
+    let mut acc_box = Aabb::empty();
+    let mut acc_count = 0;
+    for i in 0..BIN_NUM - 1 {
+        acc_box = acc_box.surrounding(&bounds[i]);
+        acc_count += counts[i];
+        if acc_count == 0 || right_count[i + 1] == 0 {
+            continue;
+        }
+        let cost = acc_box.surface_area() * acc_count as f32
+            + right_area[i + 1] * right_count[i + 1] as f32;
+        if cost < best_cost {
+            best_cost = cost;
+            best_bin = i;
+        }
+    }
+
+    let area = bbox.surface_area();
+    let leaf_cost = prims.len() as f32;
+    let split_cost = if area > 0.0 {
+        TRAVERSAL_COST + best_cost / area
+    } else {
+        f32::MAX
+    };
+    if prims.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+        return None;
+    }
+    if best_cost == f32::MAX {
+        let mid = prims.len() / 2;
+        prims.sort_by(|a, b| a.centroid[axis].partial_cmp(&b.centroid[axis]).unwrap());
+        return Some((axis, mid));
+    }
+
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if bin_of(&prims[i]) <= best_bin {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+    Some((axis, mid))
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut rec: Option<HitRecord> = None;
+        let mut closest_so_far = t_max;
+
+        for h in self.unbounded.iter() {
+            if let Some(hr) = h.as_ref().hit(r, t_min, closest_so_far) {
+                closest_so_far = hr.t;
+                rec = Some(hr);
+            }
+        }
+
+        self.tree
+            .traverse(r, t_min, closest_so_far, |range, closest_so_far| {
+                let mut closest_so_far = closest_so_far;
+                let mut found = None;
+                for h in self.objects[range].iter() {
+                    if let Some(hr) = h.as_ref().hit(r, t_min, closest_so_far) {
+                        closest_so_far = hr.t;
+                        found = Some(hr.t);
+                        rec = Some(hr);
+                    }
+                }
+                found
+            });
+        rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.tree.bounding_box()
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        self.objects
+            .iter()
+            .chain(self.unbounded.iter())
+            .flat_map(|h| h.lights())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bvh::*;
+    use crate::material::{Lambertian, MaterialKind};
+    use crate::random::rand_uniform;
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+
+    fn random_vec3(scale: f32) -> Vec3 {
+        scale
+            * Vec3::new(
+                rand_uniform() - 0.5,
+                rand_uniform() - 0.5,
+                rand_uniform() - 0.5,
+            )
+    }
+
+    fn random_objects() -> Vec<Box<dyn Hittable + Send + Sync>> {
+        let mat = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let mut list: Vec<Box<dyn Hittable + Send + Sync>> = vec![];
+        for _ in 0..200 {
+            list.push(Box::new(Sphere::new(
+                random_vec3(20.0),
+                0.5 * rand_uniform(),
+                mat.clone(),
+            )));
+            let p = random_vec3(20.0);
+            list.push(Box::new(Triangle::new(
+                p,
+                p + random_vec3(2.0),
+                p + random_vec3(2.0),
+                mat.clone(),
+            )));
+        }
+        list
+    }
+
+    #[test]
+    fn same_hits_as_list() {
+        fastrand::seed(1);
+        let mut world = HittableList::new();
+        world.list = random_objects();
+        fastrand::seed(1);
+        let bvh = Bvh::new(random_objects());
+        assert_eq!(bvh.len(), world.list.len());
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let r = Ray::new(random_vec3(40.0), random_vec3(1.0));
+            let a = world.hit(&r, 0.001, f32::MAX);
+            let b = bvh.hit(&r, 0.001, f32::MAX);
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.t, b.t);
+                    hits += 1;
+                }
+                (None, None) => {}
+                (a, b) => panic!("{:?} != {:?}", a, b),
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn empty() {
+        let bvh = Bvh::new(vec![]);
+        let r = Ray::new(Vec3::zero(), Vec3::new(1.0, 0.0, 0.0));
+        assert!(bvh.is_empty());
+        assert!(bvh.hit(&r, 0.001, f32::MAX).is_none());
+        assert!(bvh.bounding_box().is_none());
+    }
+
+    #[test]
+    fn coincident_centroids() {
+        let mat = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let mut list: Vec<Box<dyn Hittable + Send + Sync>> = vec![];
+        for i in 0..50 {
+            list.push(Box::new(Sphere::new(
+                Vec3::zero(),
+                1.0 + i as f32,
+                mat.clone(),
+            )));
+        }
+        let bvh = Bvh::new(list);
+        let r = Ray::new(Vec3::new(0.0, 0.0, 100.0), Vec3::new(0.0, 0.0, -1.0));
+        let hr = bvh.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_eq!(hr.t, 100.0 - 50.0);
+    }
+}
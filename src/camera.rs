@@ -10,6 +10,9 @@ pub struct Camera {
     u: Vec3,
     v: Vec3,
     lens_radius: f32,
+    // shutter open and close times
+    time0: f32,
+    time1: f32,
 }
 
 fn random_in_unit_disk() -> Vec3 {
@@ -46,9 +49,20 @@ impl Camera {
                 - focus_dist * w,
             horizontal: 2.0 * half_width * focus_dist * u,
             vertical: 2.0 * half_height * focus_dist * v,
-            u: u,
-            v: v,
+            u,
+            v,
             lens_radius: aperture / 2.0,
+            time0: 0.0,
+            time1: 0.0,
+        }
+    }
+
+    // rays are spread evenly over the time the shutter is open
+    pub fn shutter(self, open: f32, close: f32) -> Camera {
+        Camera {
+            time0: open,
+            time1: close,
+            ..self
         }
     }
 
@@ -56,9 +70,28 @@ impl Camera {
         let rd = self.lens_radius * random_in_unit_disk();
         let offset = self.u * rd.x() + self.v * rd.y();
 
-        Ray::new(
+        let time = self.time0 + rand_uniform() * (self.time1 - self.time0);
+        Ray::with_time(
             self.origin + offset,
             self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
+            time,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::camera::*;
+
+    #[test]
+    fn shutter() {
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let c = Camera::new(Vec3::zero(), -Vec3::one(), up, 40.0, 1.0, 0.0, 1.0);
+        assert_eq!(c.get_ray(0.5, 0.5).time(), 0.0);
+        let c = c.shutter(1.0, 1.5);
+        for _ in 0..100 {
+            let t = c.get_ray(0.5, 0.5).time();
+            assert!((1.0..=1.5).contains(&t));
+        }
+    }
+}
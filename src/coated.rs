@@ -0,0 +1,331 @@
+use crate::hit::HitRecord;
+use crate::material::{reflect, MaterialKind, ScatterRecord};
+use crate::medium::Medium;
+use crate::microfacet::{fresnel_dielectric, Ggx};
+use crate::random::{rand_uniform, random_unit_vector};
+use crate::ray::Ray;
+use crate::shape::Frame;
+use crate::vec3::Vec3;
+use std::f32::consts::PI;
+
+// a layer of varnish over any material, such as car paint or glossy plastic,
+// reflecting by its Fresnel reflectance and refracting the rest to the base
+#[derive(Clone, Debug)]
+pub struct Coated {
+    base: Box<MaterialKind>,
+    ref_idx: f32,
+    ggx: Ggx,
+    // cosine weighted mean Fresnel reflectance from inside the coat
+    internal: f32,
+}
+
+impl Coated {
+    // a smooth coat for roughness 0
+    pub fn new(base: MaterialKind, ref_idx: f32, roughness: f32) -> Coated {
+        let n = 256;
+        let external: f32 = (0..n)
+            .map(|i| {
+                let cos = (i as f32 + 0.5) / n as f32;
+                2.0 * cos * fresnel_dielectric(cos, ref_idx) / n as f32
+            })
+            .sum();
+        Coated {
+            base: Box::new(base),
+            ref_idx,
+            ggx: Ggx::from_roughness(roughness),
+            internal: 1.0 - (1.0 - external) / (ref_idx * ref_idx),
+        }
+    }
+
+    pub fn base(&self) -> &MaterialKind {
+        &self.base
+    }
+
+    // a mirror of a coat, over any base
+    pub fn is_smooth(&self) -> bool {
+        self.ggx.is_smooth()
+    }
+
+    // the direction inside the coat of w outside it
+    fn inward(&self, w: Vec3) -> Vec3 {
+        let (x, y) = (w.x() / self.ref_idx, w.y() / self.ref_idx);
+        Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+    }
+
+    // the direction outside of w inside, None if totally reflected
+    fn outward(&self, w: Vec3) -> Option<Vec3> {
+        let (x, y) = (w.x() * self.ref_idx, w.y() * self.ref_idx);
+        let cos2 = 1.0 - x * x - y * y;
+        if cos2 <= 0.0 {
+            None
+        } else {
+            Some(Vec3::new(x, y, cos2.sqrt()))
+        }
+    }
+
+    // the ray the base sees under the coat, coming from wo
+    fn base_ray(&self, frame: &Frame, r_in: &Ray, rec: &HitRecord, wo: Vec3) -> Ray {
+        let d = frame.world(self.inward(wo));
+        Ray::with_time(rec.p + d, -d, r_in.time())
+    }
+
+    // the base's albedo, approximated by its reflectance head on as if diffuse
+    fn albedo(&self, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+        let up = rec.normal;
+        let normal = Ray::with_time(rec.p + up, -up, r_in.time());
+        let a = PI * self.base.eval(&normal, rec, up);
+        a.max(Vec3::zero()).min(Vec3::one())
+    }
+
+    // how often light through the coat is sampled cosine weighted rather
+    // than by the base, never for delta bases
+    fn cosine_fraction(albedo: Vec3) -> f32 {
+        if albedo.x() + albedo.y() + albedo.z() > 0.0 {
+            0.5
+        } else {
+            0.0
+        }
+    }
+
+    // BSDF times cosine, and the density of scatter picking wi by other than
+    // a delta distribution
+    fn eval_pdf(
+        &self,
+        frame: &Frame,
+        r_in: &Ray,
+        rec: &HitRecord,
+        wo: Vec3,
+        wi: Vec3,
+    ) -> (Vec3, f32) {
+        let eta = self.ref_idx;
+        let p_coat = fresnel_dielectric(wo.z(), eta);
+        let mut f = Vec3::zero();
+        let mut pdf = 0.0;
+        if !self.ggx.is_smooth() && wi.z() > 0.0 {
+            let h = (wo + wi).unit_vector();
+            if wo.dot(h) > 0.0 {
+                let fresnel = fresnel_dielectric(wo.dot(h), eta);
+                f += Vec3::one() * (fresnel * self.ggx.d(h) * self.ggx.g2(wo, wi) / (4.0 * wo.z()));
+                pdf += p_coat * self.ggx.pdf_visible(wo, h) / (4.0 * wo.dot(h));
+            }
+        }
+
+        let base_ray = self.base_ray(frame, r_in, rec, wo);
+        let transmitted = 1.0 - p_coat;
+        let albedo = self.albedo(r_in, rec);
+        let cosine = Self::cosine_fraction(albedo);
+        if wi.z() > 0.0 {
+            // radiance shrinks as the solid angle widens out of the coat
+            let inside = self.inward(wi);
+            let d = frame.world(inside);
+            let base_f = self.base.eval(&base_ray, rec, d);
+            let base_pdf = self.base.pdf(&base_ray, rec, d);
+            let widening = wi.z() / (eta * eta * inside.z());
+            let through = transmitted * (1.0 - fresnel_dielectric(wi.z(), eta)) * widening;
+            let bounces = Vec3::one() - self.internal * albedo;
+            f += through * base_f / bounces;
+            pdf += transmitted * ((1.0 - cosine) * base_pdf * widening + cosine * wi.z() / PI);
+        } else {
+            // on into the solid under the coat
+            let d = frame.world(wi);
+            f += transmitted * self.base.eval(&base_ray, rec, d);
+            pdf += transmitted * (1.0 - cosine) * self.base.pdf(&base_ray, rec, d);
+        }
+        (f, pdf)
+    }
+
+    fn local(&self, r_in: &Ray, rec: &HitRecord) -> (Frame, Vec3) {
+        let frame = Frame::new(Vec3::zero(), rec.normal);
+        (frame, frame.local(-r_in.direction().unit_vector()))
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, wo) = self.local(r_in, rec);
+        // seen from under the coat
+        if wo.z() <= 0.0 {
+            return self.base.scatter(r_in, rec);
+        }
+        let eta = self.ref_idx;
+        let p_coat = fresnel_dielectric(wo.z(), eta);
+        let wi = if rand_uniform() < p_coat {
+            if self.ggx.is_smooth() {
+                let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+                return Some(ScatterRecord {
+                    ray: Ray::with_time(rec.p, frame.world(wi), r_in.time()),
+                    attenuation: Vec3::one(),
+                    pdf: None,
+                });
+            }
+            let h = self.ggx.sample_visible(wo, rand_uniform(), rand_uniform());
+            let wi = reflect(-wo, h);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else if rand_uniform() < Self::cosine_fraction(self.albedo(r_in, rec)) {
+            let d = Vec3::new(0.0, 0.0, 1.0) + random_unit_vector();
+            if d.squared_length() < 1e-8 {
+                Vec3::new(0.0, 0.0, 1.0)
+            } else {
+                d.unit_vector()
+            }
+        } else {
+            let base_ray = self.base_ray(&frame, r_in, rec, wo);
+            let s = self.base.scatter(&base_ray, rec)?;
+            let inside = frame.local(s.ray.direction().unit_vector());
+            let wi = if inside.z() > 0.0 {
+                self.outward(inside)?
+            } else {
+                inside
+            };
+            if s.pdf.is_none() {
+                // the coat's choice cancels its transmittance on the way in,
+                // and a delta base reflects back exactly what the coat does
+                let mut attenuation = s.attenuation;
+                if wi.z() > 0.0 {
+                    let fresnel = fresnel_dielectric(wi.z(), eta);
+                    let bounces = Vec3::one() - fresnel * s.attenuation;
+                    attenuation = (1.0 - fresnel) * attenuation / bounces;
+                }
+                return Some(ScatterRecord {
+                    ray: Ray::with_time(rec.p, frame.world(wi), r_in.time()),
+                    attenuation,
+                    pdf: None,
+                });
+            }
+            wi
+        };
+        let (f, pdf) = self.eval_pdf(&frame, r_in, rec, wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord {
+            ray: Ray::with_time(rec.p, frame.world(wi), r_in.time()),
+            attenuation: f / pdf,
+            pdf: Some(pdf),
+        })
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let (frame, wo) = self.local(r_in, rec);
+        if wo.z() <= 0.0 {
+            return self.base.pdf(r_in, rec, direction);
+        }
+        let wi = frame.local(direction.unit_vector());
+        self.eval_pdf(&frame, r_in, rec, wo, wi).1
+    }
+
+    // BSDF times cosine towards direction
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let (frame, wo) = self.local(r_in, rec);
+        if wo.z() <= 0.0 {
+            return self.base.eval(r_in, rec, direction);
+        }
+        let wi = frame.local(direction.unit_vector());
+        self.eval_pdf(&frame, r_in, rec, wo, wi).0
+    }
+
+    pub fn interior(&self) -> Option<&dyn Medium> {
+        self.base.interior()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coated::*;
+    use crate::material::{Lambertian, Metal};
+    use float_eq::assert_float_eq;
+
+    // checks scatter against pdf and eval, returning the mean attenuation
+    // and the fraction of delta samples
+    fn check(m: Coated, wo: Vec3) -> (Vec3, f32) {
+        let kind = MaterialKind::Coated(m);
+        let mut rec = HitRecord::new(&kind);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        let r = Ray::new(wo, -wo);
+        let n = 50000;
+        let mut mean = Vec3::zero();
+        let mut integral = 0.0;
+        let mut smooth = 0;
+        let mut delta = 0;
+        for _ in 0..n {
+            // half uniform, half from the material itself
+            let d = if fastrand::bool() {
+                Some(random_unit_vector())
+            } else {
+                kind.scatter(&r, &rec)
+                    .filter(|s| s.pdf.is_some())
+                    .map(|s| s.ray.direction().unit_vector())
+            };
+            if let Some(d) = d {
+                let pdf = kind.pdf(&r, &rec, d);
+                integral += pdf / (0.5 / (4.0 * PI) + 0.5 * pdf);
+            }
+            if let Some(s) = kind.scatter(&r, &rec) {
+                match s.pdf {
+                    Some(pdf) => {
+                        let d = s.ray.direction();
+                        assert_float_eq!(pdf, kind.pdf(&r, &rec, d), r2nd <= 1e-3);
+                        let f = kind.eval(&r, &rec, d);
+                        assert!((f / pdf - s.attenuation).length() < 1e-3);
+                        smooth += 1;
+                    }
+                    None => delta += 1,
+                }
+                mean += s.attenuation;
+            }
+        }
+        let integral = integral / n as f32;
+        assert_float_eq!(integral, smooth as f32 / n as f32, abs <= 0.02);
+        (mean / n as f32, delta as f32 / n as f32)
+    }
+
+    #[test]
+    fn smooth_coat() {
+        fastrand::seed(8);
+        let wo = Vec3::new(0.6, 0.8, 0.0);
+        let reflectance = fresnel_dielectric(0.8, 1.5);
+        // over black only the coat reflects, picked by its reflectance
+        let black = Coated::new(
+            MaterialKind::Lambertian(Lambertian::new(Vec3::zero())),
+            1.5,
+            0.0,
+        );
+        let (mean, delta) = check(black, wo);
+        assert_float_eq!(mean.x(), reflectance, abs <= 0.01);
+        assert_float_eq!(delta, reflectance, abs <= 0.01);
+        // a white base loses little to the light trapped under the coat
+        let white = Coated::new(
+            MaterialKind::Lambertian(Lambertian::new(Vec3::one())),
+            1.5,
+            0.0,
+        );
+        let (mean, _) = check(white, wo);
+        assert!(mean.x() > 0.95 && mean.x() < 1.02, "{:?}", mean);
+        // and a mirror nothing
+        let mirror = Coated::new(MaterialKind::Metal(Metal::new(Vec3::one(), 0.0)), 1.5, 0.0);
+        let (mean, delta) = check(mirror, wo);
+        assert_float_eq!(delta, 1.0, abs <= 0.0);
+        assert_float_eq!(mean.x(), 1.0, abs <= 1e-4);
+    }
+
+    #[test]
+    fn rough_coat() {
+        fastrand::seed(9);
+        let wo = Vec3::new(-0.6, 0.8, 0.0);
+        let red = Vec3::new(0.8, 0.1, 0.1);
+        let base = MaterialKind::Lambertian(Lambertian::new(red));
+        let (rough, delta) = check(Coated::new(base.clone(), 1.5, 0.4), wo);
+        assert_float_eq!(delta, 0.0, abs <= 0.0);
+        let (smooth, _) = check(Coated::new(base, 1.5, 0.0), wo);
+        // the same light, only spread differently
+        assert!((rough - smooth).length() < 0.03, "{:?} {:?}", rough, smooth);
+        // darker than the bare base where it is bright, lighter where dark
+        let reflectance = fresnel_dielectric(0.8, 1.5);
+        assert!(
+            rough.x() < red.x() && rough.z() > reflectance,
+            "{:?}",
+            rough
+        );
+    }
+}
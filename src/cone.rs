@@ -0,0 +1,183 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::shape::{azimuth, disk_bbox, solve_quadratic, Frame};
+use crate::vec3::Vec3;
+
+// closed cone from a base disk to apex; u goes around the axis, v runs from
+// base to apex on the side and out from the center on the base
+#[derive(Clone, Debug)]
+pub struct Cone {
+    frame: Frame,
+    radius: f32,
+    height: f32,
+    material: MaterialKind,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: f32, mat: MaterialKind) -> Cone {
+        Cone {
+            frame: Frame::new(base, apex - base),
+            radius,
+            height: (apex - base).length(),
+            material: mat,
+        }
+    }
+}
+
+impl Cone {
+    // calls f with the parameter, local point and local outward normal of
+    // every crossing of the local ray l, and whether it is on the base
+    fn crossings(&self, l: &Ray, mut f: impl FnMut(f32, Vec3, Vec3, bool)) {
+        let (o, d) = (l.origin(), l.direction());
+        let h = self.height;
+        let k2 = (self.radius / h) * (self.radius / h);
+
+        // the side: x^2 + y^2 = k^2 (h - z)^2 between base and apex
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let half_b = o.x() * d.x() + o.y() * d.y() + k2 * (h - o.z()) * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * (h - o.z()) * (h - o.z());
+        let roots = match solve_quadratic(a, half_b, c) {
+            Some((t0, t1)) => [Some(t0), Some(t1)],
+            // parallel to a line of the side, which it meets at most once
+            None if a == 0.0 && half_b != 0.0 => [Some(-c / (2.0 * half_b)), None],
+            None => [None, None],
+        };
+        for &t in roots.iter().flatten() {
+            let p = l.point_at_parameter(t);
+            if (0.0..=h).contains(&p.z()) {
+                let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                // the apex has no normal of its own
+                let n = if rho > 0.0 {
+                    Vec3::new(p.x(), p.y(), rho * self.radius / h).unit_vector()
+                } else {
+                    Vec3::new(0.0, 0.0, 1.0)
+                };
+                f(t, p, n, false);
+            }
+        }
+
+        // the base
+        if d.z() != 0.0 {
+            let t = -o.z() / d.z();
+            let p = l.point_at_parameter(t);
+            if p.x() * p.x() + p.y() * p.y() <= self.radius * self.radius {
+                f(t, p, Vec3::new(0.0, 0.0, -1.0), true);
+            }
+        }
+    }
+
+    fn record(&self, r: &Ray, t: f32, p: Vec3, normal: Vec3, base: bool) -> HitRecord<'_> {
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.normal = self.frame.world(normal);
+        rec.u = azimuth(p);
+        rec.v = if base {
+            (p.x() * p.x() + p.y() * p.y()).sqrt() / self.radius
+        } else {
+            p.z() / self.height
+        };
+        rec
+    }
+}
+
+impl Hittable for Cone {
+    // normals point out of the cone, also when the ray leaves it from inside
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut found = None;
+        self.crossings(&self.frame.local_ray(r), |t, p, n, base| {
+            if t_min < t && t < closest {
+                closest = t;
+                found = Some((t, p, n, base));
+            }
+        });
+        let (t, p, n, base) = found?;
+        Some(self.record(r, t, p, n, base))
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        let mut hits = vec![];
+        self.crossings(&self.frame.local_ray(r), |t, p, n, base| {
+            if t_min < t && t < t_max {
+                hits.push(self.record(r, t, p, n, base));
+            }
+        });
+        hits.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+        hits
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let f = &self.frame;
+        let apex = f.origin + self.height * f.z;
+        let b = disk_bbox(f.origin, f.z, self.radius);
+        Some(b.surrounding(&Aabb::new(apex, apex)).padded(0.0001))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cone::*;
+    use crate::material::Lambertian;
+    use float_eq::assert_float_eq;
+
+    fn cone() -> Cone {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        Cone::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), 1.0, m)
+    }
+
+    #[test]
+    fn side() {
+        let c = cone();
+        let r = Ray::new(Vec3::new(5.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.5, abs <= 1e-5);
+        let s = 0.5f32.sqrt();
+        assert!((rec.normal - Vec3::new(s, s, 0.0)).length() < 1e-5);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-5);
+
+        // parallel to the line of the side through (1, 0, 0) and the apex,
+        // into the base and out through the side
+        let d = Vec3::new(1.0, 1.0, 0.0);
+        let rec = c.hit(&Ray::new(Vec3::new(-0.5, -1.0, 0.0), d), 0.001, f32::MAX);
+        let rec = rec.unwrap();
+        assert!((rec.p - Vec3::new(0.5, 0.0, 0.0)).length() < 1e-5);
+        assert_float_eq!(rec.normal.y(), -1.0, abs <= 1e-6);
+        let rec = c.hit(&Ray::new(rec.p, d), 0.001, f32::MAX).unwrap();
+        assert!((rec.p - Vec3::new(0.75, 0.25, 0.0)).length() < 1e-5);
+        assert!((rec.normal - Vec3::new(s, s, 0.0)).length() < 1e-5);
+
+        let r = Ray::new(Vec3::new(5.0, 1.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(c.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn along_axis() {
+        let c = cone();
+        let r = Ray::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 2.0, abs <= 1e-5);
+        assert_float_eq!(rec.normal.y(), 1.0, abs <= 1e-6);
+
+        let r = Ray::new(Vec3::new(0.0, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 3.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), -1.0, abs <= 1e-6);
+
+        // from inside, out through the side
+        let r = Ray::new(Vec3::new(0.0, 0.25, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 0.75, abs <= 1e-5);
+        assert!(rec.normal.z() > 0.0);
+    }
+
+    #[test]
+    fn bounding_box() {
+        let c = cone();
+        let b = c.bounding_box().unwrap();
+        assert!((b.min - Vec3::new(-1.0, 0.0, -1.0)).length() < 1e-3);
+        assert!((b.max - Vec3::new(1.0, 1.0, 1.0)).length() < 1e-3);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // the first object without the second
+    Difference,
+}
+
+impl CsgOp {
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            CsgOp::Union => a || b,
+            CsgOp::Intersection => a && b,
+            CsgOp::Difference => a && !b,
+        }
+    }
+}
+
+// combination of two closed objects with outward normals, such as spheres,
+// boxes, the other solids or further Csg nodes; each part of the surface
+// keeps the material of the object it comes from
+pub struct Csg {
+    op: CsgOp,
+    a: Box<dyn Hittable + Send + Sync>,
+    b: Box<dyn Hittable + Send + Sync>,
+    bbox: Option<Aabb>,
+}
+
+impl Csg {
+    pub fn new(
+        op: CsgOp,
+        a: Box<dyn Hittable + Send + Sync>,
+        b: Box<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        let (box_a, box_b) = (a.bounding_box(), b.bounding_box());
+        let bbox = match op {
+            CsgOp::Union => box_a.zip(box_b).map(|(a, b)| a.surrounding(&b)),
+            CsgOp::Intersection => match (box_a, box_b) {
+                (Some(a), Some(b)) => Some(Aabb {
+                    min: a.min.max(b.min),
+                    max: a.max.min(b.max),
+                }),
+                (a, b) => a.or(b),
+            },
+            CsgOp::Difference => box_a,
+        };
+        Csg { op, a, b, bbox }
+    }
+
+    pub fn union(a: Box<dyn Hittable + Send + Sync>, b: Box<dyn Hittable + Send + Sync>) -> Csg {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(
+        a: Box<dyn Hittable + Send + Sync>,
+        b: Box<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(
+        a: Box<dyn Hittable + Send + Sync>,
+        b: Box<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.hits(r, t_min, t_max).into_iter().next()
+    }
+
+    // walks the crossings of both objects along the whole line, starting
+    // outside of both, and keeps those where the combination is entered or left
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        if let Some(b) = self.bbox {
+            if !b.hit(r, t_min, t_max) {
+                return vec![];
+            }
+        }
+        let a = self.a.hits(r, -f32::MAX, f32::MAX);
+        let b = self.b.hits(r, -f32::MAX, f32::MAX);
+        let mut events: Vec<(HitRecord, bool)> = a
+            .into_iter()
+            .map(|rec| (rec, true))
+            .chain(b.into_iter().map(|rec| (rec, false)))
+            .collect();
+        events.sort_by(|x, y| x.0.t.partial_cmp(&y.0.t).unwrap());
+
+        let d = r.direction();
+        let (mut in_a, mut in_b) = (false, false);
+        let mut inside = false;
+        let mut hits = vec![];
+        for (mut rec, from_a) in events {
+            if rec.t >= t_max {
+                break;
+            }
+            // by the normal rather than by counting, so that a tangent
+            // crossing reported once can't invert the rest of the ray
+            let entering = rec.normal.dot(d) < 0.0;
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let now = self.op.inside(in_a, in_b);
+            if now == inside {
+                continue;
+            }
+            inside = now;
+            if rec.t > t_min {
+                // outwards from the combination, e.g. into the hole cut by b
+                if (rec.normal.dot(d) < 0.0) != now {
+                    rec.normal = -rec.normal;
+                }
+                hits.push(rec);
+            }
+        }
+        hits
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+}
+
+// so that nodes nest without naming the box type
+impl From<Csg> for Box<dyn Hittable + Send + Sync> {
+    fn from(c: Csg) -> Self {
+        Box::new(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::box_shape::BoxShape;
+    use crate::csg::*;
+    use crate::cylinder::Cylinder;
+    use crate::material::{Lambertian, MaterialKind};
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+    use float_eq::assert_float_eq;
+
+    fn mat() -> MaterialKind {
+        MaterialKind::Lambertian(Lambertian::new(Vec3::one()))
+    }
+
+    fn sphere(x: f32, radius: f32) -> Box<dyn Hittable + Send + Sync> {
+        Box::new(Sphere::new(Vec3::new(x, 0.0, 0.0), radius, mat()))
+    }
+
+    #[test]
+    fn lens() {
+        // the overlap of two unit spheres, 1 apart, spans x in [0, 1]
+        let lens = Csg::intersection(sphere(1.0, 1.0), sphere(0.0, 1.0));
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hits = lens.hits(&r, 0.001, f32::MAX);
+        assert_eq!(hits.len(), 2);
+        assert_float_eq!(hits[0].t, 5.0, abs <= 1e-5);
+        assert_float_eq!(hits[0].normal.x(), -1.0, abs <= 1e-5);
+        assert_float_eq!(hits[1].t, 6.0, abs <= 1e-5);
+        assert_float_eq!(hits[1].normal.x(), 1.0, abs <= 1e-5);
+
+        // from inside, the exit
+        let r = Ray::new(Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = lens.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 0.75f32.sqrt(), abs <= 1e-5);
+        assert!(rec.normal.y() > 0.0);
+
+        // within one sphere only
+        let r = Ray::new(Vec3::new(-0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(lens.hit(&r, 0.001, f32::MAX).is_none());
+        let b = lens.bounding_box().unwrap();
+        assert_float_eq!(b.min.x(), 0.0, abs <= 1e-6);
+        assert_float_eq!(b.max.x(), 1.0, abs <= 1e-6);
+    }
+
+    // only finds the nearest hit, like primitives without their own hits
+    struct Nearest(Sphere);
+
+    impl Hittable for Nearest {
+        fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+            self.0.hit(r, t_min, t_max)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.0.bounding_box()
+        }
+    }
+
+    #[test]
+    fn stepping() {
+        let s = Nearest(Sphere::new(Vec3::zero(), 1.0, mat()));
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let ts: Vec<f32> = s.hits(&r, 0.001, f32::MAX).iter().map(|h| h.t).collect();
+        assert_eq!(ts, vec![4.0, 6.0]);
+
+        let lens = Csg::intersection(sphere(1.0, 1.0), Box::new(s));
+        let hits = lens.hits(&r, 0.001, f32::MAX);
+        assert_eq!(hits.len(), 2);
+        assert_float_eq!(hits[0].t, 5.0, abs <= 1e-5);
+        assert_float_eq!(hits[1].t, 6.0, abs <= 1e-5);
+    }
+
+    #[test]
+    fn union() {
+        let u = Csg::union(sphere(0.0, 1.0), sphere(1.0, 1.0));
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hits = u.hits(&r, 0.001, f32::MAX);
+        // no surfaces inside the union
+        assert_eq!(hits.len(), 2);
+        assert_float_eq!(hits[0].t, 4.0, abs <= 1e-5);
+        assert_float_eq!(hits[1].t, 7.0, abs <= 1e-5);
+        let hits = u.hits(&r, 0.001, 5.0);
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn difference() {
+        // a bite out of the +x side
+        let bitten = Csg::difference(sphere(0.0, 1.0), sphere(1.0, 0.5));
+        let r = Ray::new(Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = bitten.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.5, abs <= 1e-5);
+        // out of the solid, into the bite
+        assert_float_eq!(rec.normal.x(), 1.0, abs <= 1e-5);
+        assert_eq!(bitten.hits(&r, 0.001, f32::MAX).len(), 2);
+
+        // a hole drilled through a block, and the block nested in a union
+        let block: Box<dyn Hittable + Send + Sync> = Box::new(BoxShape::new(
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::one(),
+            mat(),
+        ));
+        let drill = Box::new(Cylinder::new(
+            Vec3::new(0.0, -2.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            0.5,
+            mat(),
+        ));
+        let part = Csg::union(Csg::difference(block, drill).into(), sphere(5.0, 1.0));
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let r = Ray::new(Vec3::new(0.0, 5.0, 0.0), down);
+        assert!(part.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.2, 5.0, 0.0), down);
+        assert!(part.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.7, 5.0, 0.0), down);
+        let rec = part.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.0, abs <= 1e-5);
+        assert_float_eq!(rec.normal.y(), 1.0, abs <= 1e-5);
+
+        // across the hole, whose walls face into it
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hits = part.hits(&r, 0.001, f32::MAX);
+        let ts: Vec<f32> = hits.iter().map(|h| h.t).collect();
+        let expected = [4.0, 4.5, 5.5, 6.0, 9.0, 11.0];
+        assert_eq!(ts.len(), expected.len());
+        for (t, e) in ts.iter().zip(expected.iter()) {
+            assert_float_eq!(*t, *e, abs <= 1e-4);
+        }
+        assert_float_eq!(hits[1].normal.x(), 1.0, abs <= 1e-5);
+        assert_float_eq!(hits[2].normal.x(), -1.0, abs <= 1e-5);
+    }
+}
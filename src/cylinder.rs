@@ -0,0 +1,188 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::shape::{azimuth, disk_bbox, solve_quadratic, Frame};
+use crate::vec3::Vec3;
+
+// closed cylinder from base to top; u goes around the axis, v runs from base
+// to top on the side and out from the center on the caps
+#[derive(Clone, Debug)]
+pub struct Cylinder {
+    frame: Frame,
+    radius: f32,
+    height: f32,
+    material: MaterialKind,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: f32, mat: MaterialKind) -> Cylinder {
+        Cylinder {
+            frame: Frame::new(base, top - base),
+            radius,
+            height: (top - base).length(),
+            material: mat,
+        }
+    }
+}
+
+impl Cylinder {
+    // calls f with the parameter, local point and local outward normal of
+    // every crossing of the local ray l, and whether it is on a cap
+    fn crossings(&self, l: &Ray, mut f: impl FnMut(f32, Vec3, Vec3, bool)) {
+        let (o, d) = (l.origin(), l.direction());
+
+        // the side; rays along the axis only meet the caps
+        let a = d.x() * d.x() + d.y() * d.y();
+        let half_b = o.x() * d.x() + o.y() * d.y();
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, half_b, c) {
+            for &t in [t0, t1].iter() {
+                let p = l.point_at_parameter(t);
+                if (0.0..=self.height).contains(&p.z()) {
+                    f(t, p, Vec3::new(p.x(), p.y(), 0.0) / self.radius, false);
+                }
+            }
+        }
+
+        // the caps
+        if d.z() != 0.0 {
+            for &(z, sign) in [(0.0, -1.0), (self.height, 1.0)].iter() {
+                let t = (z - o.z()) / d.z();
+                let p = l.point_at_parameter(t);
+                if p.x() * p.x() + p.y() * p.y() <= self.radius * self.radius {
+                    f(t, p, Vec3::new(0.0, 0.0, sign), true);
+                }
+            }
+        }
+    }
+
+    fn record(&self, r: &Ray, t: f32, p: Vec3, normal: Vec3, cap: bool) -> HitRecord<'_> {
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.normal = self.frame.world(normal);
+        rec.u = azimuth(p);
+        rec.v = if cap {
+            (p.x() * p.x() + p.y() * p.y()).sqrt() / self.radius
+        } else {
+            p.z() / self.height
+        };
+        rec
+    }
+}
+
+impl Hittable for Cylinder {
+    // normals point out of the cylinder, also when the ray leaves it from inside
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut found = None;
+        self.crossings(&self.frame.local_ray(r), |t, p, n, cap| {
+            if t_min < t && t < closest {
+                closest = t;
+                found = Some((t, p, n, cap));
+            }
+        });
+        let (t, p, n, cap) = found?;
+        Some(self.record(r, t, p, n, cap))
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        let mut hits = vec![];
+        self.crossings(&self.frame.local_ray(r), |t, p, n, cap| {
+            if t_min < t && t < t_max {
+                hits.push(self.record(r, t, p, n, cap));
+            }
+        });
+        hits.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+        hits
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let f = &self.frame;
+        let top = f.origin + self.height * f.z;
+        let b = disk_bbox(f.origin, f.z, self.radius);
+        Some(b.surrounding(&disk_bbox(top, f.z, self.radius)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cylinder::*;
+    use crate::material::Lambertian;
+    use float_eq::assert_float_eq;
+
+    fn cylinder() -> Cylinder {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        Cylinder::new(Vec3::zero(), Vec3::new(0.0, 2.0, 0.0), 1.0, m)
+    }
+
+    #[test]
+    fn side() {
+        let c = cylinder();
+        let r = Ray::new(Vec3::new(5.0, 1.5, 0.0), Vec3::new(-2.0, 0.0, 0.0));
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 2.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.x(), 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.75, abs <= 1e-6);
+
+        // from inside, the far wall with its outward normal
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.z(), 1.0, abs <= 1e-6);
+
+        // passing above the top
+        let r = Ray::new(Vec3::new(5.0, 2.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(c.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn along_axis() {
+        let c = cylinder();
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        let r = Ray::new(Vec3::new(0.5, 5.0, 0.0), down);
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 3.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-6);
+
+        // from inside, out through the bottom cap
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), down);
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), -1.0, abs <= 1e-6);
+
+        // on the side's surface, where it meets the rim of the cap
+        let r = Ray::new(Vec3::new(0.0, 5.0, 1.0), down);
+        let rec = c.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 3.0, abs <= 1e-6);
+
+        let r = Ray::new(Vec3::new(0.0, 5.0, 1.01), down);
+        assert!(c.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn bounding_box() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let c = Cylinder::new(Vec3::zero(), Vec3::new(2.0, 2.0, 0.0), 1.0, m);
+        let b = c.bounding_box().unwrap();
+        let s = 0.5f32.sqrt();
+        assert!((b.min - Vec3::new(-s, -s, -1.0)).length() < 1e-5);
+        assert!((b.max - Vec3::new(2.0 + s, 2.0 + s, 1.0)).length() < 1e-5);
+
+        fastrand::seed(3);
+        for _ in 0..200 {
+            let o = 5.0 * crate::random::random_unit_vector();
+            let r = Ray::new(o, Vec3::new(1.0, 1.0, 0.0) - o);
+            if let Some(rec) = c.hit(&r, 0.001, f32::MAX) {
+                let p = rec.p;
+                assert!(p.x() >= b.min.x() - 1e-4 && p.x() <= b.max.x() + 1e-4);
+                assert!(p.y() >= b.min.y() - 1e-4 && p.y() <= b.max.y() + 1e-4);
+                assert!(p.z() >= b.min.z() - 1e-4 && p.z() <= b.max.z() + 1e-4);
+                assert_float_eq!(rec.normal.length(), 1.0, abs <= 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
+use crate::material::MaterialKind;
+use crate::random::rand_uniform;
+use crate::ray::Ray;
+use crate::shape::{azimuth, disk_bbox, Frame};
+use crate::vec3::Vec3;
+
+// flat disk; u goes around the center and v out from it
+#[derive(Clone, Debug)]
+pub struct Disk {
+    frame: Frame,
+    radius: f32,
+    material: MaterialKind,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, mat: MaterialKind) -> Disk {
+        Disk {
+            frame: Frame::new(center, normal),
+            radius,
+            material: mat,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let l = self.frame.local_ray(r);
+        let dz = l.direction().z();
+        if dz == 0.0 {
+            return None;
+        }
+        let t = -l.origin().z() / dz;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let p = l.point_at_parameter(t);
+        let rho2 = p.x() * p.x() + p.y() * p.y();
+        if rho2 > self.radius * self.radius {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.u = azimuth(p);
+        rec.v = rho2.sqrt() / self.radius;
+        rec.normal = if dz < 0.0 {
+            self.frame.z
+        } else {
+            -self.frame.z
+        };
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(disk_bbox(self.frame.origin, self.frame.z, self.radius).padded(0.0001))
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        match self.material {
+            MaterialKind::DiffuseLight(_) => vec![Box::new(self.clone())],
+            _ => vec![],
+        }
+    }
+}
+
+impl Light for Disk {
+    fn sample(&self) -> HitRecord<'_> {
+        let v = rand_uniform().sqrt();
+        let u = rand_uniform();
+        let (s, c) = (2.0 * std::f32::consts::PI * u).sin_cos();
+        let mut rec = HitRecord::new(&self.material);
+        rec.p = self.frame.origin + self.frame.world(self.radius * v * Vec3::new(c, s, 0.0));
+        rec.normal = self.frame.z;
+        rec.u = u;
+        rec.v = v;
+        rec
+    }
+
+    fn area(&self) -> f32 {
+        std::f32::consts::PI * self.radius * self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::disk::*;
+    use crate::material::DiffuseLight;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn hit() {
+        let m = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let d = Disk::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 2.0, m);
+
+        let r = Ray::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        let rec = d.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 0.5, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), -1.0, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-6);
+
+        let r = Ray::new(Vec3::new(1.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_float_eq!(
+            d.hit(&r, 0.001, f32::MAX).unwrap().normal.y(),
+            1.0,
+            abs <= 1e-6
+        );
+
+        let r = Ray::new(Vec3::new(2.5, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(d.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(d.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn sample() {
+        fastrand::seed(1);
+        let m = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let d = Disk::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(1.0, 1.0, 0.0), 0.5, m);
+        let b = d.bounding_box().unwrap();
+        for _ in 0..100 {
+            let p = d.sample().p - Vec3::new(1.0, 2.0, 3.0);
+            assert!(p.length() <= 0.5 + 1e-6);
+            assert_float_eq!(p.dot(Vec3::new(1.0, 1.0, 0.0)), 0.0, abs <= 1e-5);
+            assert!(b.hit(
+                &Ray::new(p + Vec3::new(1.0, 2.0, 10.0), Vec3::new(0.0, 0.0, -1.0)),
+                0.0,
+                f32::MAX
+            ));
+        }
+        assert_float_eq!(d.area(), std::f32::consts::PI * 0.25, abs <= 1e-6);
+    }
+}
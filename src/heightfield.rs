@@ -0,0 +1,325 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::triangle::intersect;
+use crate::vec3::Vec3;
+
+// terrain over a regular grid of height samples, each cell split into two
+// triangles; samples run along x, rows along z. (u, v) spans [0, 1] over the
+// grid with v = 1 at the first row, so that an image texture of the same
+// picture lines up. Normals are smooth and face the ray, as for triangles
+#[derive(Clone, Debug)]
+pub struct Heightfield {
+    nx: usize,
+    nz: usize,
+    // sample positions, row by row
+    points: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    min: Vec3,
+    // size of a cell along x and z
+    dx: f32,
+    dz: f32,
+    bbox: Aabb,
+    material: MaterialKind,
+}
+
+impl Heightfield {
+    // heights in [0, 1] are scaled by size.y, and the grid spans size.x and
+    // size.z from min; panics unless there are at least 2 by 2 samples and
+    // size.x and size.z are positive
+    pub fn new(
+        heights: &[f32],
+        nx: usize,
+        nz: usize,
+        min: Vec3,
+        size: Vec3,
+        mat: MaterialKind,
+    ) -> Heightfield {
+        assert!(nx >= 2 && nz >= 2);
+        assert!(size.x() > 0.0 && size.z() > 0.0);
+        assert_eq!(heights.len(), nx * nz);
+        let dx = size.x() / (nx - 1) as f32;
+        let dz = size.z() / (nz - 1) as f32;
+        let mut points = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                points.push(
+                    min + Vec3::new(i as f32 * dx, heights[j * nx + i] * size.y(), j as f32 * dz),
+                );
+            }
+        }
+        // by central differences, one sided at the edges
+        let y = |i: usize, j: usize| points[j * nx + i].y();
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let slope_x = (y(i1, j) - y(i0, j)) / ((i1 - i0) as f32 * dx);
+                let slope_z = (y(i, j1) - y(i, j0)) / ((j1 - j0) as f32 * dz);
+                normals.push(Vec3::new(-slope_x, 1.0, -slope_z).unit_vector());
+            }
+        }
+        let bbox = Aabb::from_points(&points).padded(0.0001);
+        Heightfield {
+            nx,
+            nz,
+            points,
+            normals,
+            min,
+            dx,
+            dz,
+            bbox,
+            material: mat,
+        }
+    }
+
+    // one sample per pixel of a grayscale image, 8 or 16 bits deep; the top
+    // row of the image is at min.z
+    pub fn load(
+        image_file_path: &str,
+        min: Vec3,
+        size: Vec3,
+        mat: MaterialKind,
+    ) -> Result<Heightfield, image::ImageError> {
+        let image = image::open(image_file_path)?;
+        let color = image.color();
+        // 8 bit images widened to 16 bits don't reach 1
+        let (w, h, heights): (u32, u32, Vec<f32>) =
+            if color.bytes_per_pixel() == 2 * color.channel_count() {
+                let gray = image.to_luma16();
+                let heights = gray.pixels().map(|p| p[0] as f32 / 65535.0).collect();
+                (gray.width(), gray.height(), heights)
+            } else {
+                let gray = image.to_luma8();
+                let heights = gray.pixels().map(|p| p[0] as f32 / 255.0).collect();
+                (gray.width(), gray.height(), heights)
+            };
+        if w < 2 || h < 2 {
+            return Err(image::ImageError::Parameter(
+                image::error::ParameterError::from_kind(image::error::ParameterErrorKind::Generic(
+                    "a heightfield needs at least 2 by 2 pixels".to_string(),
+                )),
+            ));
+        }
+        Ok(Heightfield::new(
+            &heights, w as usize, h as usize, min, size, mat,
+        ))
+    }
+
+    // the nearer hit with the two triangles of cell (i, j)
+    fn hit_cell(
+        &self,
+        r: &Ray,
+        i: usize,
+        j: usize,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let index = |i: usize, j: usize| j * self.nx + i;
+        let corners = [
+            index(i, j),
+            index(i + 1, j),
+            index(i + 1, j + 1),
+            index(i, j + 1),
+        ];
+        let mut best: Option<(f32, [usize; 3], f32, f32)> = None;
+        for tri in [[0, 1, 2], [0, 2, 3]].iter() {
+            let ids = [corners[tri[0]], corners[tri[1]], corners[tri[2]]];
+            let points = [
+                self.points[ids[0]],
+                self.points[ids[1]],
+                self.points[ids[2]],
+            ];
+            let t_max = best.map_or(t_max, |b| b.0);
+            if let Some((t, wb, wc)) = intersect(&points, r, t_min, t_max, false) {
+                best = Some((t, ids, wb, wc));
+            }
+        }
+        let (t, ids, wb, wc) = best?;
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.u = ((rec.p.x() - self.min.x()) / (self.dx * (self.nx - 1) as f32)).clamp(0.0, 1.0);
+        rec.v =
+            1.0 - ((rec.p.z() - self.min.z()) / (self.dz * (self.nz - 1) as f32)).clamp(0.0, 1.0);
+        let [na, nb, nc] = [
+            self.normals[ids[0]],
+            self.normals[ids[1]],
+            self.normals[ids[2]],
+        ];
+        let normal = ((1.0 - wb - wc) * na + wb * nb + wc * nc).unit_vector();
+        rec.normal = if normal.dot(r.direction()) < 0.0 {
+            normal
+        } else {
+            -normal
+        };
+        Some(rec)
+    }
+
+    // lowest and highest sample around cell (i, j)
+    fn cell_range(&self, i: usize, j: usize) -> (f32, f32) {
+        let ys = [
+            self.points[j * self.nx + i].y(),
+            self.points[j * self.nx + i + 1].y(),
+            self.points[(j + 1) * self.nx + i].y(),
+            self.points[(j + 1) * self.nx + i + 1].y(),
+        ];
+        let low = ys.iter().cloned().fold(f32::MAX, f32::min);
+        let high = ys.iter().cloned().fold(f32::MIN, f32::max);
+        (low, high)
+    }
+}
+
+// traversal of the cells of one axis: the current cell, the step between
+// cells, the t of the next cell boundary and the t between boundaries
+struct Axis {
+    cell: isize,
+    step: isize,
+    t_next: f32,
+    t_delta: f32,
+}
+
+impl Axis {
+    fn new(origin: f32, direction: f32, t: f32, min: f32, size: f32, cells: usize) -> Axis {
+        let g = (origin + t * direction - min) / size;
+        let cell = (g.floor() as isize).clamp(0, cells as isize - 1);
+        if direction == 0.0 {
+            return Axis {
+                cell,
+                step: 0,
+                t_next: f32::INFINITY,
+                t_delta: f32::INFINITY,
+            };
+        }
+        let step = if direction > 0.0 { 1 } else { -1 };
+        let boundary = min + (cell + (step + 1) / 2) as f32 * size;
+        Axis {
+            cell,
+            step,
+            t_next: (boundary - origin) / direction,
+            t_delta: size / direction.abs(),
+        }
+    }
+}
+
+impl Hittable for Heightfield {
+    // 2D DDA over the cells under the ray, nearest first
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t0, t1) = self.bbox.clip(r, t_min, t_max)?;
+        let (o, d) = (r.origin(), r.direction());
+        let mut x = Axis::new(o.x(), d.x(), t0, self.min.x(), self.dx, self.nx - 1);
+        let mut z = Axis::new(o.z(), d.z(), t0, self.min.z(), self.dz, self.nz - 1);
+        let mut t_enter = t0;
+        loop {
+            let t_exit = x.t_next.min(z.t_next).min(t1);
+            // skip cells the ray passes wholly above or below
+            let (low, high) = self.cell_range(x.cell as usize, z.cell as usize);
+            let (y0, y1) = (o.y() + t_enter * d.y(), o.y() + t_exit * d.y());
+            if y0.min(y1) <= high && y0.max(y1) >= low {
+                let rec = self.hit_cell(r, x.cell as usize, z.cell as usize, t_min, t_max);
+                if rec.is_some() {
+                    return rec;
+                }
+            }
+            if t_exit >= t1 {
+                return None;
+            }
+            let axis = if x.t_next < z.t_next { &mut x } else { &mut z };
+            axis.cell += axis.step;
+            axis.t_next += axis.t_delta;
+            t_enter = t_exit;
+            if x.cell < 0
+                || z.cell < 0
+                || x.cell >= self.nx as isize - 1
+                || z.cell >= self.nz as isize - 1
+            {
+                return None;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heightfield::*;
+    use crate::material::Lambertian;
+    use crate::random::SeededRng;
+    use float_eq::assert_float_eq;
+
+    fn mat() -> MaterialKind {
+        MaterialKind::Lambertian(Lambertian::new(Vec3::one()))
+    }
+
+    #[test]
+    fn slope() {
+        // rising along x by 1 over 4, so the normal is (-1, 4, 0) normalized
+        let heights: Vec<f32> = (0..15).map(|k| (k % 5) as f32 / 4.0).collect();
+        let h = Heightfield::new(
+            &heights,
+            5,
+            3,
+            Vec3::zero(),
+            Vec3::new(4.0, 1.0, 2.0),
+            mat(),
+        );
+        let r = Ray::new(Vec3::new(2.5, 5.0, 0.7), Vec3::new(0.0, -1.0, 0.0));
+        let rec = h.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.p.y(), 0.625, abs <= 1e-5);
+        let n = Vec3::new(-1.0, 4.0, 0.0).unit_vector();
+        assert!((rec.normal - n).length() < 1e-5);
+        assert_float_eq!(rec.u, 0.625, abs <= 1e-5);
+        assert_float_eq!(rec.v, 0.65, abs <= 1e-5);
+
+        // from below the normal faces the ray
+        let r = Ray::new(Vec3::new(2.5, -5.0, 0.7), Vec3::new(0.0, 1.0, 0.0));
+        let rec = h.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.normal.y() < 0.0);
+        let r = Ray::new(Vec3::new(4.5, 5.0, 0.7), Vec3::new(0.0, -1.0, 0.0));
+        assert!(h.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let rng = SeededRng::new(7);
+        let (nx, nz) = (9, 6);
+        let heights: Vec<f32> = (0..nx * nz).map(|_| rng.uniform()).collect();
+        let min = Vec3::new(-2.0, 0.5, -1.0);
+        let h = Heightfield::new(&heights, nx, nz, min, Vec3::new(4.0, 1.0, 3.0), mat());
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = 6.0 * rng.unit_vector() + Vec3::new(0.0, 1.0, 0.5);
+            let target = Vec3::new(
+                min.x() + 4.0 * rng.uniform(),
+                min.y() + rng.uniform(),
+                min.z() + 3.0 * rng.uniform(),
+            );
+            let r = Ray::new(origin, target - origin);
+            let mut nearest: Option<f32> = None;
+            for j in 0..nz - 1 {
+                for i in 0..nx - 1 {
+                    if let Some(rec) = h.hit_cell(&r, i, j, 0.001, f32::MAX) {
+                        if nearest.map_or(true, |t| rec.t < t) {
+                            nearest = Some(rec.t);
+                        }
+                    }
+                }
+            }
+            let found = h.hit(&r, 0.001, f32::MAX).map(|rec| rec.t);
+            match (found, nearest) {
+                (Some(a), Some(b)) => {
+                    assert_float_eq!(a, b, abs <= 1e-4);
+                    hits += 1;
+                }
+                (None, None) => {}
+                _ => panic!("{:?} != {:?} for {:?}", found, nearest, r.direction()),
+            }
+        }
+        assert!(hits > 500);
+    }
+}
@@ -1,51 +1,97 @@
+use crate::aabb::Aabb;
+use crate::light::Light;
 use crate::material::MaterialKind;
+use crate::medium::Medium;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
 #[derive(Debug)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub t: f32,
     pub p: Vec3,
     pub normal: Vec3,
-    pub material: MaterialKind,
+    // surface coordinates for texture lookup
+    pub u: f32,
+    pub v: f32,
+    pub material: &'a MaterialKind,
+    // set on the boundary of a participating medium, which rays cross
+    // without scattering
+    pub medium: Option<&'a dyn Medium>,
 }
 
-impl HitRecord {
-    pub fn new(mt: MaterialKind) -> HitRecord {
+impl<'a> HitRecord<'a> {
+    pub fn new(mt: &'a MaterialKind) -> HitRecord<'a> {
         HitRecord {
             t: 0.0,
             p: Vec3::zero(),
             normal: Vec3::zero(),
+            u: 0.0,
+            v: 0.0,
             material: mt,
+            medium: None,
         }
     }
 }
 
 pub trait Hittable {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
+    // None for unbounded objects such as infinite planes
+    fn bounding_box(&self) -> Option<Aabb>;
+    // emissive primitives, for light sampling
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        vec![]
+    }
+    // every intersection in (t_min, t_max) in ascending order, for Csg;
+    // the default steps from hit to hit, closed shapes report them directly
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        let mut hits: Vec<HitRecord> = vec![];
+        let mut t = t_min;
+        while let Some(rec) = self.hit(r, t, t_max) {
+            t = rec.t;
+            hits.push(rec);
+        }
+        hits
+    }
 }
 
 pub struct HittableList {
     pub list: Vec<Box<dyn Hittable + Send + Sync>>,
 }
 
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HittableList {
     pub fn new() -> HittableList {
         HittableList { list: vec![] }
     }
+}
 
-    pub fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+impl Hittable for HittableList {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let mut rec: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
         for h in self.list.iter() {
-            match h.as_ref().hit(r, t_min, closest_so_far) {
-                Some(hr) => {
-                    closest_so_far = hr.t;
-                    rec = Some(hr);
-                }
-                None => {}
+            if let Some(hr) = h.as_ref().hit(r, t_min, closest_so_far) {
+                closest_so_far = hr.t;
+                rec = Some(hr);
             }
         }
         rec
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut bbox = Aabb::empty();
+        for h in self.list.iter() {
+            bbox = bbox.surrounding(&h.as_ref().bounding_box()?);
+        }
+        Some(bbox)
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        self.list.iter().flat_map(|h| h.lights()).collect()
+    }
 }
@@ -0,0 +1,256 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
+use crate::ray::{keyframe_weight, Ray};
+use crate::transform::Transform;
+use crate::vec3::Vec3;
+use std::sync::Arc;
+
+// a shared object placed in the world by a transform, optionally moving
+// from there by offset between time0 and time1
+pub struct Instance {
+    object: Arc<dyn Hittable + Send + Sync>,
+    transform: Transform,
+    offset: Vec3,
+    time0: f32,
+    time1: f32,
+    bbox: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, transform: Transform) -> Instance {
+        Instance::moving(object, transform, Vec3::zero(), 0.0, 0.0)
+    }
+
+    pub fn moving(
+        object: Arc<dyn Hittable + Send + Sync>,
+        transform: Transform,
+        offset: Vec3,
+        time0: f32,
+        time1: f32,
+    ) -> Instance {
+        let bbox = object.bounding_box().map(|b| {
+            let b = transform.bbox(&b);
+            b.surrounding(&Aabb::new(b.min + offset, b.max + offset))
+        });
+        Instance {
+            object,
+            transform,
+            offset,
+            time0,
+            time1,
+            bbox,
+        }
+    }
+
+    fn displacement(&self, time: f32) -> Vec3 {
+        keyframe_weight(time, self.time0, self.time1) * self.offset
+    }
+
+    // r in object space, along with the displacement to add back to points
+    fn local(&self, r: &Ray) -> (Ray, Vec3) {
+        let d = self.displacement(r.time());
+        let inverse = self.transform.inverse();
+        let local = Ray::with_time(
+            inverse.point(r.origin() - d),
+            inverse.vector(r.direction()),
+            r.time(),
+        );
+        (local, d)
+    }
+}
+
+// moves a hit record from object space out to world space
+fn to_world<'a>(transform: &Transform, mut rec: HitRecord<'a>) -> HitRecord<'a> {
+    rec.p = transform.point(rec.p);
+    rec.normal = transform.normal(rec.normal).unit_vector();
+    rec
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        // the direction isn't normalized, so t is the same in both spaces
+        let (local, d) = self.local(r);
+        let mut rec = to_world(&self.transform, self.object.hit(&local, t_min, t_max)?);
+        rec.p += d;
+        Some(rec)
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        let (local, d) = self.local(r);
+        self.object
+            .hits(&local, t_min, t_max)
+            .into_iter()
+            .map(|rec| {
+                let mut rec = to_world(&self.transform, rec);
+                rec.p += d;
+                rec
+            })
+            .collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        // areas are only known to scale by a constant factor under similarity
+        // transforms, and light samples have no time; other emitters are
+        // still found by scattered rays
+        let scale = match self.transform.uniform_scale() {
+            Some(s) if self.offset.squared_length() == 0.0 => s,
+            _ => return vec![],
+        };
+        self.object
+            .lights()
+            .into_iter()
+            .map(|light| {
+                Box::new(InstanceLight {
+                    light,
+                    transform: self.transform,
+                    area_scale: scale * scale,
+                }) as Box<dyn Light + Send + Sync>
+            })
+            .collect()
+    }
+}
+
+struct InstanceLight {
+    light: Box<dyn Light + Send + Sync>,
+    transform: Transform,
+    area_scale: f32,
+}
+
+impl Hittable for InstanceLight {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let inverse = self.transform.inverse();
+        let local = Ray::with_time(
+            inverse.point(r.origin()),
+            inverse.vector(r.direction()),
+            r.time(),
+        );
+        let rec = self.light.hit(&local, t_min, t_max)?;
+        Some(to_world(&self.transform, rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.light.bounding_box().map(|b| self.transform.bbox(&b))
+    }
+}
+
+impl Light for InstanceLight {
+    fn sample(&self) -> HitRecord<'_> {
+        to_world(&self.transform, self.light.sample())
+    }
+
+    fn area(&self) -> f32 {
+        self.light.area() * self.area_scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instance::*;
+    use crate::light::LightList;
+    use crate::material::{DiffuseLight, Lambertian, MaterialKind};
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+    use float_eq::assert_float_eq;
+
+    fn sphere(material: MaterialKind) -> Arc<dyn Hittable + Send + Sync> {
+        Arc::new(Sphere::new(Vec3::zero(), 1.0, material))
+    }
+
+    #[test]
+    fn scaled_sphere() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let t = Transform::identity()
+            .scale(Vec3::new(2.0, 1.0, 1.0))
+            .translate(Vec3::new(0.0, 0.0, -5.0));
+        let s = Instance::new(sphere(m), t);
+
+        let b = s.bounding_box().unwrap();
+        assert_float_eq!(b.min.x(), -2.0, abs <= 1e-6);
+        assert_float_eq!(b.max.z(), -4.0, abs <= 1e-6);
+
+        let r = Ray::new(Vec3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = s.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 3.0, abs <= 1e-5);
+        assert_float_eq!(rec.p.x(), -2.0, abs <= 1e-5);
+        assert_float_eq!(rec.normal.x(), -1.0, abs <= 1e-5);
+
+        // a point on the ellipsoid away from the axes, where the normal
+        // isn't just the scaled object space normal
+        let r = Ray::new(Vec3::new(1.0, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = s.hit(&r, 0.001, f32::MAX).unwrap();
+        let p = rec.p - Vec3::new(0.0, 0.0, -5.0);
+        let gradient = Vec3::new(p.x() / 4.0, p.y(), p.z()).unit_vector();
+        assert!((rec.normal - gradient).length() < 1e-5);
+    }
+
+    #[test]
+    fn shared() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let object = sphere(m);
+        let a = Instance::new(
+            object.clone(),
+            Transform::identity().translate(Vec3::new(-3.0, 0.0, 0.0)),
+        );
+        let b = Instance::new(
+            object,
+            Transform::identity().translate(Vec3::new(3.0, 0.0, 0.0)),
+        );
+        let r = Ray::new(Vec3::new(-3.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(a.hit(&r, 0.001, f32::MAX).is_some());
+        assert!(b.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn lights() {
+        let m = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let t = Transform::identity()
+            .scale(Vec3::new(2.0, 2.0, 2.0))
+            .translate(Vec3::new(0.0, 10.0, 0.0));
+        let s = Instance::new(sphere(m.clone()), t);
+        let lights = LightList::new(&s);
+        assert_eq!(lights.list.len(), 1);
+        assert_float_eq!(
+            lights.list[0].area(),
+            16.0 * std::f32::consts::PI,
+            r2nd <= 1e-5
+        );
+        for _ in 0..10 {
+            let (rec, _) = lights.sample(Vec3::zero()).unwrap();
+            assert_float_eq!(
+                (rec.p - Vec3::new(0.0, 10.0, 0.0)).length(),
+                2.0,
+                abs <= 1e-4
+            );
+        }
+
+        let t = Transform::identity().scale(Vec3::new(1.0, 2.0, 1.0));
+        assert!(Instance::new(sphere(m), t).lights().is_empty());
+    }
+
+    #[test]
+    fn moving() {
+        let m = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let t = Transform::identity().translate(Vec3::new(0.0, 0.0, -5.0));
+        let s = Instance::moving(sphere(m), t, Vec3::new(3.0, 0.0, 0.0), 0.0, 1.0);
+        let b = s.bounding_box().unwrap();
+        assert_float_eq!(b.min.x(), -1.0, abs <= 1e-5);
+        assert_float_eq!(b.max.x(), 4.0, abs <= 1e-5);
+        assert!(s.lights().is_empty());
+
+        let r =
+            |time: f32| Ray::with_time(Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time);
+        assert!(s.hit(&r(0.0), 0.001, f32::MAX).is_none());
+        assert!(s.hit(&r(0.5), 0.001, f32::MAX).is_none());
+        let rec = s.hit(&r(1.0), 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.0, abs <= 1e-5);
+        assert_float_eq!(rec.p.x(), 3.0, abs <= 1e-5);
+        let hits = s.hits(&r(2.0), 0.001, f32::MAX);
+        assert_eq!(hits.len(), 2);
+        assert_float_eq!(hits[1].p.z(), -6.0, abs <= 1e-5);
+    }
+}
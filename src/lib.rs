@@ -1,10 +1,36 @@
+pub mod aabb;
+pub mod box_shape;
+pub mod bvh;
 pub mod camera;
+pub mod coated;
+pub mod cone;
+pub mod csg;
+pub mod cylinder;
+pub mod disk;
+pub mod heightfield;
 pub mod hit;
+pub mod instance;
+pub mod light;
 pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod microfacet;
 pub mod model;
+pub mod obj;
+pub mod perlin;
+pub mod plane;
+pub mod principled;
+pub mod quad;
 pub mod random;
 pub mod ray;
 pub mod renderer;
+pub mod scene;
+pub mod sdf;
+pub mod shape;
 pub mod sphere;
+pub mod texture;
+pub mod torus;
+pub mod transform;
 pub mod triangle;
 pub mod vec3;
+pub mod volume;
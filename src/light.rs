@@ -0,0 +1,166 @@
+use crate::hit::{HitRecord, Hittable};
+use crate::random::rand_uniform;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+pub trait Light: Hittable {
+    // point uniformly distributed over the surface, with its normal and material
+    fn sample(&self) -> HitRecord<'_>;
+    fn area(&self) -> f32;
+
+    // density of sample() per solid angle for the point seen from origin along direction
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        match self.hit(&Ray::new(origin, direction), 0.001, f32::MAX) {
+            Some(rec) => area_to_solid_angle(origin, &rec) / self.area(),
+            None => 0.0,
+        }
+    }
+}
+
+// converts a density per area at rec.p into one per solid angle as seen from origin
+fn area_to_solid_angle(origin: Vec3, rec: &HitRecord) -> f32 {
+    let d = rec.p - origin;
+    let distance_squared = d.squared_length();
+    let cosine = rec.normal.dot(d).abs() / distance_squared.sqrt();
+    if cosine < 1e-6 {
+        return 0.0;
+    }
+    distance_squared / cosine
+}
+
+pub struct LightList {
+    pub list: Vec<Box<dyn Light + Send + Sync>>,
+}
+
+impl LightList {
+    // collects every emissive primitive of the world
+    pub fn new(world: &dyn Hittable) -> LightList {
+        LightList {
+            list: world.lights(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // picks a light uniformly and a point on it, and returns the point
+    // with its pdf per solid angle as seen from origin
+    pub fn sample(&self, origin: Vec3) -> Option<(HitRecord<'_>, f32)> {
+        if self.list.is_empty() {
+            return None;
+        }
+        let n = self.list.len();
+        let light = &self.list[((rand_uniform() * n as f32) as usize).min(n - 1)];
+        let rec = light.sample();
+
+        let pdf = area_to_solid_angle(origin, &rec) / (light.area() * n as f32);
+        if pdf == 0.0 {
+            return None;
+        }
+        Some((rec, pdf))
+    }
+
+    // density of sample() per solid angle for direction from origin
+    pub fn pdf(&self, origin: Vec3, direction: Vec3) -> f32 {
+        if self.list.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self.list.iter().map(|l| l.pdf(origin, direction)).sum();
+        sum / self.list.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hit::HittableList;
+    use crate::light::*;
+    use crate::material::{DiffuseLight, Lambertian, MaterialKind};
+    use crate::sphere::Sphere;
+    use crate::triangle::Triangle;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn collect_emissive() {
+        let light = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let lam = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let mut world = HittableList::new();
+        world
+            .list
+            .push(Box::new(Sphere::new(Vec3::zero(), 1.0, light.clone())));
+        world
+            .list
+            .push(Box::new(Sphere::new(Vec3::zero(), 1.0, lam)));
+        world.list.push(Box::new(Triangle::new(
+            Vec3::zero(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            light,
+        )));
+        let lights = LightList::new(&world);
+        assert_eq!(lights.list.len(), 2);
+    }
+
+    #[test]
+    fn sample_triangle() {
+        let light = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let t = Triangle::new(
+            Vec3::zero(),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            light,
+        );
+        assert_float_eq!(t.area(), 2.0, abs <= 1e-6);
+
+        let mut world = HittableList::new();
+        world.list.push(Box::new(t));
+        let lights = LightList::new(&world);
+        for _ in 0..100 {
+            let (rec, pdf) = lights.sample(Vec3::new(0.0, 0.0, 1.0)).unwrap();
+            assert!(rec.p.x() >= 0.0 && rec.p.y() >= 0.0);
+            assert!(rec.p.x() + rec.p.y() <= 2.0 + 1e-5);
+            assert_float_eq!(rec.p.z(), 0.0, abs <= 1e-6);
+            let d = rec.p - Vec3::new(0.0, 0.0, 1.0);
+            let cosine = 1.0 / d.length();
+            assert_float_eq!(pdf, d.squared_length() / (cosine * 2.0), r2nd <= 1e-4);
+        }
+    }
+
+    #[test]
+    fn pdf_matches_sample() {
+        let light = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let mut world = HittableList::new();
+        world.list.push(Box::new(Triangle::new(
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(1.0, 2.0, -1.0),
+            Vec3::new(0.0, 2.0, 1.0),
+            light.clone(),
+        )));
+        world
+            .list
+            .push(Box::new(Sphere::new(Vec3::new(3.0, 0.0, 0.0), 0.5, light)));
+        let lights = LightList::new(&world);
+        let origin = Vec3::zero();
+        fastrand::seed(0);
+        for _ in 0..100 {
+            let (rec, pdf) = lights.sample(origin).unwrap();
+            let visible = world.hit(&Ray::new(origin, rec.p - origin), 0.001, 1.0 - 1e-4);
+            if visible.is_none() {
+                assert_float_eq!(lights.pdf(origin, rec.p - origin), pdf, r2nd <= 1e-2);
+            }
+        }
+        assert_eq!(lights.pdf(origin, Vec3::new(0.0, -1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn sample_sphere() {
+        let light = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one()));
+        let s = Sphere::new(Vec3::new(1.0, 2.0, 3.0), 2.0, light);
+        assert_float_eq!(s.area(), 16.0 * std::f32::consts::PI, r2nd <= 1e-6);
+        for _ in 0..100 {
+            let rec = s.sample();
+            assert_float_eq!((rec.p - s.center).length(), 2.0, abs <= 1e-5);
+            assert_float_eq!(rec.normal.length(), 1.0, abs <= 1e-5);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use clap::{App, Arg};
 use once_cell::sync::Lazy;
+use rrt::bvh::Bvh;
 use rrt::camera::Camera;
 use rrt::hit::HittableList;
 use rrt::material::{Dielectric, Lambertian, MaterialKind, Metal};
 use rrt::model::ramiel;
+use rrt::plane::Plane;
 use rrt::random::rand_uniform;
-use rrt::renderer::rendering;
+use rrt::renderer::{rendering, Background};
+use rrt::scene::load_scene;
 use rrt::sphere::Sphere;
 use rrt::vec3::Vec3;
 
@@ -13,7 +16,7 @@ const NX: u32 = 1920 * 2;
 const NY: u32 = 1080 * 2;
 const NS: u32 = 1000;
 
-static SCENE: Lazy<HittableList> = Lazy::new(|| random_scene());
+static SCENE: Lazy<Bvh> = Lazy::new(|| Bvh::from(random_scene()));
 static CAM: Lazy<Camera> = Lazy::new(|| {
     let lookfrom = Vec3::new(13.0, 2.0, 3.0);
     let lookat = Vec3::new(0.0, 0.0, 0.0);
@@ -33,9 +36,9 @@ fn random_scene() -> HittableList {
     fastrand::seed(0);
     let mut world = HittableList::new();
 
-    world.list.push(Box::new(Sphere::new(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
+    world.list.push(Box::new(Plane::new(
+        Vec3::zero(),
+        Vec3::new(0.0, 1.0, 0.0),
         MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
     )));
 
@@ -72,7 +75,7 @@ fn random_scene() -> HittableList {
                     let s = Box::new(Sphere::new(
                         center,
                         0.2,
-                        MaterialKind::Dielectric(Dielectric::new(1.5, Vec3::one())),
+                        MaterialKind::Dielectric(Dielectric::new(1.5)),
                     ));
                     world.list.push(s);
                 }
@@ -83,7 +86,7 @@ fn random_scene() -> HittableList {
     world.list.push(Box::new(Sphere::new(
         Vec3::new(0.0, 1.0, 0.0),
         1.0,
-        MaterialKind::Dielectric(Dielectric::new(1.5, Vec3::one())),
+        MaterialKind::Dielectric(Dielectric::new(1.5)),
     )));
     world.list.push(Box::new(Sphere::new(
         Vec3::new(-4.0, 1.0, 0.0),
@@ -114,14 +117,56 @@ fn main() {
                 .takes_value(true),
         )
         .arg(Arg::with_name("silent").short("s").long("silent"))
+        .arg(
+            Arg::with_name("scene")
+                .value_name("SCENE_FILE")
+                .help("TOML scene description, renders the built-in scene if omitted")
+                .index(1),
+        )
         .get_matches();
 
     let thread: usize = matches.value_of("thread").unwrap_or("0").parse().unwrap();
-    let silent: bool = match matches.occurrences_of("silent") {
-        0 => false,
-        _ => true,
-    };
+    let silent: bool = matches.occurrences_of("silent") > 0;
     let start = std::time::SystemTime::now();
-    rendering(NX, NY, NS, &CAM, &SCENE, thread, "my_scene.png", silent);
+    match matches.value_of("scene") {
+        Some(path) => {
+            let scene = match load_scene(path) {
+                Ok(scene) => Box::leak(Box::new(scene)),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    std::process::exit(1);
+                }
+            };
+            let output = match &scene.output {
+                Some(output) => output.clone(),
+                None => {
+                    let stem = std::path::Path::new(path).file_stem().unwrap();
+                    format!("{}.png", stem.to_string_lossy())
+                }
+            };
+            rendering(
+                scene.width,
+                scene.height,
+                scene.samples,
+                &scene.camera,
+                &scene.world,
+                scene.background,
+                thread,
+                &output,
+                silent,
+            );
+        }
+        None => rendering(
+            NX,
+            NY,
+            NS,
+            &CAM,
+            &*SCENE,
+            Background::Sky,
+            thread,
+            "my_scene.png",
+            silent,
+        ),
+    }
     println!("{:?}", start.elapsed().unwrap());
 }
@@ -1,17 +1,111 @@
+use crate::coated::Coated;
 use crate::hit::HitRecord;
-use crate::random::rand_uniform;
+use crate::medium::{Absorber, Medium};
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, Ggx};
+use crate::principled::Principled;
+use crate::random::{rand_uniform, random_unit_vector};
 use crate::ray::Ray;
+use crate::shape::Frame;
+use crate::texture::TextureKind;
 use crate::vec3::Vec3;
+use std::sync::Arc;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum MaterialKind {
     Lambertian(Lambertian),
     Metal(Metal),
+    Conductor(Conductor),
     Dielectric(Dielectric),
+    RoughDielectric(RoughDielectric),
+    // boxed, its textures would make every material large
+    Principled(Box<Principled>),
+    Coated(Coated),
+    DiffuseLight(DiffuseLight),
+    Isotropic(Isotropic),
+    HenyeyGreenstein(HenyeyGreenstein),
+}
+
+impl MaterialKind {
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        match self {
+            MaterialKind::Lambertian(m) => m.scatter(r_in, rec),
+            MaterialKind::Metal(m) => m.scatter(r_in, rec),
+            MaterialKind::Conductor(m) => m.scatter(r_in, rec),
+            MaterialKind::Dielectric(m) => m.scatter(r_in, rec),
+            MaterialKind::RoughDielectric(m) => m.scatter(r_in, rec),
+            MaterialKind::Principled(m) => m.scatter(r_in, rec),
+            MaterialKind::Coated(m) => m.scatter(r_in, rec),
+            MaterialKind::DiffuseLight(m) => m.scatter(r_in, rec),
+            MaterialKind::Isotropic(m) => m.scatter(r_in, rec),
+            MaterialKind::HenyeyGreenstein(m) => m.scatter(r_in, rec),
+        }
+    }
+
+    // density with which scatter picks direction
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        match self {
+            MaterialKind::Lambertian(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::Metal(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::Conductor(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::RoughDielectric(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::Principled(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::Coated(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::Isotropic(m) => m.pdf(r_in, rec, direction),
+            MaterialKind::HenyeyGreenstein(m) => m.pdf(r_in, rec, direction),
+            _ => 0.0,
+        }
+    }
+
+    // BSDF times cosine for light arriving from direction
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        match self {
+            MaterialKind::Lambertian(m) => m.eval(r_in, rec, direction),
+            MaterialKind::Metal(m) => m.eval(r_in, rec, direction),
+            MaterialKind::Conductor(m) => m.eval(r_in, rec, direction),
+            MaterialKind::RoughDielectric(m) => m.eval(r_in, rec, direction),
+            MaterialKind::Principled(m) => m.eval(r_in, rec, direction),
+            MaterialKind::Coated(m) => m.eval(r_in, rec, direction),
+            MaterialKind::Isotropic(m) => m.eval(r_in, rec, direction),
+            MaterialKind::HenyeyGreenstein(m) => m.eval(r_in, rec, direction),
+            _ => Vec3::zero(),
+        }
+    }
+
+    // whether scatter only ever picks from delta distributions, which can't
+    // be combined with light sampling
+    pub fn is_delta(&self) -> bool {
+        match self {
+            MaterialKind::Metal(m) => m.is_mirror(),
+            MaterialKind::Conductor(m) => m.is_mirror(),
+            MaterialKind::Dielectric(_) | MaterialKind::DiffuseLight(_) => true,
+            MaterialKind::RoughDielectric(m) => m.is_smooth(),
+            MaterialKind::Coated(m) => m.is_smooth() && m.base().is_delta(),
+            _ => false,
+        }
+    }
+
+    // the inside of a transparent solid, see Absorber
+    pub fn interior(&self) -> Option<&dyn Medium> {
+        match self {
+            MaterialKind::Dielectric(m) => Some(m.interior()),
+            MaterialKind::RoughDielectric(m) => Some(m.interior()),
+            MaterialKind::Principled(m) => Some(m.interior()),
+            MaterialKind::Coated(m) => m.interior(),
+            _ => None,
+        }
+    }
+}
+
+pub struct ScatterRecord {
+    pub ray: Ray,
+    // BSDF times cosine divided by pdf
+    pub attenuation: Vec3,
+    // density of ray's direction per solid angle, None for delta distributions
+    pub pdf: Option<f32>,
 }
 
 pub trait Material {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)>;
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord>;
 }
 
 fn random_in_unit_sphere() -> Vec3 {
@@ -24,58 +118,267 @@ fn random_in_unit_sphere() -> Vec3 {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+// direction density of `center + random_in_unit_sphere() * radius` seen from the origin,
+// i.e. the volume of the ball inside the cone d(omega) divided by the ball volume
+fn ball_direction_pdf(direction: Vec3, center: Vec3, radius: f32) -> f32 {
+    let w = direction.unit_vector();
+    let b = w.dot(center);
+    let disc = b * b - center.squared_length() + radius * radius;
+    if disc <= 0.0 {
+        return 0.0;
+    }
+    let t1 = (b - disc.sqrt()).max(0.0);
+    let t2 = b + disc.sqrt();
+    if t2 <= 0.0 {
+        return 0.0;
+    }
+    (t2.powi(3) - t1.powi(3)) / (4.0 * std::f32::consts::PI * radius.powi(3))
+}
+
+#[derive(Clone, Debug)]
 pub struct Lambertian {
-    albedo: Vec3,
+    albedo: TextureKind,
 }
 
 impl Lambertian {
-    pub fn new(a: Vec3) -> Lambertian {
-        Lambertian { albedo: a }
+    pub fn new<T: Into<TextureKind>>(a: T) -> Lambertian {
+        Lambertian { albedo: a.into() }
     }
     // }
 
     // impl Material for Lambertian {
-    pub fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)> {
-        let target = rec.p + rec.normal + random_in_unit_sphere();
-        let scattered = Ray::new(rec.p, target - rec.p);
-        Some((scattered, self.albedo))
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // cosine weighted around the normal
+        let mut direction = rec.normal + random_unit_vector();
+        if direction.squared_length() < 1e-8 {
+            direction = rec.normal;
+        }
+        let scattered = Ray::with_time(rec.p, direction, r_in.time());
+        let pdf = self.pdf(r_in, rec, direction);
+        Some(ScatterRecord {
+            ray: scattered,
+            attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            pdf: Some(pdf),
+        })
+    }
+
+    pub fn pdf(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        rec.normal.dot(direction.unit_vector()).max(0.0) / std::f32::consts::PI
+    }
+
+    // BRDF times cosine towards direction
+    pub fn eval(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let cosine = rec.normal.dot(direction.unit_vector()).max(0.0);
+        self.albedo.value(rec.u, rec.v, rec.p) * cosine / std::f32::consts::PI
     }
 }
 
-fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+pub(crate) fn reflect(v: Vec3, n: Vec3) -> Vec3 {
     v - 2.0 * v.dot(n) * n
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Metal {
-    albedo: Vec3,
+    albedo: TextureKind,
     fuzz: f32,
 }
 
 impl Metal {
-    pub fn new(a: Vec3, f: f32) -> Metal {
+    pub fn new<T: Into<TextureKind>>(a: T, f: f32) -> Metal {
         Metal {
-            albedo: a,
+            albedo: a.into(),
             fuzz: if f < 1.0 { f } else { 1.0 },
         }
     }
     // }
 
     // impl Material for Metal {
-    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)> {
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let reflected = reflect(r_in.direction().unit_vector(), rec.normal);
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * random_in_unit_sphere());
-        // let scattered = Ray::new(rec.p, reflected);
+        let scattered = Ray::with_time(
+            rec.p,
+            reflected + self.fuzz * random_in_unit_sphere(),
+            r_in.time(),
+        );
         if scattered.direction().dot(rec.normal) > 0.0 {
-            Some((scattered, self.albedo))
+            let pdf = if self.is_mirror() {
+                None
+            } else {
+                Some(ball_direction_pdf(
+                    scattered.direction(),
+                    reflected,
+                    self.fuzz,
+                ))
+            };
+            Some(ScatterRecord {
+                ray: scattered,
+                attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+                pdf,
+            })
         } else {
             None
         }
     }
+
+    // a perfect mirror can't be evaluated for an arbitrary direction
+    pub fn is_mirror(&self) -> bool {
+        self.fuzz <= 0.0
+    }
+
+    // BRDF times cosine towards direction, matching the distribution of scatter
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        if self.is_mirror() || direction.dot(rec.normal) <= 0.0 {
+            return Vec3::zero();
+        }
+        let reflected = reflect(r_in.direction().unit_vector(), rec.normal);
+        self.albedo.value(rec.u, rec.v, rec.p) * ball_direction_pdf(direction, reflected, self.fuzz)
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        if self.is_mirror() {
+            return 0.0;
+        }
+        let reflected = reflect(r_in.direction().unit_vector(), rec.normal);
+        ball_direction_pdf(direction, reflected, self.fuzz)
+    }
+}
+
+// metal as a rough surface of GGX distributed microfacets, each a mirror
+// reflecting by the Fresnel equations for the complex index of refraction
+// eta + i k, per color channel
+#[derive(Clone, Debug)]
+pub struct Conductor {
+    eta: Vec3,
+    k: Vec3,
+    ggx: Ggx,
+}
+
+impl Conductor {
+    // roughness 0 is a perfect mirror and 1 is very dull
+    pub fn new(eta: Vec3, k: Vec3, roughness: f32) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            ggx: Ggx::from_roughness(roughness),
+        }
+    }
+
+    // measured indices at the red, green and blue wavelengths 650, 550 and 450nm
+    pub fn gold(roughness: f32) -> Conductor {
+        Conductor::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Conductor {
+        Conductor::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f32) -> Conductor {
+        Conductor::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f32) -> Conductor {
+        Conductor::new(
+            Vec3::new(0.155, 0.117, 0.138),
+            Vec3::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn is_mirror(&self) -> bool {
+        self.ggx.is_smooth()
+    }
+
+    // the shading frame on the side r_in comes from, for closed shapes hit
+    // from inside, and the direction towards r_in's origin in it
+    fn local(r_in: &Ray, rec: &HitRecord) -> (Frame, Vec3) {
+        let wo = -r_in.direction().unit_vector();
+        let n = if wo.dot(rec.normal) < 0.0 {
+            -rec.normal
+        } else {
+            rec.normal
+        };
+        let frame = Frame::new(Vec3::zero(), n);
+        let wo = frame.local(wo);
+        (frame, wo)
+    }
+
+    // visible normals are sampled, so that attenuation is just the Fresnel
+    // term times the fraction of light that isn't shadowed on the way out
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, wo) = Self::local(r_in, rec);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        if self.is_mirror() {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            return Some(ScatterRecord {
+                ray: Ray::with_time(rec.p, frame.world(wi), r_in.time()),
+                attenuation: fresnel_conductor(wo.z(), self.eta, self.k),
+                pdf: None,
+            });
+        }
+        let h = self.ggx.sample_visible(wo, rand_uniform(), rand_uniform());
+        let wi = reflect(-wo, h);
+        // reflected into another microfacet; G2 is 0 below the surface
+        if wi.z() <= 0.0 || wo.dot(h) <= 0.0 {
+            return None;
+        }
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        Some(ScatterRecord {
+            ray: Ray::with_time(rec.p, frame.world(wi), r_in.time()),
+            attenuation: fresnel * (self.ggx.g2(wo, wi) / self.ggx.g1(wo)),
+            pdf: Some(self.ggx.pdf_visible(wo, h) / (4.0 * wo.dot(h))),
+        })
+    }
+
+    // wo, wi and their half vector, or None if either is below the surface
+    fn half_vector(r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Vec3, Vec3, Vec3)> {
+        let (frame, wo) = Self::local(r_in, rec);
+        let wi = frame.local(direction.unit_vector());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+        Some((wo, wi, (wo + wi).unit_vector()))
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        if self.is_mirror() {
+            return 0.0;
+        }
+        match Self::half_vector(r_in, rec, direction) {
+            Some((wo, _, h)) if wo.dot(h) > 0.0 => self.ggx.pdf_visible(wo, h) / (4.0 * wo.dot(h)),
+            _ => 0.0,
+        }
+    }
+
+    // BRDF times cosine towards direction
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        if self.is_mirror() {
+            return Vec3::zero();
+        }
+        match Self::half_vector(r_in, rec, direction) {
+            Some((wo, wi, h)) => {
+                let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+                fresnel * (self.ggx.d(h) * self.ggx.g2(wo, wi) / (4.0 * wo.z()))
+            }
+            None => Vec3::zero(),
+        }
+    }
 }
 
-fn refract(v: Vec3, n: Vec3, ni_over_nt: f32) -> Option<Vec3> {
+pub(crate) fn refract(v: Vec3, n: Vec3, ni_over_nt: f32) -> Option<Vec3> {
     let uv = v.unit_vector();
     let dt = uv.dot(n);
     let discriminant = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
@@ -92,24 +395,34 @@ fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
 
-
-#[derive(Clone, Copy, Debug)]
+// smooth glass, absorbing inside by its interior
+#[derive(Clone, Debug)]
 pub struct Dielectric {
     ref_idx: f32,
-    albedo: Vec3,
+    interior: Arc<Absorber>,
 }
 
 impl Dielectric {
-    pub fn new(ri: f32, a: Vec3) -> Dielectric {
+    // clear
+    pub fn new(ri: f32) -> Dielectric {
         Dielectric {
             ref_idx: ri,
-            albedo: a,
+            interior: Arc::new(Absorber::new(Vec3::zero())),
         }
     }
 
-    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vec3)> {
+    // white light turns color after distance inside
+    pub fn absorption(mut self, color: Vec3, distance: f32) -> Dielectric {
+        self.interior = Arc::new(Absorber::from_color(color, distance));
+        self
+    }
+
+    pub fn interior(&self) -> &Absorber {
+        &self.interior
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let reflected = reflect(r_in.direction(), rec.normal);
-        let attenuation = self.albedo;
         let (outward_normal, ni_over_nt, cosine) = if r_in.direction().dot(rec.normal) > 0.0 {
             (
                 -rec.normal,
@@ -127,14 +440,430 @@ impl Dielectric {
         let scattered = match refract(r_in.direction(), outward_normal, ni_over_nt) {
             Some(refracted) => {
                 if rand_uniform() < schlick(cosine, self.ref_idx) {
-                    Ray::new(rec.p, reflected)
+                    Ray::with_time(rec.p, reflected, r_in.time())
                 } else {
-                    Ray::new(rec.p, refracted)
+                    Ray::with_time(rec.p, refracted, r_in.time())
                 }
             }
-            None => Ray::new(rec.p, reflected),
+            None => Ray::with_time(rec.p, reflected, r_in.time()),
+        };
+
+        Some(ScatterRecord {
+            ray: scattered,
+            attenuation: Vec3::one(),
+            pdf: None,
+        })
+    }
+}
+
+// frosted glass: a GGX microfacet interface to a solid of index ref_idx,
+// reflecting and refracting by the exact Fresnel equations (Walter et al.,
+// "Microfacet Models for Refraction through Rough Surfaces", 2007), absorbing
+// inside by its interior
+#[derive(Clone, Debug)]
+pub struct RoughDielectric {
+    ref_idx: f32,
+    ggx: Ggx,
+    interior: Arc<Absorber>,
+}
+
+impl RoughDielectric {
+    // clear, with roughness 0 for smooth glass up to 1
+    pub fn new(ri: f32, roughness: f32) -> RoughDielectric {
+        RoughDielectric {
+            ref_idx: ri,
+            ggx: Ggx::from_roughness(roughness),
+            interior: Arc::new(Absorber::new(Vec3::zero())),
+        }
+    }
+
+    // white light turns color after distance inside
+    pub fn absorption(mut self, color: Vec3, distance: f32) -> RoughDielectric {
+        self.interior = Arc::new(Absorber::from_color(color, distance));
+        self
+    }
+
+    // smooth glass, scattering by delta distributions only
+    pub fn is_smooth(&self) -> bool {
+        self.ggx.is_smooth()
+    }
+
+    pub fn interior(&self) -> &Absorber {
+        &self.interior
+    }
+
+    // the shading frame on the side r_in comes from, the direction towards
+    // r_in's origin in it, and the index on the other side over this one's
+    fn local(&self, r_in: &Ray, rec: &HitRecord) -> (Frame, Vec3, f32) {
+        let wo = -r_in.direction().unit_vector();
+        let (n, eta) = if wo.dot(rec.normal) > 0.0 {
+            (rec.normal, self.ref_idx)
+        } else {
+            (-rec.normal, 1.0 / self.ref_idx)
+        };
+        let frame = Frame::new(Vec3::zero(), n);
+        let wo = frame.local(wo);
+        (frame, wo, eta)
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, wo, eta) = self.local(r_in, rec);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let scattered = |wi: Vec3| Ray::with_time(rec.p, frame.world(wi), r_in.time());
+        if self.ggx.is_smooth() {
+            let n = Vec3::new(0.0, 0.0, 1.0);
+            let wi = if rand_uniform() < fresnel_dielectric(wo.z(), eta) {
+                reflect(-wo, n)
+            } else {
+                refract(-wo, n, 1.0 / eta)?
+            };
+            return Some(ScatterRecord {
+                ray: scattered(wi),
+                attenuation: Vec3::one(),
+                pdf: None,
+            });
+        }
+        let wi = self.ggx.sample_dielectric(wo, eta)?;
+        let (f, pdf) = self.ggx.eval_dielectric(wo, wi, eta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord {
+            ray: scattered(wi),
+            attenuation: Vec3::one() * (f / pdf),
+            pdf: Some(pdf),
+        })
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        if self.ggx.is_smooth() {
+            return 0.0;
+        }
+        let (frame, wo, eta) = self.local(r_in, rec);
+        let wi = frame.local(direction.unit_vector());
+        self.ggx.eval_dielectric(wo, wi, eta).1
+    }
+
+    // BSDF times cosine towards direction
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        if self.ggx.is_smooth() {
+            return Vec3::zero();
+        }
+        let (frame, wo, eta) = self.local(r_in, rec);
+        let wi = frame.local(direction.unit_vector());
+        Vec3::one() * self.ggx.eval_dielectric(wo, wi, eta).0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffuseLight {
+    emit: TextureKind,
+}
+
+impl DiffuseLight {
+    pub fn new<T: Into<TextureKind>>(emit: T) -> DiffuseLight {
+        DiffuseLight { emit: emit.into() }
+    }
+
+    pub fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
+
+    pub fn emitted(&self, rec: &HitRecord) -> Vec3 {
+        self.emit.value(rec.u, rec.v, rec.p)
+    }
+}
+
+// phase function scattering equally in all directions, for participating media
+#[derive(Clone, Debug)]
+pub struct Isotropic {
+    albedo: TextureKind,
+}
+
+impl Isotropic {
+    pub fn new<T: Into<TextureKind>>(a: T) -> Isotropic {
+        Isotropic { albedo: a.into() }
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        Some(ScatterRecord {
+            ray: Ray::with_time(rec.p, random_unit_vector(), r_in.time()),
+            attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            pdf: Some(1.0 / (4.0 * std::f32::consts::PI)),
+        })
+    }
+
+    pub fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> f32 {
+        1.0 / (4.0 * std::f32::consts::PI)
+    }
+
+    // the phase function, there's no cosine inside a medium
+    pub fn eval(&self, _r_in: &Ray, rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, rec.p) / (4.0 * std::f32::consts::PI)
+    }
+}
+
+// phase function for media scattering forward (g > 0) or backward (g < 0),
+// with g the mean cosine of the scattering angle
+#[derive(Clone, Debug)]
+pub struct HenyeyGreenstein {
+    albedo: TextureKind,
+    g: f32,
+}
+
+impl HenyeyGreenstein {
+    // panics unless -1 < g < 1
+    pub fn new<T: Into<TextureKind>>(a: T, g: f32) -> HenyeyGreenstein {
+        assert!(g > -1.0 && g < 1.0);
+        HenyeyGreenstein {
+            albedo: a.into(),
+            g,
+        }
+    }
+
+    // density of scattering by the angle with cosine cos_theta from the
+    // incoming direction, per solid angle
+    fn phase(&self, cos_theta: f32) -> f32 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * std::f32::consts::PI * denom * denom.sqrt())
+    }
+
+    fn cos_theta(r_in: &Ray, direction: Vec3) -> f32 {
+        r_in.direction().unit_vector().dot(direction.unit_vector())
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // invert the cumulative distribution of cos_theta
+        let g = self.g;
+        let xi = rand_uniform();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * rand_uniform();
+        let w = r_in.direction().unit_vector();
+        let (t, b) = w.orthonormal_basis();
+        let direction = sin_theta * (phi.cos() * t + phi.sin() * b) + cos_theta * w;
+        // the phase function is its own pdf
+        Some(ScatterRecord {
+            ray: Ray::with_time(rec.p, direction, r_in.time()),
+            attenuation: self.albedo.value(rec.u, rec.v, rec.p),
+            pdf: Some(self.phase(cos_theta)),
+        })
+    }
+
+    pub fn pdf(&self, r_in: &Ray, _rec: &HitRecord, direction: Vec3) -> f32 {
+        self.phase(Self::cos_theta(r_in, direction))
+    }
+
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, rec.p) * self.phase(Self::cos_theta(r_in, direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::*;
+    use crate::random::random_unit_vector;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn ball_direction_pdf_integrates_to_one() {
+        fastrand::seed(0);
+        let n = 200000;
+        for (center, radius) in [
+            (Vec3::new(0.0, 0.0, 1.0), 0.3),
+            (Vec3::new(0.6, 0.0, 0.8), 0.9),
+            (Vec3::new(0.0, 1.0, 0.0), 1.0),
+        ]
+        .iter()
+        {
+            let mut sum = 0.0;
+            for _ in 0..n {
+                sum += ball_direction_pdf(random_unit_vector(), *center, *radius);
+            }
+            let integral = sum / n as f32 * 4.0 * std::f32::consts::PI;
+            assert_float_eq!(integral, 1.0, abs <= 0.02);
+        }
+    }
+
+    #[test]
+    fn conductor() {
+        fastrand::seed(2);
+        let gold = MaterialKind::Conductor(Conductor::gold(0.5));
+        let m = match &gold {
+            MaterialKind::Conductor(m) => m,
+            _ => unreachable!(),
         };
+        let mut rec = HitRecord::new(&gold);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        let r = Ray::new(Vec3::new(-1.0, 2.0, 0.0), Vec3::new(1.0, -2.0, 0.0));
+        let n = 100000;
+        let mut integral = 0.0;
+        let mut scattered = 0;
+        for _ in 0..n {
+            let d = random_unit_vector();
+            integral += m.pdf(&r, &rec, d);
+            // consistent with eval, and reciprocal
+            if let Some(s) = m.scatter(&r, &rec) {
+                scattered += 1;
+                let d = s.ray.direction();
+                assert!(d.y() > 0.0);
+                let pdf = s.pdf.unwrap();
+                assert_float_eq!(pdf, m.pdf(&r, &rec, d), r2nd <= 1e-3);
+                let f = m.eval(&r, &rec, d);
+                assert!((f / pdf - s.attenuation).length() < 1e-3);
+                let back = Ray::new(Vec3::zero(), -d);
+                let g = m.eval(&back, &rec, -r.direction());
+                let cos_o = r.direction().unit_vector().y().abs();
+                assert_float_eq!(f.y() / d.y(), g.y() / cos_o, r2nd <= 1e-3);
+            }
+        }
+        // the density misses the directions reflected below the surface
+        assert_float_eq!(
+            integral / n as f32 * 4.0 * std::f32::consts::PI,
+            scattered as f32 / n as f32,
+            abs <= 0.02
+        );
+        assert!(scattered > 9 * n / 10);
+
+        // hit from inside a closed shape it reflects back inside
+        let r = Ray::new(Vec3::zero(), Vec3::new(1.0, 2.0, 0.0));
+        let s = m.scatter(&r, &rec).unwrap();
+        assert!(s.ray.direction().y() < 0.0);
+
+        // a mirror reflects by the Fresnel reflectance, here 1 on the whole
+        let chrome = Conductor::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(1e4, 1e4, 1e4), 0.0);
+        assert!(chrome.is_mirror());
+        let s = chrome.scatter(&r, &rec).unwrap();
+        assert!(s.pdf.is_none());
+        assert_float_eq!(s.ray.direction().x(), 1.0 / 5.0f32.sqrt(), abs <= 1e-6);
+        assert_float_eq!(s.attenuation.x(), 1.0, abs <= 1e-3);
+        assert_float_eq!(chrome.pdf(&r, &rec, s.ray.direction()), 0.0, abs <= 0.0);
+    }
+
+    #[test]
+    fn dielectric() {
+        fastrand::seed(5);
+        let glass = MaterialKind::Dielectric(Dielectric::new(1.5));
+        let mut rec = HitRecord::new(&glass);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        let m = Dielectric::new(1.5).absorption(Vec3::new(0.2, 0.2, 0.85), 1.0);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.6, -0.8, 0.0));
+        let mut reflected = 0;
+        for _ in 0..10000 {
+            // tinted inside, not at the surface
+            let s = m.scatter(&r, &rec).unwrap();
+            assert_float_eq!(s.attenuation.x(), 1.0, abs <= 0.0);
+            assert_float_eq!(s.attenuation.z(), 1.0, abs <= 0.0);
+            let d = s.ray.direction().unit_vector();
+            if d.y() > 0.0 {
+                reflected += 1;
+            } else {
+                assert_float_eq!(d.x(), 0.4, abs <= 1e-5);
+            }
+        }
+        assert_float_eq!(reflected as f32 / 10000.0, schlick(0.8, 1.5), abs <= 0.01);
+    }
+
+    #[test]
+    fn rough_dielectric() {
+        fastrand::seed(3);
+        let glass = MaterialKind::RoughDielectric(RoughDielectric::new(1.5, 0.6));
+        let m = match &glass {
+            MaterialKind::RoughDielectric(m) => m,
+            _ => unreachable!(),
+        };
+        let mut rec = HitRecord::new(&glass);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        // from outside and from inside
+        for d in [Vec3::new(1.0, -2.0, 0.0), Vec3::new(1.0, 3.0, 0.5)].iter() {
+            let r = Ray::new(-*d, *d);
+            let n = 100000;
+            let mut integral = 0.0;
+            let mut energy = 0.0;
+            let mut scattered = 0;
+            let mut transmitted = 0;
+            for _ in 0..n {
+                let d = random_unit_vector();
+                integral += m.pdf(&r, &rec, d);
+                if let Some(s) = m.scatter(&r, &rec) {
+                    let d = s.ray.direction();
+                    assert_float_eq!(d.length(), 1.0, abs <= 1e-4);
+                    let pdf = s.pdf.unwrap();
+                    assert_float_eq!(pdf, m.pdf(&r, &rec, d), r2nd <= 1e-3);
+                    let f = m.eval(&r, &rec, d);
+                    assert!((f / pdf - s.attenuation).length() < 1e-3);
+                    energy += s.attenuation.x();
+                    scattered += 1;
+                    if d.y() * r.direction().y() > 0.0 {
+                        transmitted += 1;
+                    }
+                }
+            }
+            // the density misses the directions sent to the wrong side
+            let integral = integral / n as f32 * 4.0 * std::f32::consts::PI;
+            assert_float_eq!(integral, scattered as f32 / n as f32, abs <= 0.03);
+            // single scattering loses some energy, but never gains any
+            let energy = energy / n as f32;
+            assert!(energy <= 1.0 && energy > 0.8, "{}", energy);
+            assert!(transmitted > n / 2);
+        }
 
-        Some((scattered, attenuation))
+        // smooth glass refracts by Snell's law
+        let smooth = RoughDielectric::new(1.5, 0.0);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.6, -0.8, 0.0));
+        for _ in 0..100 {
+            let s = smooth.scatter(&r, &rec).unwrap();
+            assert!(s.pdf.is_none());
+            let d = s.ray.direction().unit_vector();
+            if d.y() < 0.0 {
+                assert_float_eq!(d.x(), 0.4, abs <= 1e-5);
+            } else {
+                assert_float_eq!(d.x(), 0.6, abs <= 1e-5);
+            }
+        }
+        // and reflects everything beyond the critical angle inside
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.8, 0.6, 0.0));
+        for _ in 0..100 {
+            let s = smooth.scatter(&r, &rec).unwrap();
+            assert!(s.ray.direction().y() < 0.0);
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein() {
+        fastrand::seed(1);
+        let fog = MaterialKind::Isotropic(Isotropic::new(Vec3::one()));
+        let rec = HitRecord::new(&fog);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 2.0));
+        let n = 200000;
+        for &g in [-0.5, 0.0, 0.3, 0.8].iter() {
+            let m = HenyeyGreenstein::new(Vec3::one(), g);
+            // normalized over the sphere
+            let mut sum = 0.0;
+            for _ in 0..n {
+                sum += m.pdf(&r, &rec, random_unit_vector());
+            }
+            let integral = sum / n as f32 * 4.0 * std::f32::consts::PI;
+            assert_float_eq!(integral, 1.0, abs <= 0.03);
+
+            // sampled with mean cosine g and the density pdf reports
+            let mut mean = 0.0;
+            for _ in 0..n {
+                let s = m.scatter(&r, &rec).unwrap();
+                let d = s.ray.direction();
+                assert_float_eq!(d.length(), 1.0, abs <= 1e-4);
+                assert_float_eq!(s.pdf.unwrap(), m.pdf(&r, &rec, d), r2nd <= 1e-3);
+                mean += d.z();
+            }
+            assert_float_eq!(mean / n as f32, g, abs <= 0.01);
+        }
     }
 }
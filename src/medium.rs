@@ -0,0 +1,220 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::{Isotropic, MaterialKind};
+use crate::random::rand_uniform;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use std::fmt;
+
+// what the renderer needs from a participating medium while a ray is inside
+pub trait Medium: fmt::Debug {
+    // material scattering the light, usually Isotropic or HenyeyGreenstein
+    fn phase(&self) -> &MaterialKind;
+    // where r first scatters in (t_min, t_max), if it does
+    fn sample_distance(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<f32>;
+    // fraction of light that crosses from t_min to t_max along r
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32;
+    // fraction of light per channel left by absorption from t_min to t_max,
+    // which paths are weighted by rather than sampling it
+    fn absorption(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Vec3 {
+        Vec3::one()
+    }
+}
+
+// the inside of a transparent solid, absorbing light by the Beer-Lambert law
+// without scattering it. The renderer keeps track of the interiors a path is
+// in, telling them apart by address (materials share theirs between copies
+// through an Arc), to absorb by the distance travelled inside and to turn
+// the normal out of the solid, as flat shapes face theirs to the ray
+#[derive(Debug)]
+pub struct Absorber {
+    sigma_a: Vec3,
+    // never used, as nothing scatters
+    phase: MaterialKind,
+}
+
+impl Absorber {
+    // absorption coefficients per unit length
+    pub fn new(sigma_a: Vec3) -> Absorber {
+        Absorber {
+            sigma_a,
+            phase: MaterialKind::Isotropic(Isotropic::new(Vec3::zero())),
+        }
+    }
+
+    // the coefficients leaving color of white light after distance
+    pub fn from_color(color: Vec3, distance: f32) -> Absorber {
+        let sigma = |c: f32| -c.clamp(1e-4, 1.0).ln() / distance;
+        Absorber::new(Vec3::new(
+            sigma(color.x()),
+            sigma(color.y()),
+            sigma(color.z()),
+        ))
+    }
+}
+
+impl Medium for Absorber {
+    fn phase(&self) -> &MaterialKind {
+        &self.phase
+    }
+
+    fn sample_distance(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Option<f32> {
+        None
+    }
+
+    fn transmittance(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> f32 {
+        1.0
+    }
+
+    fn absorption(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec3 {
+        let distance = (t_max - t_min) * r.direction().length();
+        let channel = |sigma: f32| {
+            if sigma == 0.0 {
+                1.0
+            } else {
+                (-sigma * distance).exp()
+            }
+        };
+        Vec3::new(
+            channel(self.sigma_a.x()),
+            channel(self.sigma_a.y()),
+            channel(self.sigma_a.z()),
+        )
+    }
+}
+
+// participating medium of uniform density filling a closed boundary with
+// outward normals; the boundary itself is invisible, and the renderer samples
+// where rays scatter inside by the phase material, usually Isotropic
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable + Send + Sync>,
+    density: f32,
+    phase: MaterialKind,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn Hittable + Send + Sync>,
+        density: f32,
+        phase: MaterialKind,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+        }
+    }
+}
+
+impl Medium for ConstantMedium {
+    fn phase(&self) -> &MaterialKind {
+        &self.phase
+    }
+
+    // the distance is exponentially distributed
+    fn sample_distance(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        let distance = -(1.0 - rand_uniform()).ln() / self.density;
+        let t = t_min + distance / r.direction().length();
+        if t < t_max {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        (-self.density * (t_max - t_min) * r.direction().length()).exp()
+    }
+}
+
+impl fmt::Debug for ConstantMedium {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConstantMedium")
+            .field("density", &self.density)
+            .field("phase", &self.phase)
+            .finish()
+    }
+}
+
+impl Hittable for ConstantMedium {
+    // the next crossing of the boundary, whose normal tells whether the ray
+    // enters or leaves the medium there
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut rec = self.boundary.hit(r, t_min, t_max)?;
+        rec.material = &self.phase;
+        rec.medium = Some(self);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Isotropic;
+    use crate::medium::*;
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+    use float_eq::assert_float_eq;
+
+    fn fog(density: f32) -> ConstantMedium {
+        let m = MaterialKind::Isotropic(Isotropic::new(Vec3::one()));
+        let boundary = Box::new(Sphere::new(Vec3::zero(), 1.0, m.clone()));
+        ConstantMedium::new(boundary, density, m)
+    }
+
+    #[test]
+    fn boundary() {
+        let f = fog(1.0);
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = f.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.0, abs <= 1e-6);
+        assert!(rec.medium.is_some());
+        assert!(matches!(rec.material, MaterialKind::Isotropic(_)));
+        // entering
+        assert!(rec.normal.dot(r.direction()) < 0.0);
+        let rec = f.hit(&r, 4.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 6.0, abs <= 1e-6);
+        assert!(rec.normal.dot(r.direction()) > 0.0);
+    }
+
+    #[test]
+    fn distance() {
+        fastrand::seed(4);
+        let f = fog(2.0);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 2.0));
+        let n = 100000;
+        let mean = (0..n)
+            .map(|_| f.sample_distance(&r, 1.0, f32::MAX).unwrap())
+            .sum::<f32>()
+            / n as f32;
+        // in units of the ray parameter
+        assert_float_eq!(mean, 1.25, abs <= 0.01);
+        // the fraction that gets through to 1.5 is the transmittance
+        let through = (0..n)
+            .filter(|_| f.sample_distance(&r, 1.0, 1.5).is_none())
+            .count();
+        let t = f.transmittance(&r, 1.0, 1.5);
+        assert_float_eq!(t, (-2.0f32).exp(), abs <= 1e-6);
+        assert_float_eq!(through as f32 / n as f32, t, abs <= 0.005);
+    }
+
+    #[test]
+    fn absorber() {
+        let a = Absorber::from_color(Vec3::new(0.5, 1.0, 0.25), 2.0);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, 2.0));
+        assert!(a.sample_distance(&r, 0.0, f32::MAX).is_none());
+        assert_float_eq!(a.transmittance(&r, 0.0, 1.0), 1.0, abs <= 0.0);
+        // the color after 2 units, and its square after 4
+        let c = a.absorption(&r, 0.5, 1.5);
+        assert_float_eq!(c.x(), 0.5, abs <= 1e-6);
+        assert_float_eq!(c.y(), 1.0, abs <= 0.0);
+        assert_float_eq!(c.z(), 0.25, abs <= 1e-6);
+        let c = a.absorption(&r, 0.0, 2.0);
+        assert_float_eq!(c.z(), 0.0625, abs <= 1e-6);
+        // clear even over an unbounded segment
+        assert_float_eq!(a.absorption(&r, 0.0, f32::MAX).y(), 1.0, abs <= 0.0);
+    }
+}
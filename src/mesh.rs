@@ -0,0 +1,276 @@
+use crate::aabb::Aabb;
+use crate::bvh::Tree;
+use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::triangle::{intersect, normal_at, shading_normal, uv_at, Triangle, DEFAULT_UV};
+use crate::vec3::Vec3;
+
+// indices into the buffers of a Mesh
+#[derive(Clone, Copy, Debug)]
+pub struct MeshTriangle {
+    pub positions: [u32; 3],
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+    pub material: u32,
+}
+
+// triangles sharing vertex buffers and a material table, with their own BVH
+pub struct Mesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f32; 2]>,
+    triangles: Vec<MeshTriangle>,
+    materials: Vec<MaterialKind>,
+    tree: Tree,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<[f32; 2]>,
+        triangles: Vec<MeshTriangle>,
+        materials: Vec<MaterialKind>,
+    ) -> Mesh {
+        let bboxes: Vec<Aabb> = triangles
+            .iter()
+            .map(|t| {
+                let points = t.positions.map(|i| positions[i as usize]);
+                Aabb::from_points(&points).padded(0.0001)
+            })
+            .collect();
+        let (tree, order) = Tree::new(&bboxes);
+        // so that each leaf refers to a contiguous range
+        let triangles = order.iter().map(|&i| triangles[i]).collect();
+
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            materials,
+            tree,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    fn points(&self, t: &MeshTriangle) -> [Vec3; 3] {
+        t.positions.map(|i| self.positions[i as usize])
+    }
+
+    // as a standalone Triangle, for light sampling
+    fn triangle(&self, t: &MeshTriangle) -> Triangle {
+        let [a, b, c] = self.points(t);
+        let mut r = Triangle::new(a, b, c, self.materials[t.material as usize].clone());
+        if let Some(uvs) = t.uvs {
+            r.set_uv(uvs.map(|i| self.uvs[i as usize]));
+        }
+        if let Some(normals) = t.normals {
+            r.set_normals(normals.map(|i| self.normals[i as usize]));
+        }
+        r
+    }
+
+    fn hit_triangle(
+        &self,
+        t: &MeshTriangle,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let points = self.points(t);
+        let [a, b, c] = points;
+        let face_normal = (b - a).cross(c - a);
+        if face_normal.squared_length() == 0.0 {
+            return None;
+        }
+        let (dist, wb, wc) = intersect(&points, r, t_min, t_max, false)?;
+        let face_normal = face_normal.unit_vector();
+
+        let mut rec = HitRecord::new(&self.materials[t.material as usize]);
+        rec.t = dist;
+        rec.p = r.point_at_parameter(dist);
+        let uv = t
+            .uvs
+            .map_or(DEFAULT_UV, |uvs| uvs.map(|i| self.uvs[i as usize]));
+        let (u, v) = uv_at(uv, wb, wc);
+        rec.u = u;
+        rec.v = v;
+        let normals = t.normals.map(|n| n.map(|i| self.normals[i as usize]));
+        let normal = normal_at(normals, face_normal, wb, wc);
+        rec.normal = shading_normal(normal, face_normal, r);
+        Some(rec)
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut rec: Option<HitRecord> = None;
+        self.tree
+            .traverse(r, t_min, t_max, |range, closest_so_far| {
+                let mut closest_so_far = closest_so_far;
+                let mut found = None;
+                for t in self.triangles[range].iter() {
+                    if let Some(hr) = self.hit_triangle(t, r, t_min, closest_so_far) {
+                        closest_so_far = hr.t;
+                        found = Some(hr.t);
+                        rec = Some(hr);
+                    }
+                }
+                found
+            });
+        rec
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.tree.bounding_box()
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        self.triangles
+            .iter()
+            .filter(|t| {
+                matches!(
+                    self.materials[t.material as usize],
+                    MaterialKind::DiffuseLight(_)
+                )
+            })
+            .map(|t| Box::new(self.triangle(t)) as Box<dyn Light + Send + Sync>)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hit::HittableList;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::mesh::*;
+    use crate::random::rand_uniform;
+    use float_eq::assert_float_eq;
+
+    fn random_vec3(scale: f32) -> Vec3 {
+        scale
+            * Vec3::new(
+                rand_uniform() - 0.5,
+                rand_uniform() - 0.5,
+                rand_uniform() - 0.5,
+            )
+    }
+
+    #[test]
+    fn normals_against_winding() {
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        ];
+        let normals = vec![Vec3::new(0.0, 0.0, -1.0)];
+        let triangles = vec![MeshTriangle {
+            positions: [0, 1, 2],
+            normals: Some([0, 0, 0]),
+            uvs: None,
+            material: 0,
+        }];
+        let materials = vec![MaterialKind::Lambertian(Lambertian::new(Vec3::one()))];
+        let mesh = Mesh::new(positions, normals, vec![], triangles, materials);
+        for &z in [1.0, -1.0].iter() {
+            let r = Ray::new(Vec3::new(0.5, 0.5, z), Vec3::new(0.0, 0.0, -z));
+            let rec = mesh.hit(&r, 0.0, f32::MAX).unwrap();
+            assert_float_eq!(rec.normal.z(), z, abs <= 1e-6);
+        }
+    }
+
+    #[test]
+    fn same_hits_as_triangles() {
+        fastrand::seed(2);
+        let materials = vec![
+            MaterialKind::Lambertian(Lambertian::new(Vec3::one())),
+            MaterialKind::Lambertian(Lambertian::new(Vec3::zero())),
+        ];
+        let positions: Vec<Vec3> = (0..600).map(|_| random_vec3(10.0)).collect();
+        let normals: Vec<Vec3> = (0..300).map(|_| random_vec3(1.0).unit_vector()).collect();
+        let uvs: Vec<[f32; 2]> = (0..300).map(|_| [rand_uniform(), rand_uniform()]).collect();
+        let index = || (rand_uniform() * 300.0) as u32 % 300;
+        let triangles: Vec<MeshTriangle> = (0..200)
+            .map(|i| MeshTriangle {
+                positions: [3 * i, 3 * i + 1, 3 * i + 2],
+                normals: if i % 2 == 0 {
+                    Some([index(), index(), index()])
+                } else {
+                    None
+                },
+                uvs: if i % 3 == 0 {
+                    Some([index(), index(), index()])
+                } else {
+                    None
+                },
+                material: i % 2,
+            })
+            .collect();
+        let mesh = Mesh::new(positions, normals, uvs, triangles, materials);
+        assert_eq!(mesh.len(), 200);
+
+        let mut world = HittableList::new();
+        for t in mesh.triangles.iter() {
+            world.list.push(Box::new(mesh.triangle(t)));
+        }
+
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let r = Ray::new(random_vec3(30.0), random_vec3(1.0));
+            match (
+                world.hit(&r, 0.001, f32::MAX),
+                mesh.hit(&r, 0.001, f32::MAX),
+            ) {
+                (Some(a), Some(b)) => {
+                    assert_float_eq!(a.t, b.t, r2nd <= 1e-4);
+                    assert!((a.normal - b.normal).length() < 1e-5);
+                    assert!((a.u - b.u).abs() < 1e-5 && (a.v - b.v).abs() < 1e-5);
+                    hits += 1;
+                }
+                (None, None) => {}
+                (a, b) => panic!("{:?} != {:?}", a, b),
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn emissive_triangles() {
+        let materials = vec![
+            MaterialKind::Lambertian(Lambertian::new(Vec3::one())),
+            MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::one())),
+        ];
+        let positions = vec![
+            Vec3::zero(),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        ];
+        let triangle = |positions, material| MeshTriangle {
+            positions,
+            normals: None,
+            uvs: None,
+            material,
+        };
+        let mesh = Mesh::new(
+            positions,
+            vec![],
+            vec![],
+            vec![triangle([0, 1, 2], 0), triangle([1, 3, 2], 1)],
+            materials,
+        );
+        let lights = mesh.lights();
+        assert_eq!(lights.len(), 1);
+        assert!((lights[0].area() - 0.5).abs() < 1e-6);
+    }
+}
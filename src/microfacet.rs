@@ -0,0 +1,266 @@
+use crate::material::{reflect, refract};
+use crate::random::rand_uniform;
+use crate::vec3::Vec3;
+use std::f32::consts::PI;
+
+// GGX (Trowbridge-Reitz) distribution of microfacet normals with Smith
+// masking-shadowing, in a shading frame where the macro normal is +z
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    // roughness in [0, 1] is squared into alpha, which looks more linear
+    pub fn from_roughness(roughness: f32) -> Ggx {
+        let r = roughness.clamp(0.0, 1.0);
+        Ggx {
+            alpha: (r * r).max(1e-4),
+        }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    // too sharp to be sampled or evaluated as anything but a mirror
+    pub fn is_smooth(&self) -> bool {
+        self.alpha <= 1e-3
+    }
+
+    // density of microfacet normals h per projected solid angle
+    pub fn d(&self, h: Vec3) -> f32 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let cos2 = h.z() * h.z();
+        let denom = cos2 * (a2 - 1.0) + 1.0;
+        a2 / (PI * denom * denom)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z() * w.z();
+        if cos2 == 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    // the fraction of microfacets visible from w
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // the fraction visible from both directions, height correlated
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // a microfacet normal visible from wo, with density pdf_visible (Heitz,
+    // "Sampling the GGX Distribution of Visible Normals", JCGT 2018); wo is
+    // on the upper side
+    pub fn sample_visible(&self, wo: Vec3, u1: f32, u2: f32) -> Vec3 {
+        let a = self.alpha;
+        let vh = Vec3::new(a * wo.x(), a * wo.y(), wo.z()).unit_vector();
+        let len2 = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Vec3::new(a * nh.x(), a * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+
+    // density of h from sample_visible, per solid angle
+    pub fn pdf_visible(&self, wo: Vec3, h: Vec3) -> f32 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z()
+    }
+
+    // a direction reflected or refracted by a visible microfacet of an
+    // interface between dielectrics, picked by its Fresnel reflectance, with
+    // eta the index below over the index above; None if it ends up on the
+    // wrong side of the surface
+    pub fn sample_dielectric(&self, wo: Vec3, eta: f32) -> Option<Vec3> {
+        let h = self.sample_visible(wo, rand_uniform(), rand_uniform());
+        if wo.dot(h) <= 0.0 {
+            return None;
+        }
+        if rand_uniform() < fresnel_dielectric(wo.dot(h), eta) {
+            Some(reflect(-wo, h)).filter(|wi| wi.z() > 0.0)
+        } else {
+            refract(-wo, h, 1.0 / eta)
+                .map(|wi| wi.unit_vector())
+                .filter(|wi| wi.z() < 0.0)
+        }
+    }
+
+    // BSDF times cosine of that interface and the density of
+    // sample_dielectric (Walter et al., "Microfacet Models for Refraction
+    // through Rough Surfaces", 2007); the transmitted part leaves out the
+    // 1 / eta^2 scaling of radiance, which cancels on the way out
+    pub fn eval_dielectric(&self, wo: Vec3, wi: Vec3, eta: f32) -> (f32, f32) {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return (0.0, 0.0);
+        }
+        if wi.z() > 0.0 {
+            let h = (wo + wi).unit_vector();
+            if wo.dot(h) <= 0.0 {
+                return (0.0, 0.0);
+            }
+            let fresnel = fresnel_dielectric(wo.dot(h), eta);
+            let f = fresnel * self.d(h) * self.g2(wo, wi) / (4.0 * wo.z());
+            let pdf = fresnel * self.pdf_visible(wo, h) / (4.0 * wo.dot(h));
+            (f, pdf)
+        } else {
+            // the microfacet refracting wo into wi, on the upper side
+            let mut h = -(wo + eta * wi);
+            if h.squared_length() == 0.0 {
+                return (0.0, 0.0);
+            }
+            h = h.unit_vector();
+            if h.z() < 0.0 {
+                h = -h;
+            }
+            let (wo_h, wi_h) = (wo.dot(h), wi.dot(h));
+            if wo_h <= 0.0 || wi_h >= 0.0 {
+                return (0.0, 0.0);
+            }
+            let fresnel = fresnel_dielectric(wo_h, eta);
+            let denom = wo_h + eta * wi_h;
+            // dh / dwi
+            let jacobian = eta * eta * -wi_h / (denom * denom);
+            let f = (1.0 - fresnel) * self.d(h) * self.g2(wo, wi) * wo_h * jacobian / wo.z();
+            let pdf = (1.0 - fresnel) * self.pdf_visible(wo, h) * jacobian;
+            (f, pdf)
+        }
+    }
+}
+
+// unpolarized Fresnel reflectance of a conductor with complex index of
+// refraction eta + i k, seen from air at an angle with cosine cos_theta
+pub fn fresnel_conductor(cos_theta: f32, eta: Vec3, k: Vec3) -> Vec3 {
+    let c = cos_theta.clamp(0.0, 1.0);
+    let channel = |eta: f32, k: f32| {
+        let cos2 = c * c;
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * c * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    };
+    Vec3::new(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
+
+// unpolarized Fresnel reflectance of an interface between dielectrics, with
+// eta the index on the far side over the index on the side light comes from
+pub fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    // total internal reflection
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::microfacet::*;
+    use crate::random::{rand_uniform, random_unit_vector};
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn distribution() {
+        fastrand::seed(4);
+        let n = 200000;
+        for &roughness in [0.5, 0.7, 1.0].iter() {
+            let ggx = Ggx::from_roughness(roughness);
+            // projected normals cover the unit disk
+            let mut sum = 0.0;
+            for _ in 0..n {
+                let h = random_unit_vector();
+                sum += ggx.d(h) * h.z().max(0.0);
+            }
+            assert_float_eq!(sum / n as f32 * 4.0 * PI, 1.0, abs <= 0.03);
+
+            // the visible normals of any direction integrate to 1
+            let wo = Vec3::new(0.6, 0.0, 0.8);
+            let mut sum = 0.0;
+            for _ in 0..n {
+                sum += ggx.pdf_visible(wo, random_unit_vector());
+            }
+            assert_float_eq!(sum / n as f32 * 4.0 * PI, 1.0, abs <= 0.03);
+
+            // and are what sample_visible draws: compare the mean of h
+            let mut mean = Vec3::zero();
+            let mut expected = Vec3::zero();
+            for _ in 0..n {
+                let h = ggx.sample_visible(wo, rand_uniform(), rand_uniform());
+                assert!(h.z() > 0.0 && wo.dot(h) >= -1e-4);
+                mean += h;
+                let h = random_unit_vector();
+                expected += 4.0 * PI * ggx.pdf_visible(wo, h) * h;
+            }
+            let diff = (mean - expected) / n as f32;
+            assert!(diff.length() < 0.02, "{:?}", diff);
+        }
+    }
+
+    #[test]
+    fn fresnel() {
+        // real eta reduces to the dielectric reflectance, 4% head on for 1.5
+        let r = fresnel_conductor(1.0, Vec3::new(1.5, 1.5, 1.5), Vec3::zero());
+        assert_float_eq!(r.x(), 0.04, abs <= 1e-6);
+        // ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2) at normal incidence
+        let r = fresnel_conductor(1.0, Vec3::new(0.2, 1.0, 1.0), Vec3::new(3.9, 2.0, 0.0));
+        assert_float_eq!(r.x(), (0.64 + 15.21) / (1.44 + 15.21), abs <= 1e-5);
+        assert_float_eq!(r.y(), 4.0 / 8.0, abs <= 1e-5);
+        assert_float_eq!(r.z(), 0.0, abs <= 1e-6);
+        // everything is reflected at grazing angles
+        let r = fresnel_conductor(0.0, Vec3::new(0.2, 1.5, 1.0), Vec3::new(3.9, 0.0, 2.0));
+        assert_float_eq!(r.x(), 1.0, abs <= 1e-5);
+        assert_float_eq!(r.y(), 1.0, abs <= 1e-5);
+
+        assert_float_eq!(fresnel_dielectric(1.0, 1.5), 0.04, abs <= 1e-6);
+        assert_float_eq!(fresnel_dielectric(1.0, 1.0 / 1.5), 0.04, abs <= 1e-6);
+        assert_float_eq!(fresnel_dielectric(0.0, 1.5), 1.0, abs <= 1e-6);
+        // Brewster's angle, where only s polarized light is reflected
+        let brewster = 1.5f32.atan().cos();
+        let cos_t = (1.0f32 - (1.0 - brewster * brewster) / 2.25).sqrt();
+        let rs = (brewster - 1.5 * cos_t) / (brewster + 1.5 * cos_t);
+        assert_float_eq!(
+            fresnel_dielectric(brewster, 1.5),
+            0.5 * rs * rs,
+            abs <= 1e-6
+        );
+        // and beyond the critical angle from inside
+        assert_float_eq!(fresnel_dielectric(0.6, 1.0 / 1.5), 1.0, abs <= 0.0);
+        let conductor = fresnel_conductor(0.6, Vec3::new(1.5, 1.5, 1.5), Vec3::zero());
+        assert_float_eq!(fresnel_dielectric(0.6, 1.5), conductor.x(), abs <= 1e-5);
+    }
+}
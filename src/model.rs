@@ -1,64 +1,68 @@
 use crate::hit::Hittable;
 use crate::material::Dielectric;
+use crate::material::DiffuseLight;
 use crate::material::Lambertian;
 use crate::material::MaterialKind;
-use crate::material::Metal;
+use crate::mesh::{Mesh, MeshTriangle};
+use crate::obj::{Obj, ObjError};
+use crate::quad::Quad;
 use crate::triangle::Triangle;
 use crate::vec3::Vec3;
 
 pub fn ramiel(position: Vec3, scale: f32) -> Vec<Box<dyn Hittable + Send + Sync>> {
     let mut r: Vec<Box<dyn Hittable + Send + Sync>> = vec![];
 
-    let mat = MaterialKind::Dielectric(Dielectric::new(1.1, Vec3::new(0.2, 0.2, 0.85)));
+    let mat =
+        MaterialKind::Dielectric(Dielectric::new(1.1).absorption(Vec3::new(0.2, 0.2, 0.85), scale));
     let hight_scale = 0.9;
 
     let t1 = Triangle::new(
         Vec3::new(1.0, 0.0, 0.0) * scale + position,
         Vec3::new(0.0, 1.0 * hight_scale, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, 1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t2 = Triangle::new(
         Vec3::new(0.0, 1.0 * hight_scale, 0.0) * scale + position,
         Vec3::new(-1.0, 0.0, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, 1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t3 = Triangle::new(
         Vec3::new(-1.0, 0.0, 0.0) * scale + position,
-        Vec3::new(0.0, -1.0 * hight_scale, 0.0) * scale + position,
+        Vec3::new(0.0, -hight_scale, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, 1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t4 = Triangle::new(
-        Vec3::new(0.0, -1.0 * hight_scale, 0.0) * scale + position,
+        Vec3::new(0.0, -hight_scale, 0.0) * scale + position,
         Vec3::new(1.0, 0.0, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, 1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t5 = Triangle::new(
         Vec3::new(1.0, 0.0, 0.0) * scale + position,
         Vec3::new(0.0, 1.0 * hight_scale, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, -1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t6 = Triangle::new(
         Vec3::new(0.0, 1.0 * hight_scale, 0.0) * scale + position,
         Vec3::new(-1.0, 0.0, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, -1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t7 = Triangle::new(
         Vec3::new(-1.0, 0.0, 0.0) * scale + position,
-        Vec3::new(0.0, -1.0 * hight_scale, 0.0) * scale + position,
+        Vec3::new(0.0, -hight_scale, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, -1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
     let t8 = Triangle::new(
-        Vec3::new(0.0, -1.0 * hight_scale, 0.0) * scale + position,
+        Vec3::new(0.0, -hight_scale, 0.0) * scale + position,
         Vec3::new(1.0, 0.0, 0.0) * scale + position,
         Vec3::new(0.0, 0.0, -1.0) * scale + position,
-        mat,
+        mat.clone(),
     );
 
     r.push(Box::new(t1));
@@ -76,88 +80,85 @@ pub fn ramiel(position: Vec3, scale: f32) -> Vec<Box<dyn Hittable + Send + Sync>
 pub fn wall() -> Vec<Box<dyn Hittable + Send + Sync>> {
     let pc = MaterialKind::Lambertian(Lambertian::new(Vec3::new(1.0, 1.0, 1.0)));
     let ps: f32 = 10.0;
+    let corner = Vec3::new(-ps, 0.0, -ps);
+    let x = Vec3::new(2.0 * ps, 0.0, 0.0);
+    let y = Vec3::new(0.0, ps, 0.0);
+    let z = Vec3::new(0.0, 0.0, 2.0 * ps);
 
-    let mut r: Vec<Box<dyn Hittable + Send + Sync>> = vec![];
-    // ground plane
-    r.push(Box::new(Triangle::new(
-        Vec3::new(-ps, 0.0, -ps),
-        Vec3::new(ps, 0.0, -ps),
-        Vec3::new(-ps, 0.0, ps),
-        pc,
-    )));
-    r.push(Box::new(Triangle::new(
-        Vec3::new(ps, 0.0, ps),
-        Vec3::new(ps, 0.0, -ps),
-        Vec3::new(-ps, 0.0, ps),
-        pc,
-    )));
+    vec![
+        // ground plane
+        Box::new(Quad::new(corner, x, z, pc.clone())),
+        // y-z
+        Box::new(Quad::new(corner, y, z, pc.clone())),
+        // y-x
+        Box::new(Quad::new(corner, y, x, pc)),
+    ]
+}
 
-    // y-z
-    r.push(Box::new(Triangle::new(
-        Vec3::new(-ps, 0.0, -ps),
-        Vec3::new(-ps, ps, -ps),
-        Vec3::new(-ps, 0.0, ps),
-        pc,
-    )));
-    r.push(Box::new(Triangle::new(
-        Vec3::new(-ps, ps, ps),
-        Vec3::new(-ps, ps, -ps),
-        Vec3::new(-ps, 0.0, ps),
-        pc,
-    )));
+// 555 unit box open towards -z, lit by a quad light under the ceiling
+pub fn cornell_box() -> Vec<Box<dyn Hittable + Send + Sync>> {
+    let red = MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.65, 0.05, 0.05)));
+    let white = MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.73, 0.73, 0.73)));
+    let green = MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.12, 0.45, 0.15)));
+    let light = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::new(15.0, 15.0, 15.0)));
 
-    // y-x
-    r.push(Box::new(Triangle::new(
-        Vec3::new(-ps, 0.0, -ps),
-        Vec3::new(-ps, ps, -ps),
-        Vec3::new(ps, 0.0, -ps),
-        pc,
-    )));
-    r.push(Box::new(Triangle::new(
-        Vec3::new(ps, ps, -ps),
-        Vec3::new(-ps, ps, -ps),
-        Vec3::new(ps, 0.0, -ps),
-        pc,
-    )));
+    let s = 555.0;
+    let x = Vec3::new(s, 0.0, 0.0);
+    let y = Vec3::new(0.0, s, 0.0);
+    let z = Vec3::new(0.0, 0.0, s);
 
-    return r;
+    vec![
+        Box::new(Quad::new(Vec3::zero(), y, z, green)),
+        Box::new(Quad::new(x, y, z, red)),
+        Box::new(Quad::new(Vec3::zero(), x, z, white.clone())),
+        Box::new(Quad::new(y, x, z, white.clone())),
+        Box::new(Quad::new(z, x, y, white)),
+        Box::new(Quad::new(
+            Vec3::new(213.0, 554.0, 227.0),
+            Vec3::new(130.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 105.0),
+            light,
+        )),
+    ]
 }
 
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::BufReader;
-
+// faces without a usemtl material get mat, and faces without normals get
+// smooth ones except across edges sharper than crease_angle degrees
 pub fn load_obj(
     obj_file_path: &str,
-) -> Result<Vec<Box<dyn Hittable + Send + Sync>>, std::io::Error> {
-    let file = File::open(obj_file_path)?;
-
-    let mut vs = vec![];
-    let mut r: Vec<Box<dyn Hittable + Send + Sync>> = vec![];
+    mat: MaterialKind,
+    crease_angle: f32,
+) -> Result<Mesh, ObjError> {
+    let mut obj = Obj::load(obj_file_path)?;
+    obj.generate_normals(crease_angle);
 
-    // let mat = MaterialKind::Dielectric(Dielectric::new(1.1, Vec3::new(0.2, 0.2, 0.85)));
-    let mat = MaterialKind::Metal(Metal::new(Vec3::new(0.9, 0.9, 0.9), 1.0));
-    let buf_reader = BufReader::new(file);
-    for line in buf_reader.lines() {
-        let line = line.unwrap();
-        let cs: Vec<_> = line.split_whitespace().collect();
-        if cs.len() == 0 {
-            continue;
-        } else if cs[0] == "v" {
-            vs.push(Vec3::new(
-                cs[1].parse().unwrap(),
-                cs[2].parse().unwrap(),
-                cs[3].parse().unwrap(),
-            ));
-        } else if cs[0] == "f" {
-            r.push(Box::new(Triangle::new(
-                vs[cs[1].parse::<usize>().unwrap() - 1],
-                vs[cs[2].parse::<usize>().unwrap() - 1],
-                vs[cs[3].parse::<usize>().unwrap() - 1],
-                mat,
-            )));
-        }
-    }
+    let default_material = obj.materials.len() as u32;
+    let triangles = obj
+        .faces
+        .iter()
+        .map(|f| {
+            let [a, b, c] = f.vertices;
+            let index = |i: Option<usize>| i.map(|i| i as u32);
+            let all = |i: [Option<u32>; 3]| match i {
+                [Some(a), Some(b), Some(c)] => Some([a, b, c]),
+                _ => None,
+            };
+            MeshTriangle {
+                positions: [a, b, c].map(|v| v.position as u32),
+                normals: all([a, b, c].map(|v| index(v.normal))),
+                uvs: all([a, b, c].map(|v| index(v.uv))),
+                material: f.material.map_or(default_material, |i| i as u32),
+            }
+        })
+        .collect();
+    let mut materials: Vec<MaterialKind> = obj.materials.into_iter().map(|m| m.material).collect();
+    materials.push(mat);
 
-    Ok(r)
+    Ok(Mesh::new(
+        obj.positions,
+        obj.normals,
+        obj.uvs,
+        triangles,
+        materials,
+    ))
 }
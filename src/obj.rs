@@ -0,0 +1,781 @@
+use crate::material::{Dielectric, DiffuseLight, Lambertian, MaterialKind, Metal};
+use crate::texture::{ImageTexture, TextureKind};
+use crate::vec3::Vec3;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+// Wavefront OBJ geometry with its MTL materials, polygons already split into triangles
+pub struct Obj {
+    pub positions: Vec<Vec3>,
+    pub uvs: Vec<[f32; 2]>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<ObjMaterial>,
+    // faces of each `o` or `g` statement
+    pub groups: Vec<Group>,
+}
+
+// zero based indices into the buffers of Obj
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Face {
+    pub vertices: [FaceVertex; 3],
+    // index into Obj::materials, None before any usemtl
+    pub material: Option<usize>,
+}
+
+pub struct ObjMaterial {
+    pub name: String,
+    pub material: MaterialKind,
+}
+
+pub struct Group {
+    pub name: String,
+    pub faces: Range<usize>,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(String, std::io::Error),
+    Parse {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    Texture(String, image::ImageError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, e) => write!(f, "{}: {}", path, e),
+            ObjError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path, line, message),
+            ObjError::Texture(path, e) => write!(f, "{}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// statements of a file with comments removed and `\` continuations joined,
+// along with the line they start on
+fn statements(src: &str) -> Vec<(usize, String)> {
+    let mut r = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (i, line) in src.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(j) => &line[..j],
+            None => line,
+        };
+        let (line, continued) = match line.trim_end().strip_suffix('\\') {
+            Some(l) => (l, true),
+            None => (line, false),
+        };
+        let (start, mut text) = pending.take().unwrap_or((i + 1, String::new()));
+        text.push(' ');
+        text.push_str(line);
+        if continued {
+            pending = Some((start, text));
+        } else if !text.trim().is_empty() {
+            r.push((start, text));
+        }
+    }
+    if let Some(p) = pending {
+        r.push(p);
+    }
+    r
+}
+
+struct Parser<'a> {
+    path: &'a str,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_string(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn float(&self, s: Option<&str>) -> Result<f32, ObjError> {
+        let s = s.ok_or_else(|| self.error("missing number".to_string()))?;
+        // NaN and infinities parse, but can't be ordered or bounded
+        s.parse()
+            .ok()
+            .filter(|x: &f32| x.is_finite())
+            .ok_or_else(|| self.error(format!("invalid number `{}`", s)))
+    }
+
+    fn floats<const N: usize>(&self, args: &[&str]) -> Result<[f32; N], ObjError> {
+        let mut r = [0.0; N];
+        for (i, x) in r.iter_mut().enumerate() {
+            *x = self.float(args.get(i).copied())?;
+        }
+        Ok(r)
+    }
+
+    // 1 based, or negative relative to the end of a buffer of length len
+    fn index(&self, s: &str, len: usize, what: &str) -> Result<usize, ObjError> {
+        let i: i64 = s
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index `{}`", what, s)))?;
+        let r = if i > 0 { i - 1 } else { len as i64 + i };
+        if i == 0 || r < 0 || r >= len as i64 {
+            return Err(self.error(format!("{} index {} out of range", what, i)));
+        }
+        Ok(r as usize)
+    }
+}
+
+impl Obj {
+    pub fn load(obj_file_path: &str) -> Result<Obj, ObjError> {
+        let src = std::fs::read_to_string(obj_file_path)
+            .map_err(|e| ObjError::Io(obj_file_path.to_string(), e))?;
+        let base_dir = Path::new(obj_file_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        Obj::parse(&src, obj_file_path, base_dir)
+    }
+
+    // path is only used in errors, mtllib files are looked up relative to base_dir
+    pub fn parse(src: &str, path: &str, base_dir: &Path) -> Result<Obj, ObjError> {
+        let mut obj = Obj {
+            positions: vec![],
+            uvs: vec![],
+            normals: vec![],
+            faces: vec![],
+            materials: vec![],
+            groups: vec![],
+        };
+        let mut material = None;
+        let mut p = Parser { path, line: 0 };
+
+        for (line, text) in statements(src) {
+            p.line = line;
+            let mut words = text.split_whitespace();
+            let keyword = match words.next() {
+                Some(k) => k,
+                None => continue,
+            };
+            let args: Vec<&str> = words.collect();
+            match keyword {
+                "v" => {
+                    let [x, y, z] = p.floats(&args)?;
+                    obj.positions.push(Vec3::new(x, y, z));
+                }
+                "vt" => {
+                    let u = p.float(args.first().copied())?;
+                    let v = match args.get(1) {
+                        Some(v) => p.float(Some(v))?,
+                        None => 0.0,
+                    };
+                    obj.uvs.push([u, v]);
+                }
+                "vn" => {
+                    let [x, y, z] = p.floats(&args)?;
+                    obj.normals.push(Vec3::new(x, y, z));
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(p.error("a face needs at least 3 vertices".to_string()));
+                    }
+                    let vertices = args
+                        .iter()
+                        .map(|a| obj.face_vertex(&p, a))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let points: Vec<Vec3> =
+                        vertices.iter().map(|v| obj.positions[v.position]).collect();
+                    for [a, b, c] in triangulate(&points) {
+                        obj.faces.push(Face {
+                            vertices: [vertices[a], vertices[b], vertices[c]],
+                            material,
+                        });
+                    }
+                }
+                "o" | "g" => {
+                    if let Some(g) = obj.groups.last_mut() {
+                        g.faces.end = obj.faces.len();
+                    }
+                    obj.groups.push(Group {
+                        name: args.join(" "),
+                        faces: obj.faces.len()..obj.faces.len(),
+                    });
+                }
+                "mtllib" => {
+                    if args.is_empty() {
+                        return Err(p.error("missing material library".to_string()));
+                    }
+                    // one or more files, or a single name with spaces in it
+                    let joined = args.join(" ");
+                    let files = if args.iter().all(|f| base_dir.join(f).is_file())
+                        || !base_dir.join(&joined).is_file()
+                    {
+                        args.clone()
+                    } else {
+                        vec![joined.as_str()]
+                    };
+                    for file in files {
+                        let mtl_path = base_dir.join(file);
+                        let mtl_path = mtl_path.to_string_lossy();
+                        let src = std::fs::read_to_string(mtl_path.as_ref())
+                            .map_err(|e| ObjError::Io(mtl_path.to_string(), e))?;
+                        let mtl_dir = Path::new(mtl_path.as_ref())
+                            .parent()
+                            .unwrap_or_else(|| Path::new(""));
+                        obj.materials.extend(parse_mtl(&src, &mtl_path, mtl_dir)?);
+                    }
+                }
+                "usemtl" => {
+                    let name = args.join(" ");
+                    // the last definition wins, as with most importers
+                    material = obj.materials.iter().rposition(|m| m.name == name);
+                    if material.is_none() {
+                        return Err(p.error(format!("unknown material `{}`", name)));
+                    }
+                }
+                // smoothing groups, lines, points, curves and the rest
+                _ => {}
+            }
+        }
+        if let Some(g) = obj.groups.last_mut() {
+            g.faces.end = obj.faces.len();
+        }
+        Ok(obj)
+    }
+
+    // gives every face without normals angle-weighted vertex normals, averaged
+    // over the faces around each position whose normals are within crease_angle
+    // degrees of the face's own, so that sharper edges stay hard
+    pub fn generate_normals(&mut self, crease_angle: f32) {
+        let cos_crease = crease_angle.to_radians().cos();
+        let face_normals: Vec<Vec3> = self
+            .faces
+            .iter()
+            .map(|f| {
+                let [a, b, c] = f.vertices.map(|v| self.positions[v.position]);
+                let n = (b - a).cross(c - a);
+                if n.squared_length() > 0.0 {
+                    n.unit_vector()
+                } else {
+                    Vec3::zero()
+                }
+            })
+            .collect();
+
+        // corners around each position, welding duplicated positions
+        let mut corners: HashMap<[u32; 3], Vec<(usize, f32)>> = HashMap::new();
+        let key = |p: Vec3| [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+        for (i, f) in self.faces.iter().enumerate() {
+            let p = f.vertices.map(|v| self.positions[v.position]);
+            for k in 0..3 {
+                let e1 = p[(k + 1) % 3] - p[k];
+                let e2 = p[(k + 2) % 3] - p[k];
+                let cosine = e1.dot(e2) / (e1.length() * e2.length());
+                let angle = if cosine.is_nan() {
+                    0.0
+                } else {
+                    cosine.clamp(-1.0, 1.0).acos()
+                };
+                corners.entry(key(p[k])).or_default().push((i, angle));
+            }
+        }
+
+        // corners smoothed over the same faces share their normal
+        let mut generated: HashMap<([u32; 3], Vec<usize>), Option<usize>> = HashMap::new();
+        for (i, face) in self.faces.iter_mut().enumerate() {
+            if face.vertices.iter().all(|v| v.normal.is_some())
+                || face_normals[i].squared_length() == 0.0
+            {
+                continue;
+            }
+            for v in face.vertices.iter_mut() {
+                let k = key(self.positions[v.position]);
+                let smoothed: Vec<(usize, f32)> = corners[&k]
+                    .iter()
+                    .copied()
+                    .filter(|&(j, _)| face_normals[i].dot(face_normals[j]) >= cos_crease)
+                    .collect();
+                let faces = smoothed.iter().map(|&(j, _)| j).collect();
+                let normals = &mut self.normals;
+                v.normal = *generated.entry((k, faces)).or_insert_with(|| {
+                    let mut n = Vec3::zero();
+                    for &(j, angle) in smoothed.iter() {
+                        n += angle * face_normals[j];
+                    }
+                    if n.squared_length() > 0.0 {
+                        normals.push(n.unit_vector());
+                        Some(normals.len() - 1)
+                    } else {
+                        None
+                    }
+                });
+            }
+        }
+    }
+
+    // `v`, `v/vt`, `v//vn` or `v/vt/vn`
+    fn face_vertex(&self, p: &Parser, s: &str) -> Result<FaceVertex, ObjError> {
+        let mut parts = s.split('/');
+        let position = p.index(parts.next().unwrap_or(""), self.positions.len(), "vertex")?;
+        let uv = match parts.next() {
+            Some(t) if !t.is_empty() => Some(p.index(t, self.uvs.len(), "texture")?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(n) if !n.is_empty() => Some(p.index(n, self.normals.len(), "normal")?),
+            _ => None,
+        };
+        if parts.next().is_some() {
+            return Err(p.error(format!("invalid face vertex `{}`", s)));
+        }
+        Ok(FaceVertex {
+            position,
+            uv,
+            normal,
+        })
+    }
+}
+
+// splits a polygon into triangles by ear clipping in the plane it mostly
+// lies in, falling back to a fan when that fails on degenerate input
+fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    let fan = || (1..n - 1).map(|i| [0, i, i + 1]).collect();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method
+    let mut normal = Vec3::zero();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        normal += Vec3::new(
+            (a.y() - b.y()) * (a.z() + b.z()),
+            (a.z() - b.z()) * (a.x() + b.x()),
+            (a.x() - b.x()) * (a.y() + b.y()),
+        );
+    }
+    let abs = [normal.x().abs(), normal.y().abs(), normal.z().abs()];
+    // huge coordinates can overflow the sums
+    let size = abs[0] + abs[1] + abs[2];
+    if size == 0.0 || !size.is_finite() {
+        return fan();
+    }
+    // drop the dominant axis and keep the polygon counterclockwise
+    let axis = (0..3).max_by(|&a, &b| abs[a].total_cmp(&abs[b])).unwrap();
+    let (i, j) = ((axis + 1) % 3, (axis + 2) % 3);
+    let sign = normal[axis].signum();
+    let p2: Vec<[f32; 2]> = points.iter().map(|p| [p[i], p[j]]).collect();
+    let cross = |o: usize, a: usize, b: usize| {
+        let (o, a, b) = (p2[o], p2[a], p2[b]);
+        sign * ((a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0]))
+    };
+
+    let mut rest: Vec<usize> = (0..n).collect();
+    let mut r = vec![];
+    while rest.len() > 3 {
+        let m = rest.len();
+        let ear = (0..m).find(|&k| {
+            let (a, b, c) = (rest[(k + m - 1) % m], rest[k], rest[(k + 1) % m]);
+            if cross(a, b, c) <= 0.0 {
+                return false;
+            }
+            rest.iter().all(|&q| {
+                q == a
+                    || q == b
+                    || q == c
+                    || cross(a, b, q) < 0.0
+                    || cross(b, c, q) < 0.0
+                    || cross(c, a, q) < 0.0
+            })
+        });
+        match ear {
+            Some(k) => {
+                r.push([rest[(k + m - 1) % m], rest[k], rest[(k + 1) % m]]);
+                rest.remove(k);
+            }
+            None => return fan(),
+        }
+    }
+    r.push([rest[0], rest[1], rest[2]]);
+    r
+}
+
+#[derive(Default)]
+struct MtlDesc {
+    name: String,
+    kd: Option<[f32; 3]>,
+    ks: [f32; 3],
+    ke: [f32; 3],
+    tf: Option<[f32; 3]>,
+    ns: f32,
+    ni: f32,
+    dissolve: f32,
+    illum: u32,
+    map_kd: Option<TextureKind>,
+}
+
+impl MtlDesc {
+    fn new(name: String) -> MtlDesc {
+        MtlDesc {
+            name,
+            ni: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            ..Default::default()
+        }
+    }
+
+    // the closest material this renderer has, in order of precedence:
+    // emissive, transparent, mirror-like and diffuse
+    fn build(self) -> ObjMaterial {
+        let v = |c: [f32; 3]| Vec3::new(c[0], c[1], c[2]);
+        let is_black = |c: [f32; 3]| c.iter().all(|&x| x <= 0.0);
+        let albedo: TextureKind = match self.map_kd {
+            Some(t) => t,
+            None => v(self.kd.unwrap_or([0.8, 0.8, 0.8])).into(),
+        };
+        let material = if !is_black(self.ke) {
+            MaterialKind::DiffuseLight(DiffuseLight::new(v(self.ke)))
+        } else if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            // the transmission filter as the color after a unit of distance
+            let glass = Dielectric::new(self.ni);
+            MaterialKind::Dielectric(match self.tf {
+                Some(tf) => glass.absorption(v(tf), 1.0),
+                None => glass,
+            })
+        } else if matches!(self.illum, 3 | 5)
+            || (self.kd.is_some_and(is_black) && !is_black(self.ks))
+        {
+            // Blinn-Phong exponent to a roughness
+            let fuzz = (2.0 / (self.ns.max(0.0) + 2.0)).sqrt();
+            MaterialKind::Metal(Metal::new(v(self.ks), fuzz))
+        } else {
+            MaterialKind::Lambertian(Lambertian::new(albedo))
+        };
+        ObjMaterial {
+            name: self.name,
+            material,
+        }
+    }
+}
+
+// texture files are looked up relative to base_dir
+pub fn parse_mtl(src: &str, path: &str, base_dir: &Path) -> Result<Vec<ObjMaterial>, ObjError> {
+    let mut r = vec![];
+    let mut current: Option<MtlDesc> = None;
+    let mut p = Parser { path, line: 0 };
+
+    for (line, text) in statements(src) {
+        p.line = line;
+        let mut words = text.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = words.collect();
+        if keyword == "newmtl" {
+            if let Some(m) = current.take() {
+                r.push(m.build());
+            }
+            current = Some(MtlDesc::new(args.join(" ")));
+            continue;
+        }
+        let m = match current.as_mut() {
+            Some(m) => m,
+            None => return Err(p.error(format!("`{}` before newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => m.kd = Some(p.floats(&args)?),
+            "Ks" => m.ks = p.floats(&args)?,
+            "Ke" => m.ke = p.floats(&args)?,
+            "Tf" => m.tf = Some(p.floats(&args)?),
+            "Ns" => m.ns = p.float(args.first().copied())?,
+            "Ni" => m.ni = p.float(args.first().copied())?,
+            "d" => m.dissolve = p.float(args.first().copied())?,
+            "Tr" => m.dissolve = 1.0 - p.float(args.first().copied())?,
+            "illum" => {
+                let s = args.first().copied().unwrap_or("");
+                m.illum = s
+                    .parse()
+                    .map_err(|_| p.error(format!("invalid illumination model `{}`", s)))?;
+            }
+            "map_Kd" => {
+                // options such as -s come first, the file name last
+                let file = args
+                    .last()
+                    .ok_or_else(|| p.error("missing texture file".to_string()))?;
+                let tex_path = base_dir.join(file);
+                let tex_path = tex_path.to_string_lossy();
+                let image = ImageTexture::load(&tex_path)
+                    .map_err(|e| ObjError::Texture(tex_path.to_string(), e))?;
+                m.map_kd = Some(TextureKind::Image(image));
+            }
+            _ => {}
+        }
+    }
+    if let Some(m) = current {
+        r.push(m.build());
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::obj::*;
+
+    fn parse(src: &str) -> Result<Obj, ObjError> {
+        Obj::parse(src, "test.obj", Path::new(""))
+    }
+
+    fn positions(obj: &Obj, face: usize) -> [usize; 3] {
+        let [a, b, c] = obj.faces[face].vertices;
+        [a.position, b.position, c.position]
+    }
+
+    #[test]
+    fn face_formats() {
+        let obj = parse(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0 # comment 1 2 3\n\
+             vt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n\
+             f 1 2 3\nf 1/1 2/2 3/3\nf 1//1 2//1 3//1\nf 1/1/1 2/2/1 3/3/1\n",
+        )
+        .unwrap();
+        assert_eq!(obj.faces.len(), 4);
+        let v = obj.faces[0].vertices[1];
+        assert_eq!((v.position, v.uv, v.normal), (1, None, None));
+        let v = obj.faces[1].vertices[2];
+        assert_eq!((v.position, v.uv, v.normal), (2, Some(2), None));
+        let v = obj.faces[2].vertices[0];
+        assert_eq!((v.position, v.uv, v.normal), (0, None, Some(0)));
+        let v = obj.faces[3].vertices[1];
+        assert_eq!((v.position, v.uv, v.normal), (1, Some(1), Some(0)));
+    }
+
+    #[test]
+    fn negative_indices() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 1 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(positions(&obj, 0), [0, 1, 2]);
+        assert_eq!(positions(&obj, 1), [1, 2, 3]);
+    }
+
+    #[test]
+    fn quad_and_ngon() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        assert_eq!(obj.faces.len(), 2);
+
+        // an L shape, which a fan from the first vertex would get wrong
+        let obj =
+            parse("v 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nv 0 0 0\nv 2 0 0\nf 1 2 3 4 5 6\n").unwrap();
+        assert_eq!(obj.faces.len(), 4);
+        let mut area = 0.0;
+        for f in 0..4 {
+            let [a, b, c] = positions(&obj, f);
+            let (a, b, c) = (obj.positions[a], obj.positions[b], obj.positions[c]);
+            let signed = 0.5 * (b - a).cross(c - a).z();
+            assert!(signed > 0.0);
+            area += signed;
+        }
+        assert!((area - 3.0).abs() < 1e-6, "{}", area);
+    }
+
+    #[test]
+    fn groups() {
+        let obj =
+            parse("v 0 0 0\nv 1 0 0\nv 0 1 0\no first\nf 1 2 3\nf 1 2 3\ng second part\nf 1 2 3\n")
+                .unwrap();
+        assert_eq!(obj.groups.len(), 2);
+        assert_eq!(obj.groups[0].name, "first");
+        assert_eq!(obj.groups[0].faces, 0..2);
+        assert_eq!(obj.groups[1].name, "second part");
+        assert_eq!(obj.groups[1].faces, 2..3);
+    }
+
+    #[test]
+    fn errors() {
+        let line = |src: &str| match parse(src) {
+            Err(ObjError::Parse { line, .. }) => line,
+            _ => panic!("expected a parse error for {:?}", src),
+        };
+        assert_eq!(line("v 0 0 0\nv 1 0 zero\n"), 2);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"), 4);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0\n"), 4);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n"), 4);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2 3\n"), 4);
+        assert_eq!(line("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(line("v 0 0 0\nusemtl none\n"), 2);
+        // which would panic sorting a quad's diagonals
+        let quad = "v nan 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        assert_eq!(line(quad), 1);
+        assert_eq!(line("v 0 inf 0\n"), 1);
+        // finite, but too large for the polygon's normal
+        let quad = "v 3e38 0 0\nv 3e38 3e38 0\nv 0 3e38 3e38\nv 0 0 0\nf 1 2 3 4\n";
+        assert_eq!(parse(quad).unwrap().faces.len(), 2);
+        assert_eq!(line("vt 0 -infinity\n"), 1);
+        assert!(matches!(
+            parse("mtllib no_such_file.mtl\n"),
+            Err(ObjError::Io(..))
+        ));
+    }
+
+    #[test]
+    fn continued_line() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 \\\n 3\n").unwrap();
+        assert_eq!(positions(&obj, 0), [0, 1, 2]);
+    }
+
+    #[test]
+    fn mtl() {
+        let materials = parse_mtl(
+            "newmtl red\nKd 0.8 0.1 0.1\n\
+             newmtl chrome\nKd 0 0 0\nKs 0.9 0.9 0.9\nNs 1000\n\
+             newmtl glass\nNi 1.5\nd 0.1\n\
+             newmtl lamp\nKe 5 5 5\n",
+            "test.mtl",
+            Path::new(""),
+        )
+        .unwrap();
+        let names: Vec<&str> = materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["red", "chrome", "glass", "lamp"]);
+        assert!(matches!(materials[0].material, MaterialKind::Lambertian(_)));
+        assert!(matches!(materials[1].material, MaterialKind::Metal(_)));
+        assert!(matches!(materials[2].material, MaterialKind::Dielectric(_)));
+        assert!(matches!(
+            materials[3].material,
+            MaterialKind::DiffuseLight(_)
+        ));
+
+        assert!(parse_mtl("Kd 1 1 1\n", "test.mtl", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn mtllib() {
+        let dir = std::env::temp_dir().join(format!("rrt_obj_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.mtl"),
+            "newmtl red\nKd 1 0 0\nnewmtl lamp\nKe 1 1 1\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("a.obj"),
+            "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             f 1 2 3\nusemtl lamp\nf 1 2 3\nusemtl red\nf 1 2 3\n",
+        )
+        .unwrap();
+        let obj = Obj::load(&dir.join("a.obj").to_string_lossy());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let obj = obj.unwrap();
+        let materials: Vec<Option<usize>> = obj.faces.iter().map(|f| f.material).collect();
+        assert_eq!(materials, [None, Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn several_mtllibs() {
+        let dir = std::env::temp_dir().join(format!("rrt_mtllibs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        std::fs::write(dir.join("b.mtl"), "newmtl lamp\nKe 1 1 1\n").unwrap();
+        std::fs::write(dir.join("a b.mtl"), "newmtl blue\nKd 0 0 1\n").unwrap();
+        let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl lamp\nf 1 2 3\n";
+        let several = Obj::parse(&format!("mtllib a.mtl b.mtl\n{}", src), "a.obj", &dir);
+        // names with spaces still load when they aren't several files
+        let spaced = Obj::parse("mtllib a b.mtl\nusemtl blue\n", "a.obj", &dir);
+        let missing = Obj::parse("mtllib a.mtl c.mtl\n", "a.obj", &dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let several = several.unwrap();
+        let names: Vec<&str> = several.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["red", "lamp"]);
+        assert_eq!(several.faces[0].material, Some(1));
+        let names: Vec<String> = spaced
+            .unwrap()
+            .materials
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(names, ["blue"]);
+        assert!(matches!(missing, Err(ObjError::Io(..))));
+    }
+
+    #[test]
+    fn generated_normals() {
+        // two faces of a box meeting at a right angle, and a nearly flat fold
+        // whose faces use two copies of the same position
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 1 0 -1\nv 1 1 -1\n\
+                   v 5 0 0\nv 6 0 0\nv 5 1 0\nv 7 0 0.1\nv 6 0 0\n\
+                   f 1 2 3 4\nf 2 5 6 3\nf 7 8 9\nf 9 11 10\n";
+
+        let mut obj = parse(src).unwrap();
+        obj.generate_normals(60.0);
+        // normal of face f at the corner on position p
+        let normal = |obj: &Obj, f: usize, p: usize| {
+            let v = obj.faces[f].vertices.iter().find(|v| v.position == p);
+            obj.normals[v.unwrap().normal.unwrap()]
+        };
+        // the box edge stays hard, faces 0 to 3 are the halves of the quads
+        let n = normal(&obj, 0, 1);
+        assert!((n.z() - 1.0).abs() < 1e-6, "{:?}", n);
+        // the fold is smoothed across the copies
+        let n = normal(&obj, 4, 7);
+        assert!(n.z() < 1.0 - 1e-4 && n.x() < 0.0, "{:?}", n);
+        let m = normal(&obj, 5, 10);
+        assert!((n - m).length() < 1e-6, "{:?} {:?}", n, m);
+
+        let mut obj = parse(src).unwrap();
+        obj.generate_normals(120.0);
+        let n = normal(&obj, 0, 1);
+        assert!(n.x() > 0.1 && n.z() > 0.1, "{:?}", n);
+    }
+
+    #[test]
+    fn shared_generated_normals() {
+        // a smooth octahedron gets one normal per position
+        let mut obj = parse(
+            "v 1 0 0\nv -1 0 0\nv 0 1 0\nv 0 -1 0\nv 0 0 1\nv 0 0 -1\n\
+             f 1 3 5\nf 3 2 5\nf 2 4 5\nf 4 1 5\nf 3 1 6\nf 2 3 6\nf 4 2 6\nf 1 4 6\n",
+        )
+        .unwrap();
+        obj.generate_normals(120.0);
+        assert_eq!(obj.normals.len(), obj.positions.len());
+        for f in obj.faces.iter() {
+            for v in f.vertices.iter() {
+                let n = obj.normals[v.normal.unwrap()];
+                assert!((n - obj.positions[v.position]).length() < 1e-6, "{:?}", n);
+            }
+        }
+
+        // a cube with hard edges gets one per corner of each side
+        let mut obj = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+             f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 2 3 7 6\nf 3 4 8 7\nf 4 1 5 8\n",
+        )
+        .unwrap();
+        obj.generate_normals(60.0);
+        assert_eq!(obj.normals.len(), 24);
+    }
+
+    #[test]
+    fn keeps_file_normals() {
+        let mut obj = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 1 0 0\nf 1//1 2//1 3//1\n").unwrap();
+        obj.generate_normals(60.0);
+        assert_eq!(obj.normals.len(), 1);
+        assert_eq!(obj.faces[0].vertices[2].normal, Some(0));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::random::SeededRng;
+use crate::vec3::Vec3;
+
+const POINT_COUNT: usize = 256;
+
+// gradient noise with random unit gradients on the integer lattice
+#[derive(Debug)]
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let rng = SeededRng::new(seed);
+        let gradients = (0..POINT_COUNT).map(|_| rng.unit_vector()).collect();
+        let perm = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            rng.shuffle(&mut p);
+            p
+        };
+        let perm_x = perm();
+        let perm_y = perm();
+        let perm_z = perm();
+        Perlin {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    // in [-1, 1], zero on the lattice points
+    pub fn noise(&self, p: Vec3) -> f32 {
+        let (i, j, k) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - i, p.y() - j, p.z() - k);
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        let mask = POINT_COUNT as i64 - 1;
+
+        // Hermite smoothing of the weights
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let g = self.gradients[self.perm_x[((i + di) & mask) as usize]
+                        ^ self.perm_y[((j + dj) & mask) as usize]
+                        ^ self.perm_z[((k + dk) & mask) as usize]];
+                    let (fi, fj, fk) = (di as f32, dj as f32, dk as f32);
+                    let offset = Vec3::new(u - fi, v - fj, w - fk);
+                    sum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * g.dot(offset);
+                }
+            }
+        }
+        sum
+    }
+
+    // sum of |noise| over octaves of doubling frequency and halving weight
+    pub fn turbulence(&self, p: Vec3, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            sum += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::perlin::*;
+
+    #[test]
+    fn lattice_points() {
+        let perlin = Perlin::new(0);
+        for p in [
+            Vec3::zero(),
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(-7.0, 300.0, -1.0),
+        ]
+        .iter()
+        {
+            assert_eq!(perlin.noise(*p), 0.0);
+        }
+    }
+
+    #[test]
+    fn range() {
+        let perlin = Perlin::new(1);
+        let mut max = 0.0f32;
+        for i in 0..1000 {
+            let p = Vec3::new(i as f32 * 0.137, i as f32 * 0.071 - 20.0, i as f32 * -0.013);
+            let n = perlin.noise(p);
+            assert!((-1.0..=1.0).contains(&n));
+            max = max.max(n.abs());
+        }
+        assert!(max > 0.1);
+    }
+
+    #[test]
+    fn seeded() {
+        let p = Vec3::new(0.3, 1.7, -2.2);
+        assert_eq!(Perlin::new(7).noise(p), Perlin::new(7).noise(p));
+        assert_ne!(Perlin::new(7).noise(p), Perlin::new(8).noise(p));
+    }
+}
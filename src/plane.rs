@@ -0,0 +1,88 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// infinite plane through point, with (u, v) in world units along the plane
+#[derive(Clone, Debug)]
+pub struct Plane {
+    point: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: MaterialKind,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, mat: MaterialKind) -> Plane {
+        let normal = normal.unit_vector();
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material: mat,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.direction());
+        if denom == 0.0 {
+            return None;
+        }
+        let t = self.normal.dot(self.point - r.origin()) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        let d = rec.p - self.point;
+        rec.u = d.dot(self.tangent);
+        rec.v = d.dot(self.bitangent);
+        rec.normal = if denom < 0.0 {
+            self.normal
+        } else {
+            -self.normal
+        };
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Lambertian;
+    use crate::plane::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn hit() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let p = Plane::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 2.0, 0.0), m);
+
+        let r = Ray::new(Vec3::new(3.0, 1.0, 4.0), Vec3::new(0.0, -2.0, 0.0));
+        let rec = p.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), 1.0, abs <= 1e-6);
+        // distance from the plane's point along the plane
+        assert_float_eq!(rec.u * rec.u + rec.v * rec.v, 25.0, abs <= 1e-4);
+
+        let r = Ray::new(Vec3::new(0.0, -3.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        let rec = p.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 2.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.y(), -1.0, abs <= 1e-6);
+
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(p.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(p.hit(&r, 0.001, f32::MAX).is_none());
+    }
+}
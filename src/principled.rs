@@ -0,0 +1,375 @@
+use crate::hit::HitRecord;
+use crate::material::{reflect, ScatterRecord};
+use crate::medium::Absorber;
+use crate::microfacet::Ggx;
+use crate::random::{rand_uniform, random_unit_vector};
+use crate::ray::Ray;
+use crate::shape::Frame;
+use crate::texture::TextureKind;
+use crate::vec3::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// one material for most surfaces after Burley, "Physically Based Shading at
+// Disney" (2012) and "Extending the Disney BRDF to a BSDF with Integrated
+// Subsurface Scattering" (2015): a blend of a diffuse dielectric with a sheen,
+// a metal and rough glass, under a clear coat. Every parameter is a texture;
+// those standing for a single value take the mean of its channels, in [0, 1]
+// but for ior. Its glass bounds an interior, as Dielectric does
+#[derive(Clone, Debug)]
+pub struct Principled {
+    base_color: TextureKind,
+    // 0 for a dielectric, 1 for a metal tinted by base_color
+    metallic: TextureKind,
+    roughness: TextureKind,
+    // reflectance of the dielectric head on, 0.5 being 4%
+    specular: TextureKind,
+    // strength of a second, achromatic specular layer
+    clearcoat: TextureKind,
+    clearcoat_roughness: TextureKind,
+    // grazing retroreflection, for cloth
+    sheen: TextureKind,
+    // how much of the dielectric is glass rather than diffuse
+    transmission: TextureKind,
+    ior: TextureKind,
+    interior: Arc<Absorber>,
+}
+
+// the parameters at a hit, with the probabilities of sampling the diffuse,
+// specular, clearcoat and glass lobes
+struct Lobes {
+    base_color: Vec3,
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    clearcoat: f32,
+    sheen: f32,
+    transmission: f32,
+    eta: f32,
+    ggx: Ggx,
+    clearcoat_ggx: Ggx,
+    weights: [f32; 4],
+}
+
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+impl Principled {
+    // a rough white plastic unless told otherwise
+    pub fn new<T: Into<TextureKind>>(base_color: T) -> Principled {
+        Principled {
+            base_color: base_color.into(),
+            metallic: 0.0.into(),
+            roughness: 0.5.into(),
+            specular: 0.5.into(),
+            clearcoat: 0.0.into(),
+            clearcoat_roughness: 0.1.into(),
+            sheen: 0.0.into(),
+            transmission: 0.0.into(),
+            ior: 1.5.into(),
+            interior: Arc::new(Absorber::new(Vec3::zero())),
+        }
+    }
+
+    pub fn metallic<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.metallic = t.into();
+        self
+    }
+
+    pub fn roughness<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.roughness = t.into();
+        self
+    }
+
+    pub fn specular<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.specular = t.into();
+        self
+    }
+
+    pub fn clearcoat<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.clearcoat = t.into();
+        self
+    }
+
+    pub fn clearcoat_roughness<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.clearcoat_roughness = t.into();
+        self
+    }
+
+    pub fn sheen<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.sheen = t.into();
+        self
+    }
+
+    pub fn transmission<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.transmission = t.into();
+        self
+    }
+
+    pub fn ior<T: Into<TextureKind>>(mut self, t: T) -> Principled {
+        self.ior = t.into();
+        self
+    }
+
+    pub fn interior(&self) -> &Absorber {
+        &self.interior
+    }
+
+    // the parameters at rec, seen from inside the solid if inside
+    fn lobes(&self, rec: &HitRecord, inside: bool) -> Lobes {
+        let value = |t: &TextureKind| {
+            let c = t.value(rec.u, rec.v, rec.p);
+            (c.x() + c.y() + c.z()) / 3.0
+        };
+        let unit = |t: &TextureKind| value(t).clamp(0.0, 1.0);
+        let metallic = unit(&self.metallic);
+        // kept off 0, so that every lobe can be evaluated
+        let roughness = unit(&self.roughness).max(0.05);
+        let clearcoat = unit(&self.clearcoat);
+        let transmission = unit(&self.transmission);
+        let ior = value(&self.ior).max(1e-3);
+        let dielectric = 1.0 - metallic;
+        // only the glass is seen from inside
+        let weights = if inside {
+            [0.0, 0.0, 0.0, dielectric * transmission]
+        } else {
+            [
+                dielectric * (1.0 - transmission),
+                metallic + dielectric * (1.0 - transmission),
+                0.25 * clearcoat,
+                dielectric * transmission,
+            ]
+        };
+        Lobes {
+            base_color: self.base_color.value(rec.u, rec.v, rec.p),
+            metallic,
+            roughness,
+            specular: unit(&self.specular),
+            clearcoat,
+            sheen: unit(&self.sheen),
+            transmission,
+            eta: if inside { 1.0 / ior } else { ior },
+            ggx: Ggx::from_roughness(roughness),
+            clearcoat_ggx: Ggx::from_roughness(unit(&self.clearcoat_roughness).max(0.05)),
+            weights,
+        }
+    }
+
+    // the shading frame on the side r_in comes from, the direction towards
+    // r_in's origin in it, and the parameters there
+    fn local(&self, r_in: &Ray, rec: &HitRecord) -> (Frame, Vec3, Lobes) {
+        let wo = -r_in.direction().unit_vector();
+        let inside = wo.dot(rec.normal) < 0.0;
+        let n = if inside { -rec.normal } else { rec.normal };
+        let frame = Frame::new(Vec3::zero(), n);
+        let wo = frame.local(wo);
+        (frame, wo, self.lobes(rec, inside))
+    }
+
+    // BSDF times cosine summed over the lobes, and the density of scatter
+    // picking any of them
+    fn eval_pdf(l: &Lobes, wo: Vec3, wi: Vec3) -> (Vec3, f32) {
+        let total: f32 = l.weights.iter().sum();
+        if wo.z() <= 0.0 || total <= 0.0 {
+            return (Vec3::zero(), 0.0);
+        }
+        let [p_diffuse, p_specular, p_clearcoat, p_glass] = l.weights.map(|w| w / total);
+        let dielectric = 1.0 - l.metallic;
+        let opaque = dielectric * (1.0 - l.transmission);
+        let mut f = Vec3::zero();
+        let mut pdf = 0.0;
+
+        if wi.z() > 0.0 && p_glass < 1.0 {
+            let h = (wo + wi).unit_vector();
+            let cos_d = wi.dot(h);
+            let f_d90 = 0.5 + 2.0 * l.roughness * cos_d * cos_d;
+            let retro = (1.0 + (f_d90 - 1.0) * schlick_weight(wi.z()))
+                * (1.0 + (f_d90 - 1.0) * schlick_weight(wo.z()));
+            let diffuse =
+                l.base_color * (retro / PI) + Vec3::one() * (l.sheen * schlick_weight(cos_d));
+            f += opaque * wi.z() * diffuse;
+            pdf += p_diffuse * wi.z() / PI;
+
+            if wo.dot(h) > 0.0 {
+                let fh = schlick_weight(wo.dot(h));
+                let f0 = 0.08 * l.specular;
+                let metal = l.base_color + (Vec3::one() - l.base_color) * fh;
+                let fresnel = Vec3::one() * (opaque * (f0 + (1.0 - f0) * fh)) + l.metallic * metal;
+                let ggx = &l.ggx;
+                f += fresnel * (ggx.d(h) * ggx.g2(wo, wi) / (4.0 * wo.z()));
+                pdf += p_specular * ggx.pdf_visible(wo, h) / (4.0 * wo.dot(h));
+
+                let coat = &l.clearcoat_ggx;
+                let fresnel = 0.04 + 0.96 * fh;
+                let f_coat = 0.25 * l.clearcoat * fresnel * coat.d(h) * coat.g2(wo, wi);
+                f += Vec3::one() * (f_coat / (4.0 * wo.z()));
+                pdf += p_clearcoat * coat.pdf_visible(wo, h) / (4.0 * wo.dot(h));
+            }
+        }
+
+        if p_glass > 0.0 {
+            let (f_glass, pdf_glass) = l.ggx.eval_dielectric(wo, wi, l.eta);
+            // the base color tints what goes through
+            let tint = if wi.z() < 0.0 {
+                l.base_color
+            } else {
+                Vec3::one()
+            };
+            f += tint * (dielectric * l.transmission * f_glass);
+            pdf += p_glass * pdf_glass;
+        }
+        (f, pdf)
+    }
+
+    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let (frame, wo, l) = self.local(r_in, rec);
+        let total: f32 = l.weights.iter().sum();
+        if wo.z() <= 0.0 || total <= 0.0 {
+            return None;
+        }
+        // pick a lobe to sample, then weight by all of them
+        let mut xi = rand_uniform() * total;
+        let mut lobe = 0;
+        while lobe < 3 && xi >= l.weights[lobe] {
+            xi -= l.weights[lobe];
+            lobe += 1;
+        }
+        let wi = match lobe {
+            0 => {
+                let d = Vec3::new(0.0, 0.0, 1.0) + random_unit_vector();
+                if d.squared_length() < 1e-8 {
+                    Vec3::new(0.0, 0.0, 1.0)
+                } else {
+                    d.unit_vector()
+                }
+            }
+            1 | 2 => {
+                let ggx = if lobe == 1 { &l.ggx } else { &l.clearcoat_ggx };
+                reflect(-wo, ggx.sample_visible(wo, rand_uniform(), rand_uniform()))
+            }
+            _ => l.ggx.sample_dielectric(wo, l.eta)?,
+        };
+        let (f, pdf) = Self::eval_pdf(&l, wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord {
+            ray: Ray::with_time(rec.p, frame.world(wi), r_in.time()),
+            attenuation: f / pdf,
+            pdf: Some(pdf),
+        })
+    }
+
+    pub fn pdf(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let (frame, wo, l) = self.local(r_in, rec);
+        Self::eval_pdf(&l, wo, frame.local(direction.unit_vector())).1
+    }
+
+    // BSDF times cosine towards direction
+    pub fn eval(&self, r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let (frame, wo, l) = self.local(r_in, rec);
+        Self::eval_pdf(&l, wo, frame.local(direction.unit_vector())).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::MaterialKind;
+    use crate::principled::*;
+    use crate::texture::Checker;
+    use float_eq::assert_float_eq;
+
+    // checks scatter against pdf and eval from two directions, returning the
+    // mean attenuation
+    fn check(m: &Principled) -> Vec3 {
+        let kind = MaterialKind::Principled(Box::new(m.clone()));
+        let mut rec = HitRecord::new(&kind);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        let r = Ray::new(Vec3::new(-1.0, 2.0, 0.0), Vec3::new(1.0, -2.0, 0.0));
+        let n = 50000;
+        let mut mean = Vec3::zero();
+        let mut integral = 0.0;
+        let mut scattered = 0;
+        for _ in 0..n {
+            // half uniform, half from the material itself, which keeps the
+            // estimate of the integral of the pdf steady for sharp lobes
+            let d = if fastrand::bool() {
+                Some(random_unit_vector())
+            } else {
+                m.scatter(&r, &rec).map(|s| s.ray.direction().unit_vector())
+            };
+            if let Some(d) = d {
+                let pdf = m.pdf(&r, &rec, d);
+                integral += pdf / (0.5 / (4.0 * PI) + 0.5 * pdf);
+            }
+            if let Some(s) = m.scatter(&r, &rec) {
+                let d = s.ray.direction();
+                let pdf = s.pdf.unwrap();
+                // the sharp clear coat is sensitive to rounding in d
+                assert_float_eq!(pdf, m.pdf(&r, &rec, d), r2nd <= 1e-2);
+                let f = m.eval(&r, &rec, d);
+                assert!((f / pdf - s.attenuation).length() < 1e-3);
+                mean += s.attenuation;
+                scattered += 1;
+            }
+        }
+        let integral = integral / n as f32;
+        assert_float_eq!(integral, scattered as f32 / n as f32, abs <= 0.02);
+        mean / n as f32
+    }
+
+    #[test]
+    fn lobes() {
+        fastrand::seed(6);
+        // a white diffuse surface reflects about everything
+        let white = check(&Principled::new(Vec3::one()).specular(0.0).roughness(1.0));
+        assert_float_eq!(white.x(), 1.0, abs <= 0.1);
+        // a black one only its specular highlight
+        let black = check(&Principled::new(Vec3::zero()));
+        assert!(black.x() > 0.01 && black.x() < 0.15, "{:?}", black);
+        // a metal is colored by base_color
+        let gold = check(
+            &Principled::new(Vec3::new(1.0, 0.8, 0.3))
+                .metallic(1.0)
+                .roughness(0.3),
+        );
+        assert!(gold.x() > gold.y() && gold.y() > gold.z());
+        assert!(gold.x() <= 1.0 && gold.x() > 0.85, "{:?}", gold);
+        // the clear coat adds to the dielectric
+        let coated = check(&Principled::new(Vec3::zero()).clearcoat(1.0));
+        assert!(coated.x() > black.x());
+        // glass lets most through, and sheen brightens
+        check(
+            &Principled::new(Vec3::one())
+                .transmission(1.0)
+                .roughness(0.3),
+        );
+        check(&Principled::new(Vec3::new(0.2, 0.3, 0.8)).sheen(1.0));
+    }
+
+    #[test]
+    fn textured() {
+        // metal on the even cells of a checker, rough plastic on the odd ones
+        let base = Vec3::new(0.9, 0.1, 0.1);
+        let metallic = TextureKind::Checker(Checker::new(1.0, 0.0, 1.0));
+        let roughness = TextureKind::Checker(Checker::new(1.0, 0.8, 0.2));
+        let m = Principled::new(base)
+            .metallic(metallic)
+            .roughness(roughness);
+        let metal = Principled::new(base).metallic(1.0).roughness(0.2);
+        let plastic = Principled::new(base).roughness(0.8);
+        let kind = MaterialKind::Principled(Box::new(m.clone()));
+        let mut rec = HitRecord::new(&kind);
+        rec.normal = Vec3::new(0.0, 1.0, 0.0);
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.0));
+        let d = Vec3::new(0.2, 1.0, 0.1);
+        for (p, expected) in [(0.5, &metal), (1.5, &plastic)].iter() {
+            rec.p = Vec3::new(*p, 0.0, 0.5);
+            let f = m.eval(&r, &rec, d);
+            let g = expected.eval(&r, &rec, d);
+            assert!((f - g).length() < 1e-6, "{:?} {:?}", f, g);
+            assert_float_eq!(m.pdf(&r, &rec, d), expected.pdf(&r, &rec, d), r2nd <= 1e-6);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
+use crate::material::MaterialKind;
+use crate::random::rand_uniform;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// parallelogram spanned by u and v from corner, with (u, v) in [0, 1] along them
+#[derive(Clone, Debug)]
+pub struct Quad {
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // maps a point in the plane to its coordinates along u and v
+    w: Vec3,
+    material: MaterialKind,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, mat: MaterialKind) -> Quad {
+        let n = u.cross(v);
+        Quad {
+            corner,
+            u,
+            v,
+            normal: n.unit_vector(),
+            w: n / n.dot(n),
+            material: mat,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.direction());
+        if denom == 0.0 {
+            return None;
+        }
+        let t = self.normal.dot(self.corner - r.origin()) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let p = r.point_at_parameter(t);
+        let d = p - self.corner;
+        let alpha = self.w.dot(d.cross(self.v));
+        let beta = self.w.dot(self.u.cross(d));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.normal = if denom < 0.0 {
+            self.normal
+        } else {
+            -self.normal
+        };
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let c = self.corner;
+        Some(Aabb::from_points(&[c, c + self.u, c + self.v, c + self.u + self.v]).padded(0.0001))
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        match self.material {
+            MaterialKind::DiffuseLight(_) => vec![Box::new(self.clone())],
+            _ => vec![],
+        }
+    }
+}
+
+impl Light for Quad {
+    fn sample(&self) -> HitRecord<'_> {
+        let (a, b) = (rand_uniform(), rand_uniform());
+        let mut rec = HitRecord::new(&self.material);
+        rec.p = self.corner + a * self.u + b * self.v;
+        rec.normal = self.normal;
+        rec.u = a;
+        rec.v = b;
+        rec
+    }
+
+    fn area(&self) -> f32 {
+        self.u.cross(self.v).length()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Lambertian;
+    use crate::quad::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn hit() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let q = Quad::new(
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            m,
+        );
+        let d = Vec3::new(0.0, 0.0, -1.0);
+
+        let rec = q.hit(&Ray::new(Vec3::new(2.5, 0.5, 1.0), d), 0.001, f32::MAX);
+        let rec = rec.unwrap();
+        assert_float_eq!(rec.t, 1.0, abs <= 1e-6);
+        assert_float_eq!(rec.u, 0.5, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-6);
+        assert_float_eq!(rec.normal.z(), 1.0, abs <= 1e-6);
+
+        // outside the slanted side
+        assert!(q
+            .hit(&Ray::new(Vec3::new(1.2, 0.5, 1.0), d), 0.001, f32::MAX)
+            .is_none());
+        assert!(q
+            .hit(&Ray::new(Vec3::new(2.5, 1.5, 1.0), d), 0.001, f32::MAX)
+            .is_none());
+
+        let rec = q.hit(&Ray::new(Vec3::new(2.5, 0.5, -1.0), -d), 0.001, f32::MAX);
+        assert_float_eq!(rec.unwrap().normal.z(), -1.0, abs <= 1e-6);
+        assert_float_eq!(q.area(), 2.0, abs <= 1e-6);
+    }
+}
@@ -1,5 +1,61 @@
+use crate::vec3::Vec3;
 use fastrand;
 
 pub fn rand_uniform() -> f32 {
     fastrand::f32()
 }
+
+// uniformly distributed on the unit sphere
+pub fn random_unit_vector() -> Vec3 {
+    unit_vector(rand_uniform(), rand_uniform())
+}
+
+fn unit_vector(u1: f32, u2: f32) -> Vec3 {
+    let z = 2.0 * u1 - 1.0;
+    let a = 2.0 * std::f32::consts::PI * u2;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vec3::new(r * a.cos(), r * a.sin(), z)
+}
+
+// generator for scene content such as noise tables, which must come out the
+// same for a given seed on every run, independent of the sampling generator
+pub struct SeededRng {
+    rng: fastrand::Rng,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng {
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    pub fn uniform(&self) -> f32 {
+        self.rng.f32()
+    }
+
+    pub fn unit_vector(&self) -> Vec3 {
+        unit_vector(self.uniform(), self.uniform())
+    }
+
+    pub fn shuffle<T>(&self, slice: &mut [T]) {
+        self.rng.shuffle(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::random::*;
+
+    #[test]
+    fn seeded() {
+        let a = SeededRng::new(42);
+        let b = SeededRng::new(42);
+        let c = SeededRng::new(43);
+        let xs: Vec<f32> = (0..8).map(|_| a.uniform()).collect();
+        let ys: Vec<f32> = (0..8).map(|_| b.uniform()).collect();
+        let zs: Vec<f32> = (0..8).map(|_| c.uniform()).collect();
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+}
@@ -3,11 +3,17 @@ use crate::vec3::Vec3;
 pub struct Ray {
     A: Vec3, // ray origin
     B: Vec3, // ray direction
+    time: f32,
 }
 
 impl Ray {
     pub fn new(a: Vec3, b: Vec3) -> Ray {
-        Ray { A: a, B: b }
+        Ray::with_time(a, b, 0.0)
+    }
+
+    // a ray at a moment while the shutter is open, for motion blur
+    pub fn with_time(a: Vec3, b: Vec3, time: f32) -> Ray {
+        Ray { A: a, B: b, time }
     }
 
     pub fn origin(&self) -> Vec3 {
@@ -18,7 +24,19 @@ impl Ray {
         self.B
     }
 
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
     pub fn point_at_parameter(&self, t: f32) -> Vec3 {
         self.A + t * self.B
     }
 }
+
+// how far time is from time0 towards time1, held at the keyframes outside
+pub fn keyframe_weight(time: f32, time0: f32, time1: f32) -> f32 {
+    if time1 <= time0 {
+        return 0.0;
+    }
+    ((time - time0) / (time1 - time0)).clamp(0.0, 1.0)
+}
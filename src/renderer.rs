@@ -1,47 +1,281 @@
 use crate::camera::Camera;
-use crate::hit::HittableList;
+use crate::hit::{HitRecord, Hittable};
+use crate::light::LightList;
 use crate::material::MaterialKind;
+use crate::medium::Medium;
 use crate::random::rand_uniform;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 use image::ImageBuffer;
 use indicatif::ProgressBar;
+use std::sync::Arc;
 
-fn color(r: &Ray, world: &HittableList, depth: i32) -> Vec3 {
+#[derive(Clone, Copy, Debug)]
+pub enum Background {
+    // white to light blue gradient
+    Sky,
+    Color(Vec3),
+}
+
+impl Background {
+    fn color(&self, r: &Ray) -> Vec3 {
+        match self {
+            Background::Sky => {
+                let ud = r.direction().unit_vector();
+                let t = 0.5 * (ud.y() + 1.0);
+                (1.0 - t) * Vec3::new(1.0, 1.0, 1.0) + t * Vec3::new(0.5, 0.7, 1.0)
+            }
+            Background::Color(c) => *c,
+        }
+    }
+}
+
+fn emitted(hr: &HitRecord) -> Vec3 {
+    match hr.material {
+        MaterialKind::DiffuseLight(m) => m.emitted(hr),
+        _ => Vec3::zero(),
+    }
+}
+
+// MIS weight of the strategy with density pdf against the other one
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+// the media a path is nested in, innermost last, so that leaving fog inside
+// glass goes back to the glass; deeper nesting than this is ignored
+const MAX_MEDIA: usize = 8;
+
+#[derive(Clone, Copy, Default)]
+struct Media<'a> {
+    stack: [Option<&'a dyn Medium>; MAX_MEDIA],
+    len: usize,
+}
+
+impl<'a> Media<'a> {
+    // the one the path travels through
+    fn top(&self) -> Option<&'a dyn Medium> {
+        self.len.checked_sub(1).and_then(|i| self.stack[i])
+    }
+
+    fn contains(&self, m: &dyn Medium) -> bool {
+        self.stack[..self.len]
+            .iter()
+            .flatten()
+            .any(|s| same_medium(*s, m))
+    }
+
+    fn enter(mut self, m: &'a dyn Medium) -> Media<'a> {
+        if self.len < MAX_MEDIA {
+            self.stack[self.len] = Some(m);
+            self.len += 1;
+        }
+        self
+    }
+
+    // boundaries may overlap, so m isn't necessarily the innermost
+    fn leave(mut self, m: &dyn Medium) -> Media<'a> {
+        let i = self.stack[..self.len]
+            .iter()
+            .rposition(|s| s.is_some_and(|s| same_medium(s, m)));
+        if let Some(i) = i {
+            self.stack.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+            self.stack[self.len] = None;
+        }
+        self
+    }
+}
+
+// the media past the boundary of a volume r crosses at hr, entering or
+// leaving it
+fn medium_after<'a>(r: &Ray, hr: &HitRecord<'a>, media: Media<'a>) -> Media<'a> {
+    match hr.medium {
+        Some(m) if hr.normal.dot(r.direction()) < 0.0 => media.enter(m),
+        Some(m) => media.leave(m),
+        None => media,
+    }
+}
+
+// the inside of a transparent solid, which paths keep track of
+fn interior<'a>(hr: &HitRecord<'a>) -> Option<&'a dyn Medium> {
+    hr.material.interior()
+}
+
+fn same_medium(a: &dyn Medium, b: &dyn Medium) -> bool {
+    std::ptr::eq(
+        a as *const dyn Medium as *const u8,
+        b as *const dyn Medium as *const u8,
+    )
+}
+
+// turns the normal on the surface of a transparent solid out of it, by
+// whether the path is inside, as flat shapes face their normals to the ray
+fn orient_outward(r: &Ray, hr: &mut HitRecord, media: &Media) {
+    if let Some(i) = interior(hr) {
+        let inside = media.contains(i);
+        let facing = if hr.normal.dot(r.direction()) < 0.0 {
+            hr.normal
+        } else {
+            -hr.normal
+        };
+        hr.normal = if inside { -facing } else { facing };
+    }
+}
+
+// the media of a ray leaving hr in direction, entering or leaving the solid
+// that hr's surface bounds
+fn medium_toward<'a>(hr: &HitRecord<'a>, direction: Vec3, media: Media<'a>) -> Media<'a> {
+    match interior(hr) {
+        Some(i) if direction.dot(hr.normal) >= 0.0 => media.leave(i),
+        // reflected back inside, the path is in it already
+        Some(i) if !media.contains(i) => media.enter(i),
+        _ => media,
+    }
+}
+
+// next-event estimation: light from a sampled point on a light source,
+// dimmed by the media on the way
+fn direct_light(
+    r: &Ray,
+    hr: &HitRecord,
+    world: &(dyn Hittable + Send + Sync),
+    lights: &LightList,
+    media: Media,
+) -> Vec3 {
+    let (light, pdf) = match lights.sample(hr.p) {
+        Some(sample) => sample,
+        None => return Vec3::zero(),
+    };
+    let d = light.p - hr.p;
+    let f = hr.material.eval(r, hr, d);
+    if f.squared_length() == 0.0 {
+        return Vec3::zero();
+    }
+
+    let distance = d.length();
+    let shadow = Ray::with_time(hr.p, d / distance, r.time());
+    let mut media = medium_toward(hr, d, media);
+    let mut t_min = 0.001;
+    let mut transmittance = 1.0;
+    let mut absorbed = Vec3::one();
+    loop {
+        let hit = world.hit(&shadow, t_min, distance - 0.001);
+        if let Some(m) = media.top() {
+            let t = hit.as_ref().map_or(distance, |h| h.t);
+            transmittance *= m.transmittance(&shadow, t_min, t);
+            absorbed *= m.absorption(&shadow, t_min, t);
+        }
+        match hit {
+            None => break,
+            Some(h) if h.medium.is_some() => {
+                media = medium_after(&shadow, &h, media);
+                t_min = h.t + 0.001;
+            }
+            Some(_) => return Vec3::zero(),
+        }
+    }
+    let weight = power_heuristic(pdf, hr.material.pdf(r, hr, d));
+    weight * transmittance * absorbed * f * emitted(&light) / pdf
+}
+
+// the next scattering event along r, either on a surface or inside the medium
+// the ray travels through, crossing medium boundaries on the way; returns the
+// media around the event and the fraction of light absorbed on the way
+fn next_event<'a>(
+    r: &Ray,
+    world: &'a (dyn Hittable + Send + Sync),
+    media: Media<'a>,
+) -> (Option<HitRecord<'a>>, Media<'a>, Vec3) {
+    let mut media = media;
+    let mut t_min = 0.001;
+    let mut absorbed = Vec3::one();
+    loop {
+        let hit = world.hit(r, t_min, f32::MAX);
+        if let Some(m) = media.top() {
+            let t_max = hit.as_ref().map_or(f32::MAX, |h| h.t);
+            if let Some(t) = m.sample_distance(r, t_min, t_max) {
+                let mut rec = HitRecord::new(m.phase());
+                rec.t = t;
+                rec.p = r.point_at_parameter(t);
+                absorbed *= m.absorption(r, t_min, t);
+                return (Some(rec), media, absorbed);
+            }
+            absorbed *= m.absorption(r, t_min, t_max);
+        }
+        match hit {
+            Some(hr) if hr.medium.is_some() => {
+                media = medium_after(r, &hr, media);
+                t_min = hr.t + 0.001;
+            }
+            hit => return (hit, media, absorbed),
+        }
+    }
+}
+
+// scatter_pdf is the density of r's direction when the previous bounce also
+// sampled the lights, so that emission found by r is weighted against it;
+// media are the ones r starts in
+fn color(
+    r: &Ray,
+    world: &(dyn Hittable + Send + Sync),
+    lights: &LightList,
+    background: &Background,
+    depth: i32,
+    scatter_pdf: Option<f32>,
+    media: Media,
+) -> Vec3 {
     if depth >= 50 {
         return Vec3::zero();
     }
-    match world.hit(r, 0.001, std::f32::MAX) {
-        Some(hr) => {
-            let scatter_result = match hr.material {
-                MaterialKind::Lambertian(m) => m.scatter(r, &hr),
-                MaterialKind::Dielectric(m) => m.scatter(r, &hr),
-                MaterialKind::Metal(m) => m.scatter(r, &hr),
+    let (hit, media, absorbed) = next_event(r, world, media);
+    let radiance = match hit {
+        Some(mut hr) => {
+            orient_outward(r, &mut hr, &media);
+            let mut emitted = emitted(&hr);
+            if let Some(pdf) = scatter_pdf {
+                if emitted.squared_length() > 0.0 {
+                    emitted *= power_heuristic(pdf, lights.pdf(r.origin(), r.direction()));
+                }
+            }
+            // delta distributions can't be combined with light sampling; the
+            // other lobes are lit whatever scatter picks, or if it picks nothing
+            let direct = if lights.is_empty() || hr.material.is_delta() {
+                Vec3::zero()
+            } else {
+                direct_light(r, &hr, world, lights, media)
             };
+            let scatter_result = hr.material.scatter(r, &hr);
 
             match scatter_result {
-                Some((scattered, att)) => {
-                    return att * color(&scattered, world, depth + 1);
-                }
-                None => {
-                    return Vec3::zero();
+                Some(s) => {
+                    let pdf = if lights.is_empty() { None } else { s.pdf };
+                    let media = medium_toward(&hr, s.ray.direction(), media);
+                    let indirect = s.attenuation
+                        * color(&s.ray, world, lights, background, depth + 1, pdf, media);
+                    emitted + direct + indirect
                 }
+                None => emitted + direct,
             }
         }
-        None => {
-            let ud = r.direction().unit_vector();
-            let t = 0.5 * (ud.y() + 1.0);
-            return (1.0 - t) * Vec3::new(1.0, 1.0, 1.0) + t * Vec3::new(0.5, 0.7, 1.0);
-        }
-    }
+        None => background.color(r),
+    };
+    absorbed * radiance
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn rendering(
     width: u32,
     height: u32,
     sampling_num: u32,
     cam: &'static Camera,
-    scene: &'static HittableList,
+    scene: &'static (dyn Hittable + Send + Sync),
+    background: Background,
     thread_num: usize,
     png_file_name: &str,
     silent: bool,
@@ -50,11 +284,13 @@ pub fn rendering(
     if thread_num > 0 {
         runtime.worker_threads(thread_num);
     }
+    let lights = Arc::new(LightList::new(scene));
     runtime.enable_all().build().unwrap().block_on(async {
         let mut jh = vec![];
         {
             for j in 0..height {
                 let mut row = vec![];
+                let lights = lights.clone();
                 let h = tokio::spawn(async move {
                     for i in 0..width {
                         let mut col = Vec3::zero();
@@ -62,7 +298,8 @@ pub fn rendering(
                             let u = (rand_uniform() + i as f32) / width as f32;
                             let v = (rand_uniform() + (height - j - 1) as f32) / height as f32;
                             let r = cam.get_ray(u, v);
-                            col += color(&r, scene, 0);
+                            col +=
+                                color(&r, scene, &lights, &background, 0, None, Media::default());
                         }
                         col /= sampling_num as f32;
                         col = Vec3::new(col.x().sqrt(), col.y().sqrt(), col.z().sqrt());
@@ -95,3 +332,167 @@ pub fn rendering(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::box_shape::BoxShape;
+    use crate::coated::Coated;
+    use crate::hit::HittableList;
+    use crate::material::{Conductor, Dielectric, DiffuseLight, Isotropic, Lambertian};
+    use crate::medium::ConstantMedium;
+    use crate::principled::Principled;
+    use crate::quad::Quad;
+    use crate::renderer::*;
+    use crate::triangle::Triangle;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn light_sampling_unbiased() {
+        // rough floors under a lamp, which reject some of their samples;
+        // sampling the light must not change what they converge to
+        fastrand::seed(10);
+        let floors = [
+            MaterialKind::Conductor(Conductor::new(
+                Vec3::new(0.2, 0.2, 0.2),
+                Vec3::new(3.0, 3.0, 3.0),
+                0.7,
+            )),
+            MaterialKind::Principled(Box::new(
+                Principled::new(Vec3::new(0.8, 0.8, 0.8))
+                    .metallic(0.5)
+                    .roughness(0.7),
+            )),
+            MaterialKind::Coated(Coated::new(
+                MaterialKind::Lambertian(Lambertian::new(Vec3::new(0.8, 0.8, 0.8))),
+                1.5,
+                0.5,
+            )),
+        ];
+        let lamp = MaterialKind::DiffuseLight(DiffuseLight::new(Vec3::new(4.0, 4.0, 4.0)));
+        let background = Background::Color(Vec3::zero());
+        let r = Ray::new(Vec3::new(-1.5, 0.5, 0.0), Vec3::new(1.5, -0.5, 0.0));
+        for floor in floors.iter() {
+            let mut world = HittableList::new();
+            world.list.push(Box::new(Quad::new(
+                Vec3::new(-50.0, 0.0, -50.0),
+                Vec3::new(0.0, 0.0, 100.0),
+                Vec3::new(100.0, 0.0, 0.0),
+                floor.clone(),
+            )));
+            world.list.push(Box::new(Quad::new(
+                Vec3::new(-1.0, 2.0, -1.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 2.0),
+                lamp.clone(),
+            )));
+            let sampled = LightList::new(&world);
+            let none = LightList::new(&HittableList::new());
+            assert!(!sampled.is_empty());
+
+            let n = 50000;
+            let (mut with, mut without) = (0.0, 0.0);
+            for _ in 0..n {
+                let media = Media::default();
+                with += color(&r, &world, &sampled, &background, 0, None, media).x();
+                without += color(&r, &world, &none, &background, 0, None, media).x();
+            }
+            let (with, without) = (with / n as f32, without / n as f32);
+            assert!(without > 0.05);
+            assert_float_eq!(with, without, r2nd <= 0.03);
+        }
+    }
+
+    #[test]
+    fn glass_interior() {
+        // a slab 2 thick between two flat faces, which face the ray
+        let glass = MaterialKind::Dielectric(
+            Dielectric::new(1.5).absorption(Vec3::new(0.5, 0.8, 1.0), 1.0),
+        );
+        let mut world = HittableList::new();
+        for z in [0.0, -2.0].iter() {
+            world.list.push(Box::new(Triangle::new(
+                Vec3::new(-10.0, -10.0, *z),
+                Vec3::new(10.0, -10.0, *z),
+                Vec3::new(0.0, 10.0, *z),
+                glass.clone(),
+            )));
+        }
+        let down = Vec3::new(0.0, 0.0, -1.0);
+
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), down);
+        let (hit, media, absorbed) = next_event(&r, &world, Media::default());
+        let mut hr = hit.unwrap();
+        assert!(media.top().is_none());
+        assert_float_eq!(absorbed.x(), 1.0, abs <= 0.0);
+        orient_outward(&r, &mut hr, &media);
+        assert!(hr.normal.z() > 0.0);
+        let inside = medium_toward(&hr, down, media);
+        assert!(inside.top().is_some());
+        // reflected off the top stays outside
+        assert!(medium_toward(&hr, -down, media).top().is_none());
+
+        let r = Ray::new(hr.p, down);
+        let (hit, media, absorbed) = next_event(&r, &world, inside);
+        let mut hr = hit.unwrap();
+        assert_float_eq!(absorbed.x(), 0.25, abs <= 1e-3);
+        assert_float_eq!(absorbed.y(), 0.64, abs <= 1e-3);
+        assert_float_eq!(absorbed.z(), 1.0, abs <= 0.0);
+        // the copy of the material on the bottom face is the same solid, so
+        // its normal turns out of it, along the ray
+        orient_outward(&r, &mut hr, &media);
+        assert!(hr.normal.z() < 0.0);
+        assert!(medium_toward(&hr, down, media).top().is_none());
+        // reflected inside, it stays inside, once
+        let reflected = medium_toward(&hr, -down, media);
+        assert!(reflected.top().is_some());
+        assert!(medium_toward(&hr, down, reflected).top().is_none());
+    }
+
+    #[test]
+    fn fog_inside_glass() {
+        // a box of thin fog in the middle of a slab 4 thick
+        fastrand::seed(3);
+        let glass = MaterialKind::Dielectric(
+            Dielectric::new(1.5).absorption(Vec3::new(0.5, 0.8, 1.0), 1.0),
+        );
+        let mut world = HittableList::new();
+        for z in [0.0, -4.0].iter() {
+            world.list.push(Box::new(Triangle::new(
+                Vec3::new(-10.0, -10.0, *z),
+                Vec3::new(10.0, -10.0, *z),
+                Vec3::new(0.0, 10.0, *z),
+                glass.clone(),
+            )));
+        }
+        let fog = MaterialKind::Isotropic(Isotropic::new(Vec3::one()));
+        world.list.push(Box::new(ConstantMedium::new(
+            Box::new(BoxShape::new(
+                Vec3::new(-1.0, -1.0, -3.0),
+                Vec3::new(1.0, 1.0, -1.0),
+                fog.clone(),
+            )),
+            1e-4,
+            fog,
+        )));
+        let down = Vec3::new(0.0, 0.0, -1.0);
+
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), down);
+        let (hit, media, _) = next_event(&r, &world, Media::default());
+        let mut hr = hit.unwrap();
+        orient_outward(&r, &mut hr, &media);
+        let inside = medium_toward(&hr, down, media);
+
+        // through the fog to the bottom face, back in the glass after it
+        let r = Ray::new(hr.p, down);
+        let (hit, media, absorbed) = next_event(&r, &world, inside);
+        let mut hr = hit.unwrap();
+        assert_float_eq!(hr.p.z(), -4.0, abs <= 1e-4);
+        let glass_interior = glass.interior().unwrap();
+        assert!(same_medium(media.top().unwrap(), glass_interior));
+        // the glass absorbs where the fog isn't
+        assert_float_eq!(absorbed.x(), 0.25, abs <= 1e-3);
+        orient_outward(&r, &mut hr, &media);
+        assert!(hr.normal.z() < 0.0);
+        assert!(medium_toward(&hr, down, media).top().is_none());
+    }
+}
@@ -0,0 +1,2137 @@
+use crate::box_shape::BoxShape;
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::coated::Coated;
+use crate::cone::Cone;
+use crate::cylinder::Cylinder;
+use crate::disk::Disk;
+use crate::heightfield::Heightfield;
+use crate::hit::{Hittable, HittableList};
+use crate::instance::Instance;
+use crate::material::{
+    Conductor, Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, MaterialKind,
+    Metal, RoughDielectric,
+};
+use crate::medium::ConstantMedium;
+use crate::mesh::Mesh;
+use crate::model::load_obj;
+use crate::plane::Plane;
+use crate::principled::Principled;
+use crate::quad::Quad;
+use crate::renderer::Background;
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{
+    Checker, ImageTexture, Marble, Noise, TextureKind, Turbulence, UvChecker, Wood,
+};
+use crate::torus::Torus;
+use crate::transform::Transform;
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+use crate::volume::{DensityGrid, GridMedium};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+use toml::Spanned;
+
+pub struct Scene {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub output: Option<String>,
+    pub camera: Camera,
+    pub world: Bvh,
+    pub background: Background,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(String, std::io::Error),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Invalid {
+        line: usize,
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(path, e) => write!(f, "{}: {}", path, e),
+            SceneError::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            SceneError::Invalid {
+                line,
+                field,
+                message,
+            } => write!(f, "line {}: {}: {}", line, field, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// a table errors are reported in; inline and dotted tables have no header,
+// so where their value starts stands in for it
+struct Table {
+    name: String,
+    start: usize,
+}
+
+impl SceneError {
+    fn parse(e: toml::de::Error) -> SceneError {
+        let (line, column) = e.line_col().map_or((0, 0), |(l, c)| (l + 1, c + 1));
+        // the position is reported separately
+        let mut message = e.to_string();
+        if let Some(i) = message.rfind(" at line ") {
+            message.truncate(i);
+        }
+        SceneError::Parse {
+            line,
+            column,
+            message,
+        }
+    }
+
+    fn invalid<T>(src: &str, value: &Spanned<T>, field: String, message: String) -> SceneError {
+        SceneError::Invalid {
+            line: src[..value.start()].matches('\n').count() + 1,
+            field,
+            message,
+        }
+    }
+
+    // for values inside [table] headers, whose spans aren't useful
+    fn in_table(src: &str, table: &Table, field: String, message: String) -> SceneError {
+        let header = format!("[{}]", table.name);
+        let line = src
+            .lines()
+            .position(|l| l.trim_start().starts_with(&header))
+            .map_or_else(|| src[..table.start].matches('\n').count() + 1, |i| i + 1);
+        SceneError::Invalid {
+            line,
+            field,
+            message,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    // the sky gradient if omitted
+    background: Option<[f32; 3]>,
+    image: ImageDesc,
+    camera: CameraDesc,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    triangles: Vec<TriangleDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    planes: Vec<PlaneDesc>,
+    #[serde(default)]
+    quads: Vec<QuadDesc>,
+    #[serde(default)]
+    boxes: Vec<BoxDesc>,
+    #[serde(default)]
+    disks: Vec<DiskDesc>,
+    #[serde(default)]
+    heightfields: Vec<HeightfieldDesc>,
+    #[serde(default)]
+    cylinders: Vec<CylinderDesc>,
+    #[serde(default)]
+    cones: Vec<ConeDesc>,
+    #[serde(default)]
+    tori: Vec<TorusDesc>,
+    #[serde(default)]
+    media: Vec<MediumDesc>,
+    #[serde(default)]
+    grids: Vec<GridDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ImageDesc {
+    width: Spanned<u32>,
+    height: Spanned<u32>,
+    // per pixel, default 100
+    samples: Option<Spanned<u32>>,
+    output: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    lookfrom: [f32; 3],
+    lookat: Spanned<[f32; 3]>,
+    // default [0, 1, 0]
+    vup: Option<Spanned<[f32; 3]>>,
+    vfov: Spanned<f32>,
+    // default 0, a pinhole
+    aperture: Option<Spanned<f32>>,
+    // defaults to the distance between lookfrom and lookat
+    focus_dist: Option<Spanned<f32>>,
+    // open and close times, default [0, 1], the keyframes of moving objects
+    shutter: Option<Spanned<[f32; 2]>>,
+}
+
+// either a constant color or the name of a texture
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorDesc {
+    Color([f32; 3]),
+    Texture(String),
+}
+
+// either a constant or the name of a texture, whose channels are averaged
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalarDesc {
+    Value(f32),
+    Texture(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: [f32; 3],
+    },
+    Checker {
+        #[serde(default = "default_scale")]
+        scale: f32,
+        odd: ColorDesc,
+        even: ColorDesc,
+    },
+    UvChecker {
+        width: f32,
+        height: f32,
+        odd: ColorDesc,
+        even: ColorDesc,
+    },
+    Image {
+        file: String,
+    },
+    Noise {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+    },
+    Turbulence {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+    },
+    Marble {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        #[serde(default = "default_light")]
+        base: [f32; 3],
+        #[serde(default)]
+        vein: [f32; 3],
+    },
+    Wood {
+        #[serde(default)]
+        seed: u64,
+        #[serde(default = "default_scale")]
+        scale: f32,
+        light: [f32; 3],
+        dark: [f32; 3],
+    },
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    7
+}
+
+fn default_light() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian {
+        albedo: ColorDesc,
+    },
+    Metal {
+        albedo: ColorDesc,
+        #[serde(default)]
+        fuzz: f32,
+    },
+    // either a named metal or the complex index of refraction eta + i k
+    Conductor {
+        metal: Option<String>,
+        eta: Option<[f32; 3]>,
+        k: Option<[f32; 3]>,
+        #[serde(default)]
+        roughness: f32,
+    },
+    // absorption is the color white light turns after distance inside, and
+    // was called albedo
+    Dielectric {
+        ref_idx: f32,
+        #[serde(alias = "albedo")]
+        absorption: Option<[f32; 3]>,
+        #[serde(default = "default_distance")]
+        distance: f32,
+    },
+    #[serde(rename = "rough_dielectric")]
+    RoughDielectric {
+        ref_idx: f32,
+        #[serde(default)]
+        roughness: f32,
+        absorption: Option<[f32; 3]>,
+        #[serde(default = "default_distance")]
+        distance: f32,
+    },
+    // a dielectric coat over another material
+    Coated {
+        base: String,
+        #[serde(default = "default_coat")]
+        ref_idx: f32,
+        #[serde(default)]
+        roughness: f32,
+    },
+    // Disney's principled BSDF, with the defaults of Principled::new
+    Principled {
+        base_color: ColorDesc,
+        metallic: Option<ScalarDesc>,
+        roughness: Option<ScalarDesc>,
+        specular: Option<ScalarDesc>,
+        clearcoat: Option<ScalarDesc>,
+        clearcoat_roughness: Option<ScalarDesc>,
+        sheen: Option<ScalarDesc>,
+        transmission: Option<ScalarDesc>,
+        ior: Option<ScalarDesc>,
+    },
+    #[serde(rename = "light")]
+    DiffuseLight {
+        emit: ColorDesc,
+    },
+    // the phase functions of media
+    Isotropic {
+        albedo: ColorDesc,
+    },
+    #[serde(rename = "henyey_greenstein")]
+    HenyeyGreenstein {
+        albedo: ColorDesc,
+        // mean cosine of the scattering angle, in (-1, 1)
+        g: f32,
+    },
+}
+
+fn default_distance() -> f32 {
+    1.0
+}
+
+fn default_coat() -> f32 {
+    1.5
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: [f32; 3],
+    radius: Spanned<f32>,
+    material: Spanned<String>,
+    // how far the sphere moves from time 0 to time 1
+    motion: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriangleDesc {
+    points: [[f32; 3]; 3],
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDesc {
+    point: [f32; 3],
+    normal: Spanned<[f32; 3]>,
+    material: Spanned<String>,
+}
+
+// the parallelogram spanned by u and v from corner
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuadDesc {
+    corner: [f32; 3],
+    u: Spanned<[f32; 3]>,
+    v: [f32; 3],
+    material: Spanned<String>,
+}
+
+// opposite corners of an axis aligned box
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoxDesc {
+    min: [f32; 3],
+    max: Spanned<[f32; 3]>,
+    material: Spanned<String>,
+}
+
+// terrain from a grayscale image, black at min.y and white at min.y + size.y
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeightfieldDesc {
+    file: Spanned<String>,
+    min: [f32; 3],
+    size: Spanned<[f32; 3]>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiskDesc {
+    center: [f32; 3],
+    normal: Spanned<[f32; 3]>,
+    radius: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+// closed, between the centers of its end caps
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CylinderDesc {
+    base: [f32; 3],
+    top: Spanned<[f32; 3]>,
+    radius: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConeDesc {
+    base: [f32; 3],
+    apex: Spanned<[f32; 3]>,
+    radius: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TorusDesc {
+    center: [f32; 3],
+    axis: Spanned<[f32; 3]>,
+    major_radius: f32,
+    minor_radius: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+// fog or smoke filling a boundary, scattering by a phase function material
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MediumDesc {
+    // spans can't be kept inside tagged enums, errors point at the whole
+    boundary: Spanned<BoundaryDesc>,
+    density: Spanned<f32>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum BoundaryDesc {
+    Sphere { center: [f32; 3], radius: f32 },
+    Box { min: [f32; 3], max: [f32; 3] },
+}
+
+// smoke or clouds with densities from a raw voxel file stretched over a box
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GridDesc {
+    file: Spanned<String>,
+    resolution: Spanned<[usize; 3]>,
+    min: [f32; 3],
+    max: Spanned<[f32; 3]>,
+    // scales the file's values
+    density: Option<Spanned<f32>>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    file: Spanned<String>,
+    material: Spanned<String>,
+    // in degrees, edges sharper than this aren't smoothed
+    #[serde(default = "default_crease_angle")]
+    crease_angle: f32,
+    // applied in this order; rotate is in degrees around x, then y, then z
+    scale: Option<Spanned<[f32; 3]>>,
+    rotate: Option<[f32; 3]>,
+    translate: Option<[f32; 3]>,
+    // how far the mesh moves from time 0 to time 1, after the transforms
+    motion: Option<[f32; 3]>,
+}
+
+fn default_crease_angle() -> f32 {
+    60.0
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+// builds each named texture once, following references between them
+struct Textures<'a> {
+    src: &'a str,
+    base_dir: &'a Path,
+    descs: &'a HashMap<String, Spanned<TextureDesc>>,
+    built: HashMap<&'a str, TextureKind>,
+    // textures being built, to detect cycles
+    pending: Vec<&'a str>,
+}
+
+impl<'a> Textures<'a> {
+    fn color(
+        &mut self,
+        c: &'a ColorDesc,
+        table: &Table,
+        field: &str,
+    ) -> Result<TextureKind, SceneError> {
+        match c {
+            ColorDesc::Color(c) => Ok(TextureKind::from(vec3(*c))),
+            ColorDesc::Texture(name) => self.texture(name, table, field),
+        }
+    }
+
+    fn scalar(
+        &mut self,
+        x: &'a ScalarDesc,
+        table: &Table,
+        field: &str,
+    ) -> Result<TextureKind, SceneError> {
+        match x {
+            ScalarDesc::Value(x) => Ok(TextureKind::from(*x)),
+            ScalarDesc::Texture(name) => self.texture(name, table, field),
+        }
+    }
+
+    // table and field are where name is referenced from, for errors
+    fn texture(
+        &mut self,
+        name: &'a str,
+        table: &Table,
+        field: &str,
+    ) -> Result<TextureKind, SceneError> {
+        if let Some(t) = self.built.get(name) {
+            return Ok(t.clone());
+        }
+        let error = |message| {
+            SceneError::in_table(
+                self.src,
+                table,
+                format!("{}.{}", table.name, field),
+                message,
+            )
+        };
+        let desc = match self.descs.get(name) {
+            Some(desc) => desc,
+            None => return Err(error(format!("unknown texture `{}`", name))),
+        };
+        if self.pending.contains(&name) {
+            return Err(error(format!("texture `{}` refers to itself", name)));
+        }
+
+        self.pending.push(name);
+        let table = Table {
+            name: format!("textures.{}", name),
+            start: desc.start(),
+        };
+        let invalid = |field: &str, message: &str| {
+            SceneError::in_table(
+                self.src,
+                &table,
+                format!("{}.{}", table.name, field),
+                message.to_string(),
+            )
+        };
+        let texture = match desc.get_ref() {
+            TextureDesc::Solid { color } => TextureKind::Solid(vec3(*color)),
+            TextureDesc::Checker { scale, odd, even } => {
+                if *scale == 0.0 {
+                    return Err(invalid("scale", "must not be 0"));
+                }
+                TextureKind::Checker(Checker::new(
+                    *scale,
+                    self.color(odd, &table, "odd")?,
+                    self.color(even, &table, "even")?,
+                ))
+            }
+            TextureDesc::UvChecker {
+                width,
+                height,
+                odd,
+                even,
+            } => {
+                if *width == 0.0 {
+                    return Err(invalid("width", "must not be 0"));
+                }
+                if *height == 0.0 {
+                    return Err(invalid("height", "must not be 0"));
+                }
+                TextureKind::UvChecker(UvChecker::new(
+                    *width,
+                    *height,
+                    self.color(odd, &table, "odd")?,
+                    self.color(even, &table, "even")?,
+                ))
+            }
+            TextureDesc::Image { file } => {
+                let path = self.base_dir.join(file);
+                let image = ImageTexture::load(&path.to_string_lossy()).map_err(|e| {
+                    SceneError::in_table(
+                        self.src,
+                        &table,
+                        format!("{}.file", table.name),
+                        format!("{}: {}", path.display(), e),
+                    )
+                })?;
+                TextureKind::Image(image)
+            }
+            TextureDesc::Noise { seed, scale } => TextureKind::Noise(Noise::new(*seed, *scale)),
+            TextureDesc::Turbulence {
+                seed,
+                scale,
+                octaves,
+            } => TextureKind::Turbulence(Turbulence::new(*seed, *scale, *octaves)),
+            TextureDesc::Marble {
+                seed,
+                scale,
+                base,
+                vein,
+            } => TextureKind::Marble(Marble::new(*seed, *scale, vec3(*base), vec3(*vein))),
+            TextureDesc::Wood {
+                seed,
+                scale,
+                light,
+                dark,
+            } => TextureKind::Wood(Wood::new(*seed, *scale, vec3(*light), vec3(*dark))),
+        };
+        self.pending.pop();
+        self.built.insert(name, texture.clone());
+        Ok(texture)
+    }
+}
+
+struct Materials<'a> {
+    descs: &'a HashMap<String, Spanned<MaterialDesc>>,
+    built: HashMap<&'a str, MaterialKind>,
+    // materials being built, to detect cycles
+    pending: Vec<&'a str>,
+}
+
+impl<'a> Materials<'a> {
+    // table and field are where name is referenced from, for errors
+    fn material(
+        &mut self,
+        name: &'a str,
+        textures: &mut Textures<'a>,
+        table: &Table,
+        field: &str,
+    ) -> Result<MaterialKind, SceneError> {
+        if let Some(m) = self.built.get(name) {
+            return Ok(m.clone());
+        }
+        let src = textures.src;
+        let error = |message| {
+            SceneError::in_table(src, table, format!("{}.{}", table.name, field), message)
+        };
+        let desc = match self.descs.get(name) {
+            Some(desc) => desc,
+            None => return Err(error(format!("unknown material `{}`", name))),
+        };
+        if self.pending.contains(&name) {
+            return Err(error(format!("material `{}` refers to itself", name)));
+        }
+        self.pending.push(name);
+        let material = desc.get_ref().build(name, desc.start(), textures, self)?;
+        self.pending.pop();
+        self.built.insert(name, material.clone());
+        Ok(material)
+    }
+}
+
+impl MaterialDesc {
+    fn build<'a>(
+        &'a self,
+        name: &str,
+        // where the material's value starts in the scene file
+        start: usize,
+        textures: &mut Textures<'a>,
+        materials: &mut Materials<'a>,
+    ) -> Result<MaterialKind, SceneError> {
+        let table = Table {
+            name: format!("materials.{}", name),
+            start,
+        };
+        let src = textures.src;
+        let invalid = |field: &str, message: &str| {
+            SceneError::in_table(
+                src,
+                &table,
+                format!("{}.{}", table.name, field),
+                message.to_string(),
+            )
+        };
+        // the index and absorption of the dielectrics
+        let glass = |ref_idx: f32, distance: f32| {
+            if ref_idx <= 0.0 {
+                Err(invalid("ref_idx", "must be positive"))
+            } else if distance <= 0.0 {
+                Err(invalid("distance", "must be positive"))
+            } else {
+                Ok(())
+            }
+        };
+        Ok(match self {
+            MaterialDesc::Lambertian { albedo } => {
+                MaterialKind::Lambertian(Lambertian::new(textures.color(albedo, &table, "albedo")?))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => {
+                MaterialKind::Metal(Metal::new(textures.color(albedo, &table, "albedo")?, *fuzz))
+            }
+            MaterialDesc::Conductor {
+                metal,
+                eta,
+                k,
+                roughness,
+            } => {
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(invalid("roughness", "must be between 0 and 1"));
+                }
+                let conductor = match (metal.as_deref(), eta, k) {
+                    (Some("gold"), None, None) => Conductor::gold(*roughness),
+                    (Some("copper"), None, None) => Conductor::copper(*roughness),
+                    (Some("aluminium"), None, None) => Conductor::aluminium(*roughness),
+                    (Some("silver"), None, None) => Conductor::silver(*roughness),
+                    (Some(_), None, None) => {
+                        return Err(invalid(
+                            "metal",
+                            "must be gold, copper, aluminium or silver",
+                        ))
+                    }
+                    (None, Some(eta), Some(k)) => Conductor::new(vec3(*eta), vec3(*k), *roughness),
+                    _ => return Err(invalid("metal", "needs either metal or both eta and k")),
+                };
+                MaterialKind::Conductor(conductor)
+            }
+            MaterialDesc::Dielectric {
+                ref_idx,
+                absorption,
+                distance,
+            } => {
+                glass(*ref_idx, *distance)?;
+                let glass = Dielectric::new(*ref_idx);
+                MaterialKind::Dielectric(match absorption {
+                    Some(color) => glass.absorption(vec3(*color), *distance),
+                    None => glass,
+                })
+            }
+            MaterialDesc::RoughDielectric {
+                ref_idx,
+                roughness,
+                absorption,
+                distance,
+            } => {
+                glass(*ref_idx, *distance)?;
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(invalid("roughness", "must be between 0 and 1"));
+                }
+                let glass = RoughDielectric::new(*ref_idx, *roughness);
+                MaterialKind::RoughDielectric(match absorption {
+                    Some(color) => glass.absorption(vec3(*color), *distance),
+                    None => glass,
+                })
+            }
+            MaterialDesc::Coated {
+                base,
+                ref_idx,
+                roughness,
+            } => {
+                if *ref_idx <= 0.0 {
+                    return Err(invalid("ref_idx", "must be positive"));
+                }
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(invalid("roughness", "must be between 0 and 1"));
+                }
+                let base = materials.material(base, textures, &table, "base")?;
+                MaterialKind::Coated(Coated::new(base, *ref_idx, *roughness))
+            }
+            MaterialDesc::Principled {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                clearcoat,
+                clearcoat_roughness,
+                sheen,
+                transmission,
+                ior,
+            } => {
+                let mut m = Principled::new(textures.color(base_color, &table, "base_color")?);
+                type Setter = fn(Principled, TextureKind) -> Principled;
+                let params: [(&Option<ScalarDesc>, &str, Setter); 8] = [
+                    (metallic, "metallic", |m, t| m.metallic(t)),
+                    (roughness, "roughness", |m, t| m.roughness(t)),
+                    (specular, "specular", |m, t| m.specular(t)),
+                    (clearcoat, "clearcoat", |m, t| m.clearcoat(t)),
+                    (clearcoat_roughness, "clearcoat_roughness", |m, t| {
+                        m.clearcoat_roughness(t)
+                    }),
+                    (sheen, "sheen", |m, t| m.sheen(t)),
+                    (transmission, "transmission", |m, t| m.transmission(t)),
+                    (ior, "ior", |m, t| m.ior(t)),
+                ];
+                for (desc, field, set) in params.iter() {
+                    let desc = match desc {
+                        Some(desc) => desc,
+                        None => continue,
+                    };
+                    match desc {
+                        ScalarDesc::Value(x) if *field == "ior" && *x <= 0.0 => {
+                            return Err(invalid(field, "must be positive"))
+                        }
+                        ScalarDesc::Value(x) if *field != "ior" && !(0.0..=1.0).contains(x) => {
+                            return Err(invalid(field, "must be between 0 and 1"))
+                        }
+                        _ => {}
+                    }
+                    m = set(m, textures.scalar(desc, &table, field)?);
+                }
+                MaterialKind::Principled(Box::new(m))
+            }
+            MaterialDesc::DiffuseLight { emit } => {
+                MaterialKind::DiffuseLight(DiffuseLight::new(textures.color(emit, &table, "emit")?))
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                MaterialKind::Isotropic(Isotropic::new(textures.color(albedo, &table, "albedo")?))
+            }
+            MaterialDesc::HenyeyGreenstein { albedo, g } => {
+                if !(*g > -1.0 && *g < 1.0) {
+                    return Err(invalid("g", "must be greater than -1 and less than 1"));
+                }
+                MaterialKind::HenyeyGreenstein(HenyeyGreenstein::new(
+                    textures.color(albedo, &table, "albedo")?,
+                    *g,
+                ))
+            }
+        })
+    }
+}
+
+pub fn load_scene(scene_file_path: &str) -> Result<Scene, SceneError> {
+    let src = std::fs::read_to_string(scene_file_path)
+        .map_err(|e| SceneError::Io(scene_file_path.to_string(), e))?;
+    let base_dir = Path::new(scene_file_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    parse_scene(&src, base_dir)
+}
+
+// mesh and image files are looked up relative to base_dir
+pub fn parse_scene(src: &str, base_dir: &Path) -> Result<Scene, SceneError> {
+    let desc: SceneDesc = toml::from_str(src).map_err(SceneError::parse)?;
+
+    let width = *desc.image.width.get_ref();
+    let height = *desc.image.height.get_ref();
+    for (v, field) in [(&desc.image.width, "width"), (&desc.image.height, "height")].iter() {
+        if *v.get_ref() == 0 {
+            return Err(SceneError::invalid(
+                src,
+                v,
+                format!("image.{}", field),
+                "must be greater than 0".to_string(),
+            ));
+        }
+    }
+    let samples = match &desc.image.samples {
+        Some(s) if *s.get_ref() == 0 => {
+            return Err(SceneError::invalid(
+                src,
+                s,
+                "image.samples".to_string(),
+                "must be greater than 0".to_string(),
+            ))
+        }
+        Some(s) => *s.get_ref(),
+        None => 100,
+    };
+    let vfov = *desc.camera.vfov.get_ref();
+    if !(vfov > 0.0 && vfov < 180.0) {
+        return Err(SceneError::invalid(
+            src,
+            &desc.camera.vfov,
+            "camera.vfov".to_string(),
+            "must be greater than 0 and less than 180".to_string(),
+        ));
+    }
+    // the radius of a sphere, disk, cylinder or cone
+    let radius = |r: &Spanned<f32>, field: String| {
+        if *r.get_ref() > 0.0 {
+            Ok(*r.get_ref())
+        } else {
+            Err(SceneError::invalid(
+                src,
+                r,
+                field,
+                "must be greater than 0".to_string(),
+            ))
+        }
+    };
+
+    let mut textures = Textures {
+        src,
+        base_dir,
+        descs: &desc.textures,
+        built: HashMap::new(),
+        pending: vec![],
+    };
+    // in a fixed order so that the first error is always the same
+    let mut names: Vec<&String> = desc.textures.keys().collect();
+    names.sort();
+    for name in names {
+        let table = Table {
+            name: "textures".to_string(),
+            start: desc.textures[name].start(),
+        };
+        textures.texture(name, &table, name)?;
+    }
+    let mut names: Vec<&String> = desc.materials.keys().collect();
+    names.sort();
+    let mut materials = Materials {
+        descs: &desc.materials,
+        built: HashMap::new(),
+        pending: vec![],
+    };
+    for name in names {
+        let table = Table {
+            name: "materials".to_string(),
+            start: desc.materials[name].start(),
+        };
+        materials.material(name, &mut textures, &table, name)?;
+    }
+    let material = |name: &Spanned<String>, field: String| {
+        materials
+            .built
+            .get(name.get_ref().as_str())
+            .cloned()
+            .ok_or_else(|| {
+                SceneError::invalid(
+                    src,
+                    name,
+                    field,
+                    format!("unknown material `{}`", name.get_ref()),
+                )
+            })
+    };
+    // the material of a medium, which must be a phase function
+    let phase = |name: &Spanned<String>, field: String| {
+        let mat = material(name, field.clone())?;
+        match mat {
+            MaterialKind::Isotropic(_) | MaterialKind::HenyeyGreenstein(_) => Ok(mat),
+            _ => Err(SceneError::invalid(
+                src,
+                name,
+                field,
+                "must be an isotropic or henyey_greenstein material".to_string(),
+            )),
+        }
+    };
+
+    let mut world = HittableList::new();
+    for (i, s) in desc.spheres.iter().enumerate() {
+        let mat = material(&s.material, format!("spheres[{}].material", i))?;
+        let r = radius(&s.radius, format!("spheres[{}].radius", i))?;
+        let center = vec3(s.center);
+        match s.motion {
+            Some(m) => world.list.push(Box::new(MovingSphere::new(
+                center,
+                center + vec3(m),
+                0.0,
+                1.0,
+                r,
+                mat,
+            ))),
+            None => world.list.push(Box::new(Sphere::new(center, r, mat))),
+        }
+    }
+    for (i, t) in desc.triangles.iter().enumerate() {
+        let mat = material(&t.material, format!("triangles[{}].material", i))?;
+        let [a, b, c] = t.points;
+        world
+            .list
+            .push(Box::new(Triangle::new(vec3(a), vec3(b), vec3(c), mat)));
+    }
+    for (i, p) in desc.planes.iter().enumerate() {
+        let mat = material(&p.material, format!("planes[{}].material", i))?;
+        let normal = vec3(*p.normal.get_ref());
+        if normal.squared_length() == 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &p.normal,
+                format!("planes[{}].normal", i),
+                "must not be 0".to_string(),
+            ));
+        }
+        world
+            .list
+            .push(Box::new(Plane::new(vec3(p.point), normal, mat)));
+    }
+    for (i, q) in desc.quads.iter().enumerate() {
+        let mat = material(&q.material, format!("quads[{}].material", i))?;
+        let (u, v) = (vec3(*q.u.get_ref()), vec3(q.v));
+        if u.cross(v).squared_length() == 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &q.u,
+                format!("quads[{}].u", i),
+                "must not be parallel to v".to_string(),
+            ));
+        }
+        world
+            .list
+            .push(Box::new(Quad::new(vec3(q.corner), u, v, mat)));
+    }
+    for (i, b) in desc.boxes.iter().enumerate() {
+        let mat = material(&b.material, format!("boxes[{}].material", i))?;
+        let (min, max) = (vec3(b.min), vec3(*b.max.get_ref()));
+        if (0..3).any(|a| min[a] >= max[a]) {
+            return Err(SceneError::invalid(
+                src,
+                &b.max,
+                format!("boxes[{}].max", i),
+                "must be greater than min on every axis".to_string(),
+            ));
+        }
+        world.list.push(Box::new(BoxShape::new(min, max, mat)));
+    }
+    for (i, h) in desc.heightfields.iter().enumerate() {
+        let mat = material(&h.material, format!("heightfields[{}].material", i))?;
+        let size = vec3(*h.size.get_ref());
+        if size.x() <= 0.0 || size.z() <= 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &h.size,
+                format!("heightfields[{}].size", i),
+                "must be greater than 0 along x and z".to_string(),
+            ));
+        }
+        let path = base_dir.join(h.file.get_ref());
+        let heightfield = Heightfield::load(&path.to_string_lossy(), vec3(h.min), size, mat)
+            .map_err(|e| {
+                SceneError::invalid(
+                    src,
+                    &h.file,
+                    format!("heightfields[{}].file", i),
+                    format!("{}: {}", path.display(), e),
+                )
+            })?;
+        world.list.push(Box::new(heightfield));
+    }
+    for (i, d) in desc.disks.iter().enumerate() {
+        let mat = material(&d.material, format!("disks[{}].material", i))?;
+        let r = radius(&d.radius, format!("disks[{}].radius", i))?;
+        let normal = vec3(*d.normal.get_ref());
+        if normal.squared_length() == 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &d.normal,
+                format!("disks[{}].normal", i),
+                "must not be 0".to_string(),
+            ));
+        }
+        world
+            .list
+            .push(Box::new(Disk::new(vec3(d.center), normal, r, mat)));
+    }
+    for (i, c) in desc.cylinders.iter().enumerate() {
+        let mat = material(&c.material, format!("cylinders[{}].material", i))?;
+        let r = radius(&c.radius, format!("cylinders[{}].radius", i))?;
+        let (base, top) = (vec3(c.base), vec3(*c.top.get_ref()));
+        if (top - base).squared_length() == 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &c.top,
+                format!("cylinders[{}].top", i),
+                "must differ from base".to_string(),
+            ));
+        }
+        world.list.push(Box::new(Cylinder::new(base, top, r, mat)));
+    }
+    for (i, c) in desc.cones.iter().enumerate() {
+        let mat = material(&c.material, format!("cones[{}].material", i))?;
+        let r = radius(&c.radius, format!("cones[{}].radius", i))?;
+        let (base, apex) = (vec3(c.base), vec3(*c.apex.get_ref()));
+        if (apex - base).squared_length() == 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &c.apex,
+                format!("cones[{}].apex", i),
+                "must differ from base".to_string(),
+            ));
+        }
+        world.list.push(Box::new(Cone::new(base, apex, r, mat)));
+    }
+    for (i, t) in desc.tori.iter().enumerate() {
+        let mat = material(&t.material, format!("tori[{}].material", i))?;
+        let axis = vec3(*t.axis.get_ref());
+        if axis.squared_length() == 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &t.axis,
+                format!("tori[{}].axis", i),
+                "must not be 0".to_string(),
+            ));
+        }
+        let minor = *t.minor_radius.get_ref();
+        if !(0.0 < minor && minor < t.major_radius) {
+            return Err(SceneError::invalid(
+                src,
+                &t.minor_radius,
+                format!("tori[{}].minor_radius", i),
+                "must be greater than 0 and less than major_radius".to_string(),
+            ));
+        }
+        world.list.push(Box::new(Torus::new(
+            vec3(t.center),
+            axis,
+            t.major_radius,
+            minor,
+            mat,
+        )));
+    }
+    for (i, m) in desc.media.iter().enumerate() {
+        let mat = phase(&m.material, format!("media[{}].material", i))?;
+        if *m.density.get_ref() <= 0.0 {
+            return Err(SceneError::invalid(
+                src,
+                &m.density,
+                format!("media[{}].density", i),
+                "must be greater than 0".to_string(),
+            ));
+        }
+        let invalid = |field: &str, message: &str| {
+            SceneError::invalid(
+                src,
+                &m.boundary,
+                format!("media[{}].boundary.{}", i, field),
+                message.to_string(),
+            )
+        };
+        let boundary: Box<dyn Hittable + Send + Sync> = match *m.boundary.get_ref() {
+            BoundaryDesc::Sphere { center, radius } => {
+                if radius <= 0.0 {
+                    return Err(invalid("radius", "must be greater than 0"));
+                }
+                Box::new(Sphere::new(vec3(center), radius, mat.clone()))
+            }
+            BoundaryDesc::Box { min, max } => {
+                let (min, max) = (vec3(min), vec3(max));
+                if (0..3).any(|a| min[a] >= max[a]) {
+                    return Err(invalid("max", "must be greater than min on every axis"));
+                }
+                Box::new(BoxShape::new(min, max, mat.clone()))
+            }
+        };
+        world.list.push(Box::new(ConstantMedium::new(
+            boundary,
+            *m.density.get_ref(),
+            mat,
+        )));
+    }
+    for (i, g) in desc.grids.iter().enumerate() {
+        let mat = phase(&g.material, format!("grids[{}].material", i))?;
+        let resolution = *g.resolution.get_ref();
+        if resolution.contains(&0) {
+            return Err(SceneError::invalid(
+                src,
+                &g.resolution,
+                format!("grids[{}].resolution", i),
+                "must be greater than 0".to_string(),
+            ));
+        }
+        let (min, max) = (vec3(g.min), vec3(*g.max.get_ref()));
+        if (0..3).any(|a| min[a] >= max[a]) {
+            return Err(SceneError::invalid(
+                src,
+                &g.max,
+                format!("grids[{}].max", i),
+                "must be greater than min on every axis".to_string(),
+            ));
+        }
+        let density = match &g.density {
+            Some(d) if *d.get_ref() <= 0.0 => {
+                return Err(SceneError::invalid(
+                    src,
+                    d,
+                    format!("grids[{}].density", i),
+                    "must be greater than 0".to_string(),
+                ));
+            }
+            Some(d) => *d.get_ref(),
+            None => 1.0,
+        };
+        let path = base_dir.join(g.file.get_ref());
+        let grid = DensityGrid::load(&path, resolution).map_err(|e| {
+            SceneError::invalid(
+                src,
+                &g.file,
+                format!("grids[{}].file", i),
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        world
+            .list
+            .push(Box::new(GridMedium::new(min, max, grid, density, mat)));
+    }
+    // the same file with the same settings is loaded once and instanced
+    let mut meshes: HashMap<(&str, &str, u32), Arc<Mesh>> = HashMap::new();
+    for (i, m) in desc.meshes.iter().enumerate() {
+        let key = (
+            m.file.get_ref().as_str(),
+            m.material.get_ref().as_str(),
+            m.crease_angle.to_bits(),
+        );
+        let mesh = match meshes.get(&key) {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let mat = material(&m.material, format!("meshes[{}].material", i))?;
+                let path = base_dir.join(m.file.get_ref());
+                let mesh = load_obj(&path.to_string_lossy(), mat, m.crease_angle).map_err(|e| {
+                    SceneError::invalid(src, &m.file, format!("meshes[{}].file", i), e.to_string())
+                })?;
+                let mesh = Arc::new(mesh);
+                meshes.insert(key, mesh.clone());
+                mesh
+            }
+        };
+
+        let mut transform = Transform::identity();
+        if let Some(scale) = &m.scale {
+            let v = vec3(*scale.get_ref());
+            if v.x() == 0.0 || v.y() == 0.0 || v.z() == 0.0 {
+                return Err(SceneError::invalid(
+                    src,
+                    scale,
+                    format!("meshes[{}].scale", i),
+                    "must not be 0".to_string(),
+                ));
+            }
+            transform = transform.scale(v);
+        }
+        if let Some([x, y, z]) = m.rotate {
+            transform = transform.rotate_x(x).rotate_y(y).rotate_z(z);
+        }
+        if let Some(v) = m.translate {
+            transform = transform.translate(vec3(v));
+        }
+        let offset = vec3(m.motion.unwrap_or([0.0; 3]));
+        world.list.push(Box::new(Instance::moving(
+            mesh, transform, offset, 0.0, 1.0,
+        )));
+    }
+
+    let c = &desc.camera;
+    let lookfrom = vec3(c.lookfrom);
+    let lookat = vec3(*c.lookat.get_ref());
+    if (lookat - lookfrom).squared_length() == 0.0 {
+        return Err(SceneError::invalid(
+            src,
+            &c.lookat,
+            "camera.lookat".to_string(),
+            "must not be lookfrom".to_string(),
+        ));
+    }
+    let vup = c
+        .vup
+        .as_ref()
+        .map_or(Vec3::new(0.0, 1.0, 0.0), |v| vec3(*v.get_ref()));
+    if vup.cross(lookat - lookfrom).squared_length() == 0.0 {
+        // lookat is to blame when vup is left out
+        let (value, field) = match &c.vup {
+            Some(vup) => (vup, "camera.vup"),
+            None => (&c.lookat, "camera.lookat"),
+        };
+        return Err(SceneError::invalid(
+            src,
+            value,
+            field.to_string(),
+            "must not be parallel to the view direction".to_string(),
+        ));
+    }
+    let aperture = match &c.aperture {
+        Some(a) if *a.get_ref() < 0.0 => {
+            return Err(SceneError::invalid(
+                src,
+                a,
+                "camera.aperture".to_string(),
+                "must not be negative".to_string(),
+            ))
+        }
+        Some(a) => *a.get_ref(),
+        None => 0.0,
+    };
+    let focus_dist = match &c.focus_dist {
+        Some(d) if *d.get_ref() <= 0.0 => {
+            return Err(SceneError::invalid(
+                src,
+                d,
+                "camera.focus_dist".to_string(),
+                "must be greater than 0".to_string(),
+            ))
+        }
+        Some(d) => *d.get_ref(),
+        None => (lookfrom - lookat).length(),
+    };
+    let aspect = width as f32 / height as f32;
+    let [open, close] = c.shutter.as_ref().map_or([0.0, 1.0], |s| *s.get_ref());
+    if let Some(shutter) = &c.shutter {
+        if open > close {
+            return Err(SceneError::invalid(
+                src,
+                shutter,
+                "camera.shutter".to_string(),
+                "must not close before it opens".to_string(),
+            ));
+        }
+    }
+    let camera =
+        Camera::new(lookfrom, lookat, vup, vfov, aspect, aperture, focus_dist).shutter(open, close);
+
+    Ok(Scene {
+        width,
+        height,
+        samples,
+        output: desc.image.output,
+        camera,
+        world: Bvh::from(world),
+        background: desc
+            .background
+            .map_or(Background::Sky, |c| Background::Color(vec3(c))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ray::Ray;
+    use crate::scene::*;
+    use float_eq::assert_float_eq;
+
+    const SCENE: &str = r#"
+[image]
+width = 320
+height = 240
+samples = 10
+
+[camera]
+lookfrom = [13.0, 2.0, 3.0]
+lookat = [0.0, 0.0, 0.0]
+vfov = 20.0
+aperture = 0.1
+
+[materials.ground]
+type = "lambertian"
+albedo = [0.5, 0.5, 0.5]
+
+[materials.glass]
+type = "dielectric"
+ref_idx = 1.5
+
+[materials.mirror]
+type = "metal"
+albedo = [0.7, 0.6, 0.5]
+
+[[spheres]]
+center = [0.0, -1000.0, 0.0]
+radius = 1000
+material = "ground"
+
+[[spheres]]
+center = [0.0, 1.0, 0.0]
+radius = 1.0
+material = "glass"
+
+[[triangles]]
+points = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+material = "mirror"
+"#;
+
+    fn parse(src: &str) -> Result<Scene, SceneError> {
+        parse_scene(src, Path::new(""))
+    }
+
+    #[test]
+    fn parse_ok() {
+        let scene = parse(SCENE).unwrap();
+        assert_eq!(scene.width, 320);
+        assert_eq!(scene.height, 240);
+        assert_eq!(scene.samples, 10);
+        assert!(scene.output.is_none());
+        assert_eq!(scene.world.len(), 3);
+    }
+
+    #[test]
+    fn light_and_background() {
+        let src = format!(
+            "background = [0.0, 0.0, 0.0]\n{}\n{}",
+            SCENE, "[materials.lamp]\ntype = \"light\"\nemit = [4.0, 4.0, 4.0]\n"
+        );
+        let scene = parse(&src).unwrap();
+        match scene.background {
+            Background::Color(c) => assert_eq!(c.x(), 0.0),
+            _ => panic!("expected a background color"),
+        }
+        assert!(matches!(parse(SCENE).unwrap().background, Background::Sky));
+    }
+
+    #[test]
+    fn unknown_material() {
+        let src = SCENE.replace("material = \"glass\"", "material = \"glas\"");
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 33);
+                assert_eq!(field, "spheres[1].material");
+            }
+            _ => panic!("expected an invalid material error"),
+        }
+    }
+
+    #[test]
+    fn missing_field() {
+        let src = SCENE.replace("radius = 1.0\n", "");
+        let e = parse(&src).err().unwrap();
+        match &e {
+            SceneError::Parse { message, .. } => {
+                assert!(message.contains("radius"), "{}", message);
+                assert!(message.contains("spheres"), "{}", message);
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn wrong_type() {
+        let src = SCENE.replace("vfov = 20.0", "vfov = \"wide\"");
+        match parse(&src) {
+            Err(SceneError::Parse { line, message, .. }) => {
+                assert_eq!(line, 10);
+                assert!(message.contains("camera.vfov"), "{}", message);
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn zero_size() {
+        let src = SCENE.replace("height = 240", "height = 0");
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(field, "image.height");
+            }
+            _ => panic!("expected an invalid size error"),
+        }
+    }
+
+    #[test]
+    fn invalid_values() {
+        let disk = "\n[[disks]]\ncenter = [0.0, 0.0, 0.0]\nnormal = [0.0, 1.0, 0.0]\nradius = 1.0\nmaterial = \"mirror\"\n";
+        let cylinder = "\n[[cylinders]]\nbase = [0.0, 0.0, 0.0]\ntop = [0.0, 1.0, 0.0]\nradius = 1.0\nmaterial = \"mirror\"\n";
+        let boxes =
+            "\n[[boxes]]\nmin = [0.0, 0.0, 0.0]\nmax = [1.0, 1.0, 1.0]\nmaterial = \"mirror\"\n";
+        let fog = "\n[materials.fog]\ntype = \"isotropic\"\nalbedo = [1.0, 1.0, 1.0]\n";
+        let sphere = "\n[[media]]\nboundary = { type = \"sphere\", center = [0.0, 0.0, 0.0], radius = 1.0 }\ndensity = 0.1\nmaterial = \"fog\"\n";
+        let cube = "\n[[media]]\nboundary = { type = \"box\", min = [0.0, 0.0, 0.0], max = [1.0, 1.0, 1.0] }\ndensity = 0.1\nmaterial = \"fog\"\n";
+        let broken = [
+            (
+                SCENE.replace("samples = 10", "samples = 0"),
+                5,
+                "image.samples",
+            ),
+            (
+                SCENE.replace("vfov = 20.0", "vfov = 0.0"),
+                10,
+                "camera.vfov",
+            ),
+            (
+                SCENE.replace("vfov = 20.0", "vfov = 180.0"),
+                10,
+                "camera.vfov",
+            ),
+            (
+                SCENE.replace("radius = 1.0", "radius = -1.0"),
+                32,
+                "spheres[1].radius",
+            ),
+            (
+                format!("{}{}", SCENE, disk.replace("1.0\nmat", "0.0\nmat")),
+                42,
+                "disks[0].radius",
+            ),
+            (
+                format!("{}{}", SCENE, cylinder.replace("1.0\nmat", "-0.5\nmat")),
+                42,
+                "cylinders[0].radius",
+            ),
+            (
+                SCENE.replace("lookat = [0.0, 0.0, 0.0]", "lookat = [13.0, 2.0, 3.0]"),
+                9,
+                "camera.lookat",
+            ),
+            (
+                SCENE.replace(
+                    "aperture = 0.1",
+                    "aperture = 0.1\nvup = [-13.0, -2.0, -3.0]",
+                ),
+                12,
+                "camera.vup",
+            ),
+            // straight down, along the default vup
+            (
+                SCENE.replace("lookfrom = [13.0, 2.0, 3.0]", "lookfrom = [0.0, 5.0, 0.0]"),
+                9,
+                "camera.lookat",
+            ),
+            (
+                SCENE.replace("aperture = 0.1", "aperture = -0.1"),
+                11,
+                "camera.aperture",
+            ),
+            (
+                SCENE.replace("aperture = 0.1", "aperture = 0.1\nfocus_dist = 0.0"),
+                12,
+                "camera.focus_dist",
+            ),
+            (
+                format!(
+                    "{}{}",
+                    SCENE,
+                    boxes.replace("[1.0, 1.0, 1.0]", "[1.0, 0.0, 1.0]")
+                ),
+                41,
+                "boxes[0].max",
+            ),
+            (
+                format!("{}{}{}", SCENE, fog, sphere.replace("1.0 }", "0.0 }")),
+                44,
+                "media[0].boundary.radius",
+            ),
+            (
+                format!(
+                    "{}{}{}",
+                    SCENE,
+                    fog,
+                    cube.replace("[1.0, 1.0, 1.0]", "[1.0, 1.0, -1.0]")
+                ),
+                44,
+                "media[0].boundary.max",
+            ),
+        ];
+        for (src, line, field) in broken.iter() {
+            match parse(src) {
+                Err(SceneError::Invalid {
+                    line: l, field: f, ..
+                }) => {
+                    assert_eq!((l, f.as_str()), (*line, *field));
+                }
+                _ => panic!("expected an invalid {} error", field),
+            }
+        }
+        assert!(parse(&format!("{}{}{}", SCENE, disk, cylinder)).is_ok());
+        assert!(parse(&format!("{}{}{}{}{}", SCENE, boxes, fog, sphere, cube)).is_ok());
+    }
+
+    #[test]
+    fn invalid_textures() {
+        let checker = "\n[textures.check]\ntype = \"checker\"\nscale = 2.0\nodd = [1.0, 1.0, 1.0]\neven = [0.0, 0.0, 0.0]\n";
+        let uv = "\n[textures.check]\ntype = \"uv_checker\"\nwidth = 8.0\nheight = 8.0\nodd = [1.0, 1.0, 1.0]\neven = [0.0, 0.0, 0.0]\n";
+        // inline, without a [textures.check] header to point at
+        let inline = "\n[textures]\ncheck = { type = \"checker\", scale = 2.0, odd = [1.0, 1.0, 1.0], even = [0.0, 0.0, 0.0] }\n";
+        for src in [checker, uv, inline].iter() {
+            assert!(parse(&format!("{}{}", SCENE, src)).is_ok());
+        }
+        for (src, line, field) in [
+            (checker.replace("2.0", "0.0"), 39, "textures.check.scale"),
+            (
+                uv.replace("width = 8.0", "width = 0.0"),
+                39,
+                "textures.check.width",
+            ),
+            (
+                uv.replace("height = 8.0", "height = 0.0"),
+                39,
+                "textures.check.height",
+            ),
+            (inline.replace("2.0", "0.0"), 40, "textures.check.scale"),
+        ]
+        .iter()
+        {
+            match parse(&format!("{}{}", SCENE, src)) {
+                Err(SceneError::Invalid {
+                    line: l, field: f, ..
+                }) => {
+                    assert_eq!((l, f.as_str()), (*line, *field));
+                }
+                _ => panic!("expected an invalid {} error", field),
+            }
+        }
+    }
+
+    #[test]
+    fn conductors() {
+        let gold = "\n[materials.gold]\ntype = \"conductor\"\nmetal = \"gold\"\nroughness = 0.2\n";
+        let src = format!("{}{}", SCENE, gold);
+        assert!(parse(&src).is_ok());
+        let custom = gold.replace(
+            "metal = \"gold\"",
+            "eta = [0.2, 0.9, 1.1]\nk = [3.9, 2.4, 2.1]",
+        );
+        assert!(parse(&format!("{}{}", SCENE, custom)).is_ok());
+
+        for (change, field) in [
+            ("metal = \"tin\"", "materials.gold.metal"),
+            ("eta = [1.0, 1.0, 1.0]", "materials.gold.metal"),
+            ("roughness = 1.5", "materials.gold.roughness"),
+        ]
+        .iter()
+        {
+            let key = change.split(' ').next().unwrap();
+            let broken = gold
+                .lines()
+                .filter(|l| !l.starts_with(key))
+                .chain(std::iter::once(*change))
+                .collect::<Vec<_>>()
+                .join("\n");
+            match parse(&format!("{}{}", SCENE, broken)) {
+                Err(SceneError::Invalid { line, field: f, .. }) => {
+                    assert_eq!(line, 39);
+                    assert_eq!(f, *field);
+                }
+                _ => panic!("expected an invalid {} error", field),
+            }
+        }
+    }
+
+    #[test]
+    fn dielectrics() {
+        let tinted = "\n[materials.tinted]\ntype = \"dielectric\"\nref_idx = 1.5\nabsorption = [0.9, 0.5, 0.5]\ndistance = 2.0\n";
+        assert!(parse(&format!("{}{}", SCENE, tinted)).is_ok());
+        // the old name of absorption
+        let src = format!("{}{}", SCENE, tinted.replace("absorption", "albedo"));
+        assert!(parse(&src).is_ok());
+        let src = format!("{}{}", SCENE, tinted.replace("2.0", "-1.0"));
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 39);
+                assert_eq!(field, "materials.tinted.distance");
+            }
+            _ => panic!("expected an invalid distance error"),
+        }
+    }
+
+    #[test]
+    fn rough_dielectrics() {
+        let frosted = "\n[materials.frosted]\ntype = \"rough_dielectric\"\nref_idx = 1.5\nroughness = 0.3\nabsorption = [0.9, 0.5, 0.5]\n";
+        assert!(parse(&format!("{}{}", SCENE, frosted)).is_ok());
+        let broken = [
+            (
+                frosted.replace("0.3", "-0.1"),
+                "materials.frosted.roughness",
+            ),
+            (frosted.replace("1.5", "0.0"), "materials.frosted.ref_idx"),
+            (
+                format!("{}distance = 0.0\n", frosted),
+                "materials.frosted.distance",
+            ),
+        ];
+        for (src, field) in broken.iter() {
+            match parse(&format!("{}{}", SCENE, src)) {
+                Err(SceneError::Invalid { line, field: f, .. }) => {
+                    assert_eq!(line, 39);
+                    assert_eq!(f, *field);
+                }
+                _ => panic!("expected an invalid {} error", field),
+            }
+        }
+    }
+
+    #[test]
+    fn principled() {
+        let plastic = "\n[materials.plastic]\ntype = \"principled\"\nbase_color = [0.8, 0.1, 0.1]\nroughness = \"grain\"\nclearcoat = 1.0\nior = 1.45\n";
+        let grain = "\n[textures.grain]\ntype = \"noise\"\nscale = 4.0\n";
+        assert!(parse(&format!("{}{}{}", SCENE, plastic, grain)).is_ok());
+        let broken = [
+            (plastic.replace("1.0", "1.5"), "materials.plastic.clearcoat"),
+            (plastic.replace("1.45", "0.0"), "materials.plastic.ior"),
+            (
+                format!("{}sheen = -0.5\n", plastic),
+                "materials.plastic.sheen",
+            ),
+        ];
+        for (src, field) in broken.iter() {
+            match parse(&format!("{}{}{}", SCENE, src, grain)) {
+                Err(SceneError::Invalid { line, field: f, .. }) => {
+                    assert_eq!(line, 39);
+                    assert_eq!(f, *field);
+                }
+                _ => panic!("expected an invalid {} error", field),
+            }
+        }
+        // without the texture
+        match parse(&format!("{}{}", SCENE, plastic)) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 39);
+                assert_eq!(field, "materials.plastic.roughness");
+            }
+            _ => panic!("expected an unknown texture error"),
+        }
+    }
+
+    #[test]
+    fn coated() {
+        let varnish =
+            "\n[materials.varnish]\ntype = \"coated\"\nbase = \"ground\"\nroughness = 0.1\n";
+        assert!(parse(&format!("{}{}", SCENE, varnish)).is_ok());
+        // coats over coats, whatever order they come in
+        let twice = "\n[materials.a]\ntype = \"coated\"\nbase = \"varnish\"\n";
+        assert!(parse(&format!("{}{}{}", SCENE, varnish, twice)).is_ok());
+        let broken = [
+            (varnish.replace("ground", "paint"), "materials.varnish.base"),
+            (
+                varnish.replace("ground", "varnish"),
+                "materials.varnish.base",
+            ),
+            (varnish.replace("0.1", "1.1"), "materials.varnish.roughness"),
+            (
+                format!("{}ref_idx = 0.0\n", varnish),
+                "materials.varnish.ref_idx",
+            ),
+        ];
+        for (src, field) in broken.iter() {
+            match parse(&format!("{}{}", SCENE, src)) {
+                Err(SceneError::Invalid { line, field: f, .. }) => {
+                    assert_eq!(line, 39);
+                    assert_eq!(f, *field);
+                }
+                _ => panic!("expected an invalid {} error", field),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_material_type() {
+        let src = SCENE.replace("type = \"metal\"", "type = \"chrome\"");
+        match parse(&src) {
+            Err(SceneError::Parse { message, .. }) => {
+                assert!(message.contains("chrome"), "{}", message);
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn missing_mesh() {
+        let src = format!(
+            "{}\n[[meshes]]\nfile = \"no_such_file.obj\"\nmaterial = \"mirror\"\n",
+            SCENE
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 40);
+                assert_eq!(field, "meshes[0].file");
+            }
+            _ => panic!("expected an invalid file error"),
+        }
+    }
+
+    #[test]
+    fn textures() {
+        let src = format!(
+            "{}\n{}",
+            SCENE,
+            r#"
+[textures.fine]
+type = "uv_checker"
+width = 8.0
+height = 4.0
+odd = [0.0, 0.0, 0.0]
+even = [1.0, 1.0, 1.0]
+
+[textures.floor]
+type = "checker"
+scale = 2.0
+odd = "fine"
+even = [0.9, 0.9, 0.9]
+
+[materials.tiles]
+type = "lambertian"
+albedo = "floor"
+
+[textures.stone]
+type = "marble"
+seed = 3
+scale = 4.0
+
+[textures.oak]
+type = "wood"
+light = [0.8, 0.6, 0.4]
+dark = [0.4, 0.2, 0.1]
+
+[materials.bench]
+type = "lambertian"
+albedo = "oak"
+"#
+        );
+        let scene = parse(&src).unwrap();
+        assert_eq!(scene.world.len(), 3);
+    }
+
+    #[test]
+    fn unknown_texture() {
+        let src = format!(
+            "{}\n[materials.tiles]\ntype = \"metal\"\nalbedo = \"floor\"\n",
+            SCENE
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 39);
+                assert_eq!(field, "materials.tiles.albedo");
+            }
+            _ => panic!("expected an unknown texture error"),
+        }
+    }
+
+    #[test]
+    fn cyclic_texture() {
+        let src = format!(
+            "{}\n{}",
+            SCENE,
+            r#"
+[textures.a]
+type = "checker"
+odd = "b"
+even = [1.0, 1.0, 1.0]
+
+[textures.b]
+type = "uv_checker"
+width = 1.0
+height = 1.0
+odd = [1.0, 1.0, 1.0]
+even = "a"
+"#
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid {
+                line,
+                field,
+                message,
+            }) => {
+                assert_eq!(line, 45);
+                assert_eq!(field, "textures.b.even");
+                assert!(message.contains("`a`"), "{}", message);
+            }
+            _ => panic!("expected a cyclic texture error"),
+        }
+    }
+
+    #[test]
+    fn mesh_transforms() {
+        let dir = std::env::temp_dir().join(format!("rrt_scene_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("t.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let mesh = |extra: &str| {
+            format!(
+                "\n[[meshes]]\nfile = \"t.obj\"\nmaterial = \"mirror\"\n{}\n",
+                extra
+            )
+        };
+        let src = format!(
+            "{}{}{}",
+            SCENE,
+            mesh("scale = [2.0, 1.0, 1.0]\nrotate = [0.0, 90.0, 0.0]"),
+            mesh("translate = [0.0, 5.0, 0.0]")
+        );
+        let ok = parse_scene(&src, &dir).map(|scene| scene.world.len());
+        let src = format!("{}{}", SCENE, mesh("scale = [1.0, 0.0, 1.0]"));
+        let zero = parse_scene(&src, &dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ok.unwrap(), 5);
+        match zero {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 42);
+                assert_eq!(field, "meshes[0].scale");
+            }
+            _ => panic!("expected an invalid scale error"),
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let shapes = r#"
+[[planes]]
+point = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+material = "ground"
+
+[[quads]]
+corner = [0.0, 0.0, 0.0]
+u = [1.0, 0.0, 0.0]
+v = [0.0, 1.0, 0.0]
+material = "mirror"
+
+[[boxes]]
+min = [0.0, 0.0, 0.0]
+max = [1.0, 2.0, 3.0]
+material = "glass"
+
+[[disks]]
+center = [0.0, 3.0, 0.0]
+normal = [0.0, -1.0, 0.0]
+radius = 1.0
+material = "mirror"
+
+[[cylinders]]
+base = [2.0, 0.0, 0.0]
+top = [2.0, 1.0, 0.0]
+radius = 0.5
+material = "glass"
+
+[[cones]]
+base = [-2.0, 0.0, 0.0]
+apex = [-2.0, 1.0, 0.0]
+radius = 0.5
+material = "ground"
+
+[[tori]]
+center = [0.0, 0.5, 2.0]
+axis = [0.0, 1.0, 0.0]
+major_radius = 1.0
+minor_radius = 0.25
+material = "mirror"
+"#;
+        let scene = parse(&format!("{}{}", SCENE, shapes)).unwrap();
+        assert_eq!(scene.world.len(), 10);
+
+        let src = format!(
+            "{}{}",
+            SCENE,
+            shapes.replace("normal = [0.0, 1.0, 0.0]", "normal = [0.0, 0.0, 0.0]")
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 41);
+                assert_eq!(field, "planes[0].normal");
+            }
+            _ => panic!("expected an invalid normal error"),
+        }
+        let src = format!(
+            "{}{}",
+            SCENE,
+            shapes.replace("v = [0.0, 1.0, 0.0]", "v = [2.0, 0.0, 0.0]")
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 46);
+                assert_eq!(field, "quads[0].u");
+            }
+            _ => panic!("expected a degenerate quad error"),
+        }
+        let src = format!(
+            "{}{}",
+            SCENE,
+            shapes.replace("minor_radius = 0.25", "minor_radius = 1.5")
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 77);
+                assert_eq!(field, "tori[0].minor_radius");
+            }
+            _ => panic!("expected an invalid radius error"),
+        }
+    }
+
+    #[test]
+    fn media() {
+        let fog = r#"
+[materials.fog]
+type = "isotropic"
+albedo = [0.9, 0.9, 0.9]
+
+[[media]]
+boundary = { type = "sphere", center = [0.0, 1.0, 0.0], radius = 1.0 }
+density = 0.5
+material = "fog"
+
+[[media]]
+boundary = { type = "box", min = [-1.0, 0.0, -1.0], max = [1.0, 1.0, 1.0] }
+density = 2.0
+material = "fog"
+"#;
+        let scene = parse(&format!("{}{}", SCENE, fog)).unwrap();
+        assert_eq!(scene.world.len(), 5);
+
+        let src = format!("{}{}", SCENE, fog.replace("density = 2.0", "density = 0.0"));
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 50);
+                assert_eq!(field, "media[1].density");
+            }
+            _ => panic!("expected an invalid density error"),
+        }
+        let src = format!(
+            "{}{}",
+            SCENE,
+            fog.replace("material = \"fog\"", "material = \"glass\"")
+        );
+        match parse(&src) {
+            Err(SceneError::Invalid { field, .. }) => {
+                assert_eq!(field, "media[0].material");
+            }
+            _ => panic!("expected an invalid material error"),
+        }
+    }
+
+    #[test]
+    fn grids() {
+        let dir = std::env::temp_dir().join(format!("rrt_grid_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("smoke.raw"), [0u8, 64, 128, 255]).unwrap();
+        let smoke = r#"
+[materials.smoke]
+type = "henyey_greenstein"
+albedo = [0.8, 0.8, 0.8]
+g = 0.6
+
+[[grids]]
+file = "smoke.raw"
+resolution = [2, 2, 1]
+min = [-1.0, 0.0, -1.0]
+max = [1.0, 2.0, 1.0]
+density = 4.0
+material = "smoke"
+"#;
+        let parse = |grid: &str| parse_scene(&format!("{}{}", SCENE, grid), &dir);
+        let ok = parse(smoke).map(|scene| scene.world.len());
+        let size = parse(&smoke.replace("[2, 2, 1]", "[2, 2, 2]"));
+        let g = parse(&smoke.replace("g = 0.6", "g = 1.0"));
+        let bounds = parse(&smoke.replace("max = [1.0, 2.0, 1.0]", "max = [1.0, 0.0, 1.0]"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ok.unwrap(), 4);
+        match size {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 45);
+                assert_eq!(field, "grids[0].file");
+            }
+            _ => panic!("expected an invalid file error"),
+        }
+        match g {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 39);
+                assert_eq!(field, "materials.smoke.g");
+            }
+            _ => panic!("expected an invalid g error"),
+        }
+        match bounds {
+            Err(SceneError::Invalid { field, .. }) => {
+                assert_eq!(field, "grids[0].max");
+            }
+            _ => panic!("expected an invalid max error"),
+        }
+    }
+
+    #[test]
+    fn motion() {
+        let moving = "\n[[spheres]]\ncenter = [5.0, 1.0, 0.0]\nradius = 1.0\nmaterial = \"glass\"\nmotion = [0.0, 0.0, 3.0]\n";
+        let scene = parse(&format!("{}{}", SCENE, moving)).unwrap();
+        assert_eq!(scene.world.len(), 4);
+        let r =
+            |time: f32| Ray::with_time(Vec3::new(5.0, 1.0, 10.0), Vec3::new(0.0, 0.0, -1.0), time);
+        let hit = |time: f32| scene.world.hit(&r(time), 0.001, f32::MAX).unwrap().t;
+        assert_float_eq!(hit(0.0), 9.0, abs <= 1e-4);
+        assert_float_eq!(hit(1.0), 6.0, abs <= 1e-4);
+
+        let src = SCENE.replace("aperture = 0.1", "aperture = 0.1\nshutter = [1.0, 0.5]");
+        match parse(&src) {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 12);
+                assert_eq!(field, "camera.shutter");
+            }
+            _ => panic!("expected an invalid shutter error"),
+        }
+    }
+
+    #[test]
+    fn heightfields() {
+        let dir = std::env::temp_dir().join(format!("rrt_heightfield_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut img = image::GrayImage::new(4, 3);
+        img.put_pixel(1, 1, image::Luma([255]));
+        img.save(dir.join("hills.png")).unwrap();
+        let hills = r#"
+[[heightfields]]
+file = "hills.png"
+min = [-2.0, 0.0, 3.0]
+size = [4.0, 1.0, 2.0]
+material = "ground"
+"#;
+        let parse = |s: &str| parse_scene(&format!("{}{}", SCENE, s), &dir);
+        let ok = parse(hills);
+        let flat = parse(&hills.replace("[4.0, 1.0, 2.0]", "[4.0, 1.0, 0.0]"));
+        let missing = parse(&hills.replace("hills.png", "valleys.png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let scene = ok.unwrap();
+        assert_eq!(scene.world.len(), 4);
+        // the peak is at the second sample along x and the middle row
+        let r = Ray::new(
+            Vec3::new(-2.0 + 4.0 / 3.0 + 0.01, 5.0, 4.01),
+            Vec3::new(0.0, -1.0, 0.0),
+        );
+        let rec = scene.world.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.p.y(), 0.99, abs <= 1e-4);
+        match flat {
+            Err(SceneError::Invalid { line, field, .. }) => {
+                assert_eq!(line, 42);
+                assert_eq!(field, "heightfields[0].size");
+            }
+            _ => panic!("expected an invalid size error"),
+        }
+        match missing {
+            Err(SceneError::Invalid { field, .. }) => {
+                assert_eq!(field, "heightfields[0].file");
+            }
+            _ => panic!("expected an invalid file error"),
+        }
+    }
+}
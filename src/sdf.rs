@@ -0,0 +1,377 @@
+use crate::aabb::Aabb;
+use crate::csg::CsgOp;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// expression tree of signed distance functions, negative inside
+#[derive(Clone, Debug)]
+pub enum SdfNode {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    // axis aligned, half_size from the center along each axis
+    Cuboid {
+        center: Vec3,
+        half_size: Vec3,
+    },
+    // around the y axis
+    Torus {
+        center: Vec3,
+        major: f32,
+        minor: f32,
+    },
+    // round ended cylinder around the segment from a to b
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    },
+    Combine(CsgOp, Box<SdfNode>, Box<SdfNode>),
+    // blended over a distance of about k
+    Smooth(CsgOp, Box<SdfNode>, Box<SdfNode>, f32),
+    // grown by radius, rounding the edges
+    Round(Box<SdfNode>, f32),
+    Translate(Box<SdfNode>, Vec3),
+}
+
+// polynomial smooth minimum
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+impl SdfNode {
+    pub fn sphere(center: Vec3, radius: f32) -> SdfNode {
+        SdfNode::Sphere { center, radius }
+    }
+
+    pub fn cuboid(center: Vec3, half_size: Vec3) -> SdfNode {
+        SdfNode::Cuboid { center, half_size }
+    }
+
+    pub fn torus(center: Vec3, major: f32, minor: f32) -> SdfNode {
+        SdfNode::Torus {
+            center,
+            major,
+            minor,
+        }
+    }
+
+    pub fn capsule(a: Vec3, b: Vec3, radius: f32) -> SdfNode {
+        SdfNode::Capsule { a, b, radius }
+    }
+
+    pub fn union(self, other: SdfNode) -> SdfNode {
+        SdfNode::Combine(CsgOp::Union, Box::new(self), Box::new(other))
+    }
+
+    pub fn intersection(self, other: SdfNode) -> SdfNode {
+        SdfNode::Combine(CsgOp::Intersection, Box::new(self), Box::new(other))
+    }
+
+    pub fn difference(self, other: SdfNode) -> SdfNode {
+        SdfNode::Combine(CsgOp::Difference, Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: SdfNode, k: f32) -> SdfNode {
+        SdfNode::Smooth(CsgOp::Union, Box::new(self), Box::new(other), k)
+    }
+
+    pub fn smooth_intersection(self, other: SdfNode, k: f32) -> SdfNode {
+        SdfNode::Smooth(CsgOp::Intersection, Box::new(self), Box::new(other), k)
+    }
+
+    pub fn smooth_difference(self, other: SdfNode, k: f32) -> SdfNode {
+        SdfNode::Smooth(CsgOp::Difference, Box::new(self), Box::new(other), k)
+    }
+
+    pub fn round(self, radius: f32) -> SdfNode {
+        SdfNode::Round(Box::new(self), radius)
+    }
+
+    pub fn translate(self, offset: Vec3) -> SdfNode {
+        SdfNode::Translate(Box::new(self), offset)
+    }
+
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            SdfNode::Sphere { center, radius } => (p - *center).length() - radius,
+            SdfNode::Cuboid { center, half_size } => {
+                let d = p - *center;
+                let q = Vec3::new(d.x().abs(), d.y().abs(), d.z().abs()) - *half_size;
+                let outside = q.max(Vec3::zero()).length();
+                let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+                outside + inside
+            }
+            SdfNode::Torus {
+                center,
+                major,
+                minor,
+            } => {
+                let d = p - *center;
+                let x = (d.x() * d.x() + d.z() * d.z()).sqrt() - major;
+                (x * x + d.y() * d.y()).sqrt() - minor
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+                (pa - h * ba).length() - radius
+            }
+            SdfNode::Combine(op, a, b) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                match op {
+                    CsgOp::Union => a.min(b),
+                    CsgOp::Intersection => a.max(b),
+                    CsgOp::Difference => a.max(-b),
+                }
+            }
+            SdfNode::Smooth(op, a, b, k) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                match op {
+                    CsgOp::Union => smooth_min(a, b, *k),
+                    CsgOp::Intersection => -smooth_min(-a, -b, *k),
+                    CsgOp::Difference => -smooth_min(-a, b, *k),
+                }
+            }
+            SdfNode::Round(a, radius) => a.distance(p) - radius,
+            SdfNode::Translate(a, offset) => a.distance(p - *offset),
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        let cube = |c: Vec3, r: f32| Aabb::new(c - Vec3::new(r, r, r), c + Vec3::new(r, r, r));
+        let grown = |b: Aabb, r: f32| {
+            let r = Vec3::new(r, r, r);
+            Aabb::new(b.min - r, b.max + r)
+        };
+        match self {
+            SdfNode::Sphere { center, radius } => cube(*center, *radius),
+            SdfNode::Cuboid { center, half_size } => {
+                Aabb::new(*center - *half_size, *center + *half_size)
+            }
+            SdfNode::Torus {
+                center,
+                major,
+                minor,
+            } => {
+                let r = Vec3::new(major + minor, *minor, major + minor);
+                Aabb::new(*center - r, *center + r)
+            }
+            SdfNode::Capsule { a, b, radius } => cube(*a, *radius).surrounding(&cube(*b, *radius)),
+            // smooth intersections and differences only ever shrink the
+            // shape, while a smooth union bulges by at most k / 4
+            SdfNode::Combine(op, a, b) | SdfNode::Smooth(op, a, b, _) => {
+                let (a, b) = (a.bounding_box(), b.bounding_box());
+                let bbox = match op {
+                    CsgOp::Union => a.surrounding(&b),
+                    CsgOp::Intersection => Aabb {
+                        min: a.min.max(b.min),
+                        max: a.max.min(b.max),
+                    },
+                    CsgOp::Difference => a,
+                };
+                match self {
+                    SdfNode::Smooth(CsgOp::Union, _, _, k) => grown(bbox, k / 4.0),
+                    _ => bbox,
+                }
+            }
+            SdfNode::Round(a, radius) => grown(a.bounding_box(), *radius),
+            SdfNode::Translate(a, offset) => {
+                let b = a.bounding_box();
+                Aabb::new(b.min + *offset, b.max + *offset)
+            }
+        }
+    }
+}
+
+// closed surface where a signed distance function is 0, intersected by sphere
+// tracing inside a bounding box; the function must not overestimate the
+// distance to the surface. Normals are outward, as for the other solids
+pub struct Sdf {
+    distance: Box<dyn Fn(Vec3) -> f32 + Send + Sync>,
+    bbox: Aabb,
+    material: MaterialKind,
+    epsilon: f32,
+    max_steps: u32,
+}
+
+impl Sdf {
+    pub fn new(node: SdfNode, mat: MaterialKind) -> Sdf {
+        let bbox = node.bounding_box();
+        Sdf::from_fn(move |p| node.distance(p), bbox, mat)
+    }
+
+    // f must be negative inside and the surface must lie within bbox
+    pub fn from_fn<F>(f: F, bbox: Aabb, mat: MaterialKind) -> Sdf
+    where
+        F: Fn(Vec3) -> f32 + Send + Sync + 'static,
+    {
+        Sdf {
+            distance: Box::new(f),
+            bbox,
+            material: mat,
+            epsilon: 1e-4,
+            max_steps: 256,
+        }
+    }
+
+    // how close to the surface counts as a hit, also the step for normals
+    pub fn epsilon(self, epsilon: f32) -> Sdf {
+        Sdf { epsilon, ..self }
+    }
+
+    // rays still marching after this many steps miss
+    pub fn max_steps(self, max_steps: u32) -> Sdf {
+        Sdf { max_steps, ..self }
+    }
+
+    // gradient by central differences
+    fn normal(&self, p: Vec3, r: &Ray) -> Vec3 {
+        let f = &self.distance;
+        let h = self.epsilon;
+        let dx = Vec3::new(h, 0.0, 0.0);
+        let dy = Vec3::new(0.0, h, 0.0);
+        let dz = Vec3::new(0.0, 0.0, h);
+        let n = Vec3::new(
+            f(p + dx) - f(p - dx),
+            f(p + dy) - f(p - dy),
+            f(p + dz) - f(p - dz),
+        );
+        if n.squared_length() > 0.0 {
+            n.unit_vector()
+        } else {
+            -r.direction().unit_vector()
+        }
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let e = Vec3::new(self.epsilon, self.epsilon, self.epsilon);
+        let bbox = Aabb::new(self.bbox.min - e, self.bbox.max + e);
+        let (mut t, t_end) = bbox.clip(r, t_min, t_max)?;
+        let speed = r.direction().length();
+        // a ray starting on the surface, as scattered rays do, first has to
+        // get away from it before it can hit it again
+        let mut leaving = t <= t_min;
+        for _ in 0..self.max_steps {
+            let d = (self.distance)(r.point_at_parameter(t)).abs();
+            if d < self.epsilon {
+                if !leaving {
+                    let mut rec = HitRecord::new(&self.material);
+                    rec.t = t;
+                    rec.p = r.point_at_parameter(t);
+                    rec.normal = self.normal(rec.p, r);
+                    return Some(rec);
+                }
+                t += self.epsilon / speed;
+            } else {
+                leaving = false;
+                t += d / speed;
+            }
+            if t > t_end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Lambertian;
+    use crate::sdf::*;
+    use crate::sphere::Sphere;
+    use float_eq::assert_float_eq;
+
+    fn mat() -> MaterialKind {
+        MaterialKind::Lambertian(Lambertian::new(Vec3::one()))
+    }
+
+    #[test]
+    fn distances() {
+        let c = SdfNode::cuboid(Vec3::zero(), Vec3::new(1.0, 2.0, 3.0));
+        assert_float_eq!(c.distance(Vec3::new(3.0, 0.0, 0.0)), 2.0, abs <= 1e-6);
+        assert_float_eq!(c.distance(Vec3::new(0.5, 0.0, 0.0)), -0.5, abs <= 1e-6);
+        assert_float_eq!(c.distance(Vec3::new(4.0, 6.0, 0.0)), 5.0, abs <= 1e-6);
+
+        let t = SdfNode::torus(Vec3::zero(), 2.0, 0.5);
+        assert_float_eq!(t.distance(Vec3::new(0.0, 0.0, 2.0)), -0.5, abs <= 1e-6);
+        assert_float_eq!(t.distance(Vec3::new(0.0, 1.5, -2.0)), 1.0, abs <= 1e-6);
+
+        let a = SdfNode::sphere(Vec3::new(-1.0, 0.0, 0.0), 1.2);
+        let b = SdfNode::sphere(Vec3::new(1.0, 0.0, 0.0), 1.2);
+        let u = a.clone().union(b.clone());
+        let s = a.clone().smooth_union(b.clone(), 0.5);
+        // the blend fills in the crease between the spheres
+        let crease = Vec3::new(0.0, 0.8, 0.0);
+        assert!(s.distance(crease) < u.distance(crease));
+        let far = Vec3::new(-3.0, 0.0, 0.0);
+        assert_float_eq!(s.distance(far), u.distance(far), abs <= 1e-6);
+        let d = a.difference(b);
+        assert_float_eq!(d.distance(Vec3::new(-0.5, 0.0, 0.0)), -0.3, abs <= 1e-6);
+        assert_float_eq!(d.distance(Vec3::new(0.0, 0.0, 0.0)), 0.2, abs <= 1e-6);
+        assert!(s.bounding_box().max.x() >= 2.2 + 0.125);
+    }
+
+    #[test]
+    fn matches_sphere() {
+        let sdf = Sdf::new(SdfNode::sphere(Vec3::new(0.0, 0.0, -3.0), 1.0), mat());
+        let sphere = Sphere::new(Vec3::new(0.0, 0.0, -3.0), 1.0, mat());
+        for &(x, y) in [(0.0, 0.0), (0.5, 0.3), (-0.7, 0.69), (0.2, -0.9)].iter() {
+            let r = Ray::new(Vec3::new(x, y, 2.0), Vec3::new(0.0, 0.0, -2.0));
+            let a = sdf.hit(&r, 0.001, f32::MAX).unwrap();
+            let b = sphere.hit(&r, 0.001, f32::MAX).unwrap();
+            // stops short by up to epsilon from the surface, more along
+            // grazing rays
+            assert_float_eq!(a.t, b.t, abs <= 1e-3);
+            assert!((a.normal - b.normal).length() < 1e-2);
+        }
+        let r = Ray::new(Vec3::new(1.1, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(sdf.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn from_surface() {
+        let sdf = Sdf::new(SdfNode::sphere(Vec3::zero(), 1.0), mat());
+        // leaving outward misses, going inward finds the far side with an
+        // outward normal
+        let p = Vec3::new(0.0, 0.0, 1.0);
+        let r = Ray::new(p, Vec3::new(0.3, 0.0, 1.0));
+        assert!(sdf.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(p, Vec3::new(0.0, 0.0, -1.0));
+        let rec = sdf.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 2.0, abs <= 1e-3);
+        assert_float_eq!(rec.normal.z(), -1.0, abs <= 1e-3);
+    }
+
+    #[test]
+    fn closure_and_limits() {
+        let bbox = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::one());
+        // octahedron, whose distance bound makes rays crawl near its edges
+        let octahedron = |p: Vec3| (p.x().abs() + p.y().abs() + p.z().abs() - 1.0) * 0.577;
+        let sdf = Sdf::from_fn(octahedron, bbox, mat());
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = sdf.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.0, abs <= 1e-3);
+        assert!(sdf.hit(&r, 0.001, 3.5).is_none());
+
+        let coarse = Sdf::from_fn(octahedron, bbox, mat()).epsilon(0.05);
+        let rec = coarse.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.t < 4.0 && rec.t > 3.9);
+        // converges slowly onto a face the ray meets at an angle
+        let r = Ray::new(Vec3::new(0.3, 0.3, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = sdf.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.6, abs <= 1e-3);
+        let short = Sdf::from_fn(octahedron, bbox, mat()).max_steps(8);
+        assert!(short.hit(&r, 0.001, f32::MAX).is_none());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// helpers shared by the analytic primitives, which are intersected in a
+// local frame with their axis along z
+
+// right handed orthonormal frame; being orthonormal it leaves ray parameters alone
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Frame {
+    pub origin: Vec3,
+    pub x: Vec3,
+    pub y: Vec3,
+    pub z: Vec3,
+}
+
+impl Frame {
+    pub fn new(origin: Vec3, axis: Vec3) -> Frame {
+        let z = axis.unit_vector();
+        let (x, y) = z.orthonormal_basis();
+        Frame { origin, x, y, z }
+    }
+
+    pub fn local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.x), v.dot(self.y), v.dot(self.z))
+    }
+
+    pub fn local_ray(&self, r: &Ray) -> Ray {
+        Ray::with_time(
+            self.local(r.origin() - self.origin),
+            self.local(r.direction()),
+            r.time(),
+        )
+    }
+
+    pub fn world(&self, v: Vec3) -> Vec3 {
+        v.x() * self.x + v.y() * self.y + v.z() * self.z
+    }
+}
+
+// angle around the local z axis, in [0, 1)
+pub(crate) fn azimuth(p: Vec3) -> f32 {
+    let phi = p.y().atan2(p.x()) / (2.0 * std::f32::consts::PI);
+    if phi < 0.0 {
+        phi + 1.0
+    } else {
+        phi
+    }
+}
+
+// box around a disk of radius around center facing along the unit normal
+pub(crate) fn disk_bbox(center: Vec3, normal: Vec3, radius: f32) -> Aabb {
+    let extent = |n: f32| radius * (1.0 - n * n).max(0.0).sqrt();
+    let e = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+    Aabb::new(center - e, center + e)
+}
+
+// real roots of a t^2 + 2 half_b t + c in ascending order, computed
+// without cancellation; None if there are none or a is 0
+pub(crate) fn solve_quadratic(a: f32, half_b: f32, c: f32) -> Option<(f32, f32)> {
+    let discriminant = half_b * half_b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let q = -(half_b + half_b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shape::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn frame() {
+        let f = Frame::new(Vec3::new(1.0, 2.0, 3.0), Vec3::new(0.0, 2.0, 0.0));
+        let r = Ray::new(Vec3::new(1.0, 5.0, 3.0), Vec3::new(0.0, -2.0, 0.0));
+        let l = f.local_ray(&r);
+        assert_float_eq!(l.origin().z(), 3.0, abs <= 1e-6);
+        assert_float_eq!(l.direction().z(), -2.0, abs <= 1e-6);
+        assert_float_eq!(l.origin().x() * l.origin().x(), 0.0, abs <= 1e-6);
+        let v = Vec3::new(0.3, -0.4, 0.5);
+        assert!((f.world(f.local(v)) - v).length() < 1e-6);
+    }
+
+    #[test]
+    fn quadratic() {
+        let (t0, t1) = solve_quadratic(1.0, -3.0, 8.0).unwrap();
+        assert_float_eq!(t0, 2.0, abs <= 1e-6);
+        assert_float_eq!(t1, 4.0, abs <= 1e-6);
+        // large and small roots both accurate
+        let (t0, t1) = solve_quadratic(1.0, -5e3, 1.0).unwrap();
+        assert_float_eq!(t0, 1e-4, r2nd <= 1e-4);
+        assert_float_eq!(t1, 1e4, r2nd <= 1e-4);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+        assert!(solve_quadratic(0.0, 1.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn bbox() {
+        let b = disk_bbox(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), 2.0);
+        assert_float_eq!(b.min.x(), -2.0, abs <= 1e-6);
+        assert_float_eq!(b.max.y(), 0.0, abs <= 1e-6);
+        let n = Vec3::new(1.0, 1.0, 0.0).unit_vector();
+        let b = disk_bbox(Vec3::zero(), n, 1.0);
+        assert_float_eq!(b.max.x(), 0.5f32.sqrt(), abs <= 1e-6);
+        assert_float_eq!(b.max.z(), 1.0, abs <= 1e-6);
+    }
+}
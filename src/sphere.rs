@@ -1,8 +1,12 @@
+use crate::aabb::Aabb;
 use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
 use crate::material::MaterialKind;
-use crate::ray::Ray;
+use crate::random::random_unit_vector;
+use crate::ray::{keyframe_weight, Ray};
 use crate::vec3::Vec3;
 
+#[derive(Clone, Debug)]
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f32,
@@ -17,34 +21,207 @@ impl Sphere {
             material: mat,
         }
     }
-}
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.origin() - self.center;
+    // where the ray enters and leaves, if it passes through, with the sphere
+    // centered at center
+    fn roots(&self, center: Vec3, r: &Ray) -> Option<(f32, f32)> {
+        let oc = r.origin() - center;
         let a = r.direction().dot(r.direction());
         let b = oc.dot(r.direction());
         let c = oc.dot(oc) - self.radius * self.radius;
         let discriminat = b * b - a * c;
         if discriminat > 0.0 {
-            let temp = (-b - (b * b - a * c).sqrt()) / a;
-            if t_min < temp && temp < t_max {
-                let mut rec = HitRecord::new(self.material.clone());
-                rec.t = temp;
-                rec.p = r.point_at_parameter(rec.t);
-                rec.normal = (rec.p - self.center) / self.radius;
-                return Some(rec);
-            }
-
-            let temp = (-b + (b * b - a * c).sqrt()) / a;
-            if t_min < temp && temp < t_max {
-                let mut rec = HitRecord::new(self.material.clone());
-                rec.t = temp;
-                rec.p = r.point_at_parameter(rec.t);
-                rec.normal = (rec.p - self.center) / self.radius;
-                return Some(rec);
-            }
+            let s = discriminat.sqrt();
+            Some(((-b - s) / a, (-b + s) / a))
+        } else {
+            None
+        }
+    }
+
+    fn record(&self, center: Vec3, r: &Ray, t: f32) -> HitRecord<'_> {
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(rec.t);
+        rec.normal = (rec.p - center) / self.radius;
+        let (u, v) = sphere_uv((rec.p - center) / self.radius.abs());
+        rec.u = u;
+        rec.v = v;
+        rec
+    }
+}
+
+// (u, v) of a point on the unit sphere, u around the y axis starting at -x
+// and v from the bottom to the top
+fn sphere_uv(p: Vec3) -> (f32, f32) {
+    let theta = (-p.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z()).atan2(p.x()) + std::f32::consts::PI;
+    (
+        phi / (2.0 * std::f32::consts::PI),
+        theta / std::f32::consts::PI,
+    )
+}
+
+impl Sphere {
+    fn hit_at(&self, center: Vec3, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (near, far) = self.roots(center, r)?;
+        if t_min < near && near < t_max {
+            return Some(self.record(center, r, near));
+        }
+        if t_min < far && far < t_max {
+            return Some(self.record(center, r, far));
         }
         None
     }
+
+    fn hits_at(&self, center: Vec3, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        match self.roots(center, r) {
+            Some((near, far)) => [near, far]
+                .iter()
+                .filter(|&&t| t_min < t && t < t_max)
+                .map(|&t| self.record(center, r, t))
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.hit_at(self.center, r, t_min, t_max)
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        self.hits_at(self.center, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        match self.material {
+            MaterialKind::DiffuseLight(_) => vec![Box::new(self.clone())],
+            _ => vec![],
+        }
+    }
+}
+
+impl Light for Sphere {
+    fn sample(&self) -> HitRecord<'_> {
+        let n = random_unit_vector();
+        let mut rec = HitRecord::new(&self.material);
+        rec.p = self.center + self.radius * n;
+        rec.normal = n;
+        let (u, v) = sphere_uv(n);
+        rec.u = u;
+        rec.v = v;
+        rec
+    }
+
+    fn area(&self) -> f32 {
+        4.0 * std::f32::consts::PI * self.radius * self.radius
+    }
+}
+
+// sphere moving in a straight line from center0 at time0 to center1 at
+// time1, and resting at those outside; not sampled as a light
+#[derive(Clone, Debug)]
+pub struct MovingSphere {
+    sphere: Sphere,
+    center1: Vec3,
+    time0: f32,
+    time1: f32,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Vec3,
+        center1: Vec3,
+        time0: f32,
+        time1: f32,
+        r: f32,
+        mat: MaterialKind,
+    ) -> MovingSphere {
+        MovingSphere {
+            sphere: Sphere::new(center0, r, mat),
+            center1,
+            time0,
+            time1,
+        }
+    }
+
+    pub fn center(&self, time: f32) -> Vec3 {
+        let w = keyframe_weight(time, self.time0, self.time1);
+        self.sphere.center + w * (self.center1 - self.sphere.center)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.sphere.hit_at(self.center(r.time()), r, t_min, t_max)
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        self.sphere.hits_at(self.center(r.time()), r, t_min, t_max)
+    }
+
+    // around the whole path
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.sphere.radius, self.sphere.radius, self.sphere.radius);
+        let c0 = self.sphere.center;
+        let c1 = self.center1;
+        Some(Aabb::new(c0 - r, c0 + r).surrounding(&Aabb::new(c1 - r, c1 + r)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::{Lambertian, MaterialKind};
+    use crate::sphere::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn uv() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let s = Sphere::new(Vec3::new(0.0, 0.0, -3.0), 2.0, m);
+        let r = Ray::new(Vec3::zero(), Vec3::new(0.0, 0.0, -1.0));
+        let rec = s.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 1.0, abs <= 1e-6);
+        // +z faces the ray, a quarter turn from -x
+        assert_float_eq!(rec.u, 0.25, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-6);
+
+        let r = Ray::new(Vec3::new(0.0, 5.0, -3.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = s.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.v, 1.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn moving() {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        let s = MovingSphere::new(
+            Vec3::new(0.0, 0.0, -3.0),
+            Vec3::new(4.0, 0.0, -3.0),
+            1.0,
+            2.0,
+            1.0,
+            m,
+        );
+        let b = s.bounding_box().unwrap();
+        assert_float_eq!(b.min.x(), -1.0, abs <= 1e-6);
+        assert_float_eq!(b.max.x(), 5.0, abs <= 1e-6);
+
+        let r = |x: f32, time: f32| {
+            Ray::with_time(Vec3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), time)
+        };
+        assert!(s.hit(&r(0.0, 0.0), 0.001, f32::MAX).is_some());
+        assert!(s.hit(&r(0.0, 1.0), 0.001, f32::MAX).is_some());
+        assert!(s.hit(&r(0.0, 1.5), 0.001, f32::MAX).is_none());
+        let rec = s.hit(&r(2.0, 1.5), 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 2.0, abs <= 1e-6);
+        assert_float_eq!(rec.normal.z(), 1.0, abs <= 1e-6);
+        assert!(s.hit(&r(4.0, 3.0), 0.001, f32::MAX).is_some());
+        assert_eq!(s.hits(&r(4.0, 2.0), 0.001, f32::MAX).len(), 2);
+    }
 }
@@ -0,0 +1,337 @@
+use crate::perlin::Perlin;
+use crate::vec3::Vec3;
+use image::RgbImage;
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub enum TextureKind {
+    Solid(Vec3),
+    Checker(Checker),
+    UvChecker(UvChecker),
+    Image(ImageTexture),
+    Noise(Noise),
+    Turbulence(Turbulence),
+    Marble(Marble),
+    Wood(Wood),
+}
+
+impl TextureKind {
+    pub fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        match self {
+            TextureKind::Solid(c) => *c,
+            TextureKind::Checker(t) => t.value(u, v, p),
+            TextureKind::UvChecker(t) => t.value(u, v, p),
+            TextureKind::Image(t) => t.value(u, v, p),
+            TextureKind::Noise(t) => t.value(u, v, p),
+            TextureKind::Turbulence(t) => t.value(u, v, p),
+            TextureKind::Marble(t) => t.value(u, v, p),
+            TextureKind::Wood(t) => t.value(u, v, p),
+        }
+    }
+}
+
+impl From<Vec3> for TextureKind {
+    fn from(c: Vec3) -> TextureKind {
+        TextureKind::Solid(c)
+    }
+}
+
+// gray, for textures standing for a single value
+impl From<f32> for TextureKind {
+    fn from(x: f32) -> TextureKind {
+        TextureKind::Solid(Vec3::new(x, x, x))
+    }
+}
+
+// 3D checker pattern in world space
+#[derive(Clone, Debug)]
+pub struct Checker {
+    // cells per unit length
+    scale: f32,
+    odd: Box<TextureKind>,
+    even: Box<TextureKind>,
+}
+
+impl Checker {
+    pub fn new<T: Into<TextureKind>, U: Into<TextureKind>>(scale: f32, odd: T, even: U) -> Checker {
+        Checker {
+            scale,
+            odd: Box::new(odd.into()),
+            even: Box::new(even.into()),
+        }
+    }
+
+    pub fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        let q = self.scale * p;
+        let sum = q.x().floor() as i64 + q.y().floor() as i64 + q.z().floor() as i64;
+        if sum.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// checker pattern in surface (u, v) space
+#[derive(Clone, Debug)]
+pub struct UvChecker {
+    // cells along u and v
+    width: f32,
+    height: f32,
+    odd: Box<TextureKind>,
+    even: Box<TextureKind>,
+}
+
+impl UvChecker {
+    pub fn new<T: Into<TextureKind>, U: Into<TextureKind>>(
+        width: f32,
+        height: f32,
+        odd: T,
+        even: U,
+    ) -> UvChecker {
+        UvChecker {
+            width,
+            height,
+            odd: Box::new(odd.into()),
+            even: Box::new(even.into()),
+        }
+    }
+
+    pub fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
+        let sum = (u * self.width).floor() as i64 + (v * self.height).floor() as i64;
+        if sum.rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// image mapped onto (u, v) in [0, 1], repeated outside of it
+#[derive(Clone, Debug)]
+pub struct ImageTexture {
+    image: Arc<RgbImage>,
+}
+
+impl ImageTexture {
+    pub fn new(image: RgbImage) -> ImageTexture {
+        ImageTexture {
+            image: Arc::new(image),
+        }
+    }
+
+    pub fn load(image_file_path: &str) -> Result<ImageTexture, image::ImageError> {
+        Ok(ImageTexture::new(image::open(image_file_path)?.to_rgb8()))
+    }
+
+    pub fn value(&self, u: f32, v: f32, _p: Vec3) -> Vec3 {
+        let (w, h) = self.image.dimensions();
+        if w == 0 || h == 0 {
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+        let u = u - u.floor();
+        // image rows go from top to bottom
+        let v = 1.0 - (v - v.floor());
+        let i = ((u * w as f32) as u32).min(w - 1);
+        let j = ((v * h as f32) as u32).min(h - 1);
+        let pixel = self.image.get_pixel(i, j);
+        // inverse of the gamma 2 applied when the image is written
+        let c = |x: u8| {
+            let x = x as f32 / 255.0;
+            x * x
+        };
+        Vec3::new(c(pixel[0]), c(pixel[1]), c(pixel[2]))
+    }
+}
+
+// octaves of turbulence used by marble and wood
+const OCTAVES: u32 = 7;
+
+fn lerp(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    (1.0 - t) * a + t * b
+}
+
+// gray Perlin noise of the world space point
+#[derive(Clone, Debug)]
+pub struct Noise {
+    perlin: Arc<Perlin>,
+    // frequency per unit length
+    scale: f32,
+}
+
+impl Noise {
+    pub fn new(seed: u64, scale: f32) -> Noise {
+        Noise {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+        }
+    }
+
+    pub fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
+        let n = 0.5 * (1.0 + self.perlin.noise(self.scale * p));
+        Vec3::new(n, n, n)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Turbulence {
+    perlin: Arc<Perlin>,
+    scale: f32,
+    octaves: u32,
+}
+
+impl Turbulence {
+    pub fn new(seed: u64, scale: f32, octaves: u32) -> Turbulence {
+        Turbulence {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            octaves,
+        }
+    }
+
+    pub fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
+        let n = self
+            .perlin
+            .turbulence(self.scale * p, self.octaves)
+            .min(1.0);
+        Vec3::new(n, n, n)
+    }
+}
+
+// bands along z distorted by turbulence
+#[derive(Clone, Debug)]
+pub struct Marble {
+    perlin: Arc<Perlin>,
+    scale: f32,
+    base: Vec3,
+    vein: Vec3,
+}
+
+impl Marble {
+    pub fn new(seed: u64, scale: f32, base: Vec3, vein: Vec3) -> Marble {
+        Marble {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            base,
+            vein,
+        }
+    }
+
+    pub fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
+        let q = self.scale * p;
+        let t = 0.5 * (1.0 + (q.z() + 10.0 * self.perlin.turbulence(q, OCTAVES)).sin());
+        lerp(self.vein, self.base, t)
+    }
+}
+
+// rings around the y axis with noisy radii
+#[derive(Clone, Debug)]
+pub struct Wood {
+    perlin: Arc<Perlin>,
+    // rings per unit length
+    scale: f32,
+    light: Vec3,
+    dark: Vec3,
+}
+
+impl Wood {
+    pub fn new(seed: u64, scale: f32, light: Vec3, dark: Vec3) -> Wood {
+        Wood {
+            perlin: Arc::new(Perlin::new(seed)),
+            scale,
+            light,
+            dark,
+        }
+    }
+
+    pub fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
+        let q = self.scale * p;
+        let r = (q.x() * q.x() + q.z() * q.z()).sqrt() + 2.0 * self.perlin.noise(0.5 * q);
+        let ring = r - r.floor();
+        // sharper towards the dark edge of each ring
+        let t = ring.powi(3);
+        lerp(self.light, self.dark, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::texture::*;
+
+    #[test]
+    fn checker() {
+        let t = Checker::new(1.0, Vec3::zero(), Vec3::one());
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(0.5, 0.5, 0.5)).x(), 1.0);
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(1.5, 0.5, 0.5)).x(), 0.0);
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(-0.5, 0.5, 0.5)).x(), 0.0);
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(-0.5, -0.5, 0.5)).x(), 1.0);
+
+        let t = Checker::new(2.0, Vec3::zero(), Vec3::one());
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(0.75, 0.25, 0.25)).x(), 0.0);
+    }
+
+    #[test]
+    fn nested_checker() {
+        let inner = TextureKind::Checker(Checker::new(10.0, Vec3::zero(), Vec3::one()));
+        let t = Checker::new(1.0, inner, Vec3::new(0.5, 0.5, 0.5));
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(0.5, 0.5, 0.5)).x(), 0.5);
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(1.05, 0.05, 0.05)).x(), 1.0);
+        assert_eq!(t.value(0.0, 0.0, Vec3::new(1.15, 0.05, 0.05)).x(), 0.0);
+    }
+
+    #[test]
+    fn uv_checker() {
+        let t = UvChecker::new(4.0, 2.0, Vec3::zero(), Vec3::one());
+        assert_eq!(t.value(0.1, 0.1, Vec3::zero()).x(), 1.0);
+        assert_eq!(t.value(0.3, 0.1, Vec3::zero()).x(), 0.0);
+        assert_eq!(t.value(0.3, 0.6, Vec3::zero()).x(), 1.0);
+    }
+
+    #[test]
+    fn image() {
+        let mut img = RgbImage::new(2, 2);
+        img.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        img.put_pixel(1, 0, image::Rgb([0, 255, 0]));
+        img.put_pixel(0, 1, image::Rgb([0, 0, 255]));
+        img.put_pixel(1, 1, image::Rgb([255, 255, 255]));
+        let t = ImageTexture::new(img);
+
+        // v = 1 is the top row
+        let c = t.value(0.25, 0.75, Vec3::zero());
+        assert_eq!((c.x(), c.y(), c.z()), (1.0, 0.0, 0.0));
+        let c = t.value(0.75, 0.75, Vec3::zero());
+        assert_eq!((c.x(), c.y(), c.z()), (0.0, 1.0, 0.0));
+        let c = t.value(0.25, 0.25, Vec3::zero());
+        assert_eq!((c.x(), c.y(), c.z()), (0.0, 0.0, 1.0));
+        // repeated
+        let c = t.value(1.25, -0.75, Vec3::zero());
+        assert_eq!((c.x(), c.y(), c.z()), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn noise_seeded() {
+        let p = Vec3::new(1.3, -0.2, 4.1);
+        let a = TextureKind::Marble(Marble::new(3, 2.0, Vec3::one(), Vec3::zero()));
+        let b = TextureKind::Marble(Marble::new(3, 2.0, Vec3::one(), Vec3::zero()));
+        let c = TextureKind::Marble(Marble::new(4, 2.0, Vec3::one(), Vec3::zero()));
+        assert_eq!(a.value(0.0, 0.0, p).x(), b.value(0.0, 0.0, p).x());
+        assert_ne!(a.value(0.0, 0.0, p).x(), c.value(0.0, 0.0, p).x());
+    }
+
+    #[test]
+    fn noise_range() {
+        let textures = [
+            TextureKind::Noise(Noise::new(0, 4.0)),
+            TextureKind::Turbulence(Turbulence::new(0, 4.0, 7)),
+            TextureKind::Marble(Marble::new(0, 4.0, Vec3::one(), Vec3::zero())),
+            TextureKind::Wood(Wood::new(0, 4.0, Vec3::one(), Vec3::zero())),
+        ];
+        for t in textures.iter() {
+            for i in 0..200 {
+                let p = Vec3::new(i as f32 * 0.31, i as f32 * -0.17, i as f32 * 0.07);
+                let c = t.value(0.0, 0.0, p);
+                assert!((0.0..=1.0).contains(&c.x()), "{:?} {}", t, c.x());
+            }
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use crate::aabb::Aabb;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::ray::Ray;
+use crate::shape::{azimuth, Frame};
+use crate::vec3::Vec3;
+
+// ring torus around axis, with its tube of radius minor at distance major
+// from center; u goes around the axis and v around the tube
+#[derive(Clone, Debug)]
+pub struct Torus {
+    frame: Frame,
+    major: f32,
+    minor: f32,
+    material: MaterialKind,
+}
+
+impl Torus {
+    // panics unless 0 < minor < major
+    pub fn new(center: Vec3, axis: Vec3, major: f32, minor: f32, mat: MaterialKind) -> Torus {
+        assert!(0.0 < minor && minor < major);
+        Torus {
+            frame: Frame::new(center, axis),
+            major,
+            minor,
+            material: mat,
+        }
+    }
+}
+
+impl Torus {
+    // parameters of the local ray l where it crosses the surface, unordered
+    fn roots(&self, l: &Ray) -> Vec<f32> {
+        // in double precision along the unit direction, starting from the
+        // point closest to the center, which keeps the quartic well conditioned
+        let len = l.direction().length() as f64;
+        let d = [
+            l.direction().x() as f64 / len,
+            l.direction().y() as f64 / len,
+            l.direction().z() as f64 / len,
+        ];
+        let o = [
+            l.origin().x() as f64,
+            l.origin().y() as f64,
+            l.origin().z() as f64,
+        ];
+        let t0 = -(o[0] * d[0] + o[1] * d[1] + o[2] * d[2]);
+        let o = [o[0] + t0 * d[0], o[1] + t0 * d[1], o[2] + t0 * d[2]];
+
+        let (big, small) = (self.major as f64, self.minor as f64);
+        let oo = o[0] * o[0] + o[1] * o[1] + o[2] * o[2];
+        if oo > (big + small) * (big + small) {
+            return vec![];
+        }
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) with p = o + s d, where o.d = 0
+        let k = oo + big * big - small * small;
+        let r2 = 4.0 * big * big;
+        let coefficients = [
+            0.0,
+            2.0 * k - r2 * (d[0] * d[0] + d[1] * d[1]),
+            -2.0 * r2 * (o[0] * d[0] + o[1] * d[1]),
+            k * k - r2 * (o[0] * o[0] + o[1] * o[1]),
+        ];
+        solve_quartic(coefficients)
+            .into_iter()
+            .map(|s| ((s + t0) / len) as f32)
+            .collect()
+    }
+
+    fn record(&self, r: &Ray, l: &Ray, t: f32) -> HitRecord<'_> {
+        let p = l.point_at_parameter(t);
+        let rho = (p.x() * p.x() + p.y() * p.y()).sqrt();
+        // away from the circle through the middle of the tube
+        let n = Vec3::new(
+            p.x() * (1.0 - self.major / rho),
+            p.y() * (1.0 - self.major / rho),
+            p.z(),
+        );
+
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        rec.normal = self.frame.world(n.unit_vector());
+        rec.u = azimuth(p);
+        let theta = p.z().atan2(rho - self.major) / (2.0 * std::f32::consts::PI);
+        rec.v = if theta < 0.0 { theta + 1.0 } else { theta };
+        rec
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let l = self.frame.local_ray(r);
+        let t = self
+            .roots(&l)
+            .into_iter()
+            .filter(|&t| t_min < t && t < t_max)
+            .fold(None, |m: Option<f32>, t| Some(m.map_or(t, |m| m.min(t))))?;
+        Some(self.record(r, &l, t))
+    }
+
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord<'_>> {
+        let l = self.frame.local_ray(r);
+        let mut roots: Vec<f32> = self
+            .roots(&l)
+            .into_iter()
+            .filter(|&t| t_min < t && t < t_max)
+            .collect();
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        roots.into_iter().map(|t| self.record(r, &l, t)).collect()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let a = self.frame.z;
+        let extent = |n: f32| self.major * (1.0 - n * n).max(0.0).sqrt() + self.minor;
+        let e = Vec3::new(extent(a.x()), extent(a.y()), extent(a.z()));
+        Some(Aabb::new(self.frame.origin - e, self.frame.origin + e))
+    }
+}
+
+// real roots of x^4 + c[0] x^3 + c[1] x^2 + c[2] x + c[3] (Ferrari's
+// method), counting tangent double roots once
+fn solve_quartic(c: [f64; 4]) -> Vec<f64> {
+    let [a, b, c1, d] = c;
+    // x = y - a / 4 gives y^4 + p y^2 + q y + r
+    let aa = a * a;
+    let p = b - 3.0 / 8.0 * aa;
+    let q = c1 - 0.5 * a * b + 0.125 * aa * a;
+    let r = d - 0.25 * a * c1 + aa * b / 16.0 - 3.0 * aa * aa / 256.0;
+    let scale = 1.0 + p.abs() + r.abs().sqrt();
+
+    let mut roots = vec![];
+    if q.abs() <= 1e-12 * scale * scale * scale {
+        // biquadratic
+        for z in solve_quadratic(p, r) {
+            if z >= 0.0 {
+                roots.push(z.sqrt());
+                roots.push(-z.sqrt());
+            }
+        }
+    } else {
+        // factor into (y^2 + s y + alpha) (y^2 - s y + beta) with z = s^2 a
+        // positive root of the resolvent cubic
+        let z = largest_cubic_root(2.0 * p, p * p - 4.0 * r, -q * q).max(0.0);
+        if z > 0.0 {
+            let s = z.sqrt();
+            let alpha = 0.5 * (p + z) - 0.5 * q / s;
+            let beta = 0.5 * (p + z) + 0.5 * q / s;
+            roots.extend(solve_quadratic(s, alpha));
+            roots.extend(solve_quadratic(-s, beta));
+        }
+    }
+
+    // polish against the original polynomial
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - 0.25 * a;
+            for _ in 0..2 {
+                let f = (((x + a) * x + b) * x + c1) * x + d;
+                let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c1;
+                if df != 0.0 {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+// real roots of x^2 + b x + c; a slightly negative discriminant from
+// rounding is taken as a double root so that grazing rays still hit
+fn solve_quadratic(b: f64, c: f64) -> Vec<f64> {
+    let discriminant = 0.25 * b * b - c;
+    let tolerance = 1e-10 * (0.25 * b * b + c.abs());
+    if discriminant < -tolerance {
+        vec![]
+    } else if discriminant <= 0.0 {
+        vec![-0.5 * b]
+    } else {
+        let q = -0.5 * b - discriminant.sqrt().copysign(b);
+        if q == 0.0 {
+            vec![0.0, 0.0]
+        } else {
+            vec![q, c / q]
+        }
+    }
+}
+
+// largest real root of x^3 + a x^2 + b x + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // x = w - a / 3 gives w^3 + p w + q
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = 0.25 * q * q + p * p * p / 27.0;
+    let w = if discriminant > 0.0 {
+        let s = discriminant.sqrt();
+        (-0.5 * q + s).cbrt() + (-0.5 * q - s).cbrt()
+    } else {
+        // three real roots, p <= 0
+        let m = 2.0 * (-p / 3.0).sqrt();
+        if m == 0.0 {
+            0.0
+        } else {
+            let cos = (3.0 * q / (p * m)).clamp(-1.0, 1.0);
+            m * (cos.acos() / 3.0).cos()
+        }
+    };
+    let mut x = w - a / 3.0;
+    for _ in 0..2 {
+        let f = ((x + a) * x + b) * x + c;
+        let df = (3.0 * x + 2.0 * a) * x + b;
+        if df != 0.0 {
+            x -= f / df;
+        }
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Lambertian;
+    use crate::random::random_unit_vector;
+    use crate::torus::*;
+    use float_eq::assert_float_eq;
+
+    fn torus() -> Torus {
+        let m = MaterialKind::Lambertian(Lambertian::new(Vec3::one()));
+        Torus::new(Vec3::zero(), Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, m)
+    }
+
+    #[test]
+    fn quartic() {
+        // (x - 1)(x - 2)(x + 3)(x - 0.5)
+        let mut roots = solve_quartic([-0.5, -7.0, 9.5, -3.0]);
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(roots.len(), 4);
+        for (r, e) in roots.iter().zip([-3.0, 0.5, 1.0, 2.0].iter()) {
+            assert_float_eq!(*r, *e, abs <= 1e-9);
+        }
+        // (x^2 + 1)(x^2 + 2) has none
+        assert!(solve_quartic([0.0, 3.0, 0.0, 2.0]).is_empty());
+    }
+
+    #[test]
+    fn hit() {
+        let t = torus();
+        let r = Ray::new(Vec3::new(10.0, 0.0, 0.0), Vec3::new(-2.0, 0.0, 0.0));
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 3.75, abs <= 1e-5);
+        assert_float_eq!(rec.normal.x(), 1.0, abs <= 1e-5);
+        assert_float_eq!(rec.v, 0.0, abs <= 1e-5);
+
+        // through the tube, out of it towards the hole
+        let r = Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 0.5, abs <= 1e-5);
+        assert_float_eq!(rec.normal.x(), -1.0, abs <= 1e-5);
+
+        // from the top, down through the tube
+        let r = Ray::new(Vec3::new(0.0, 5.0, -2.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 4.5, abs <= 1e-5);
+        assert_float_eq!(rec.normal.y(), 1.0, abs <= 1e-5);
+        assert_float_eq!(rec.v, 0.25, abs <= 1e-5);
+
+        // down the axis through the hole, and past the outside
+        let r = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(t.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.0, 5.0, 2.6), Vec3::new(0.0, -1.0, 0.0));
+        assert!(t.hit(&r, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn grazing() {
+        let t = torus();
+        let d = Vec3::new(1.0, 0.0, 0.0);
+        // along the top of the tube, just above and just below
+        let r = Ray::new(Vec3::new(-10.0, 0.501, 0.0), d);
+        assert!(t.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(-10.0, 0.499, 0.0), d);
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.p.x() + 2.0).abs() < 0.05);
+        assert!(rec.normal.y() > 0.99);
+        // exactly tangent it may touch, but only at the top
+        let r = Ray::new(Vec3::new(-10.0, 0.5, 0.0), d);
+        if let Some(rec) = t.hit(&r, 0.001, f32::MAX) {
+            assert!((rec.p.x() + 2.0).abs() < 0.05);
+        }
+
+        // past the outer rim
+        let r = Ray::new(Vec3::new(-10.0, 0.0, 2.501), d);
+        assert!(t.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(-10.0, 0.0, 2.499), d);
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.p.x().abs() < 0.1);
+        assert!(rec.normal.z() > 0.99);
+
+        // touching the inner rim across the hole: the first hit is unaffected
+        let r = Ray::new(Vec3::new(-10.0, 0.0, 1.5), d);
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        let x = (2.5f32 * 2.5 - 1.5 * 1.5).sqrt();
+        assert_float_eq!(rec.p.x(), -x, abs <= 1e-4);
+        // from inside the tube, leaving it just before or just after the rim
+        let r = Ray::new(Vec3::new(-1.8, 0.0, 1.49), d);
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.p.x(), -(1.5f32 * 1.5 - 1.49 * 1.49).sqrt(), abs <= 1e-3);
+        let r = Ray::new(Vec3::new(-1.8, 0.0, 1.51), d);
+        let rec = t.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.p.x(), (2.5f32 * 2.5 - 1.51 * 1.51).sqrt(), abs <= 1e-3);
+    }
+
+    #[test]
+    fn on_surface() {
+        fastrand::seed(5);
+        let t = Torus::new(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            1.0,
+            0.3,
+            MaterialKind::Lambertian(Lambertian::new(Vec3::one())),
+        );
+        let b = t.bounding_box().unwrap();
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let o = Vec3::new(1.0, 2.0, 3.0) + 4.0 * random_unit_vector();
+            let target = Vec3::new(1.0, 2.0, 3.0) + 1.3 * random_unit_vector();
+            let r = Ray::new(o, 0.5 * (target - o));
+            if let Some(rec) = t.hit(&r, 0.001, f32::MAX) {
+                hits += 1;
+                // at distance minor from the tube's center circle
+                let l = t.frame.local(rec.p - t.frame.origin);
+                let rho = (l.x() * l.x() + l.y() * l.y()).sqrt();
+                let dist = ((rho - 1.0) * (rho - 1.0) + l.z() * l.z()).sqrt();
+                assert_float_eq!(dist, 0.3, abs <= 1e-4);
+                assert_float_eq!(rec.normal.length(), 1.0, abs <= 1e-5);
+                for i in 0..3 {
+                    assert!(b.min[i] - 1e-4 <= rec.p[i] && rec.p[i] <= b.max[i] + 1e-4);
+                }
+            }
+        }
+        assert!(hits > 100);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::aabb::Aabb;
+use crate::vec3::Vec3;
+use std::ops::Mul;
+
+// row major, acting on column vectors
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Mat4 { m }
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.m[j][i];
+            }
+        }
+        Mat4 { m }
+    }
+
+    // None for singular matrices, and those with NaN or infinite entries
+    pub fn inverse(&self) -> Option<Mat4> {
+        if self.m.iter().flatten().any(|x| !x.is_finite()) {
+            return None;
+        }
+        // Gauss-Jordan elimination with partial pivoting
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col] == 0.0 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let p = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= p;
+                inv[col][j] /= p;
+            }
+            for i in 0..4 {
+                if i != col {
+                    let f = a[i][col];
+                    for j in 0..4 {
+                        a[i][j] -= f * a[col][j];
+                        inv[i][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4 { m: inv })
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+// an invertible affine transform along with its inverse; the builder
+// methods apply their transform after the ones before them
+#[derive(Debug, Copy, Clone)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    // None if the matrix can't be inverted
+    pub fn from_matrix(matrix: Mat4) -> Option<Transform> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    // self followed by other
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    pub fn translate(&self, v: Vec3) -> Transform {
+        let mut m = Mat4::identity();
+        let mut inv = Mat4::identity();
+        for i in 0..3 {
+            m.m[i][3] = v[i];
+            inv.m[i][3] = -v[i];
+        }
+        self.then(&Transform {
+            matrix: m,
+            inverse: inv,
+        })
+    }
+
+    // panics on a zero factor
+    pub fn scale(&self, v: Vec3) -> Transform {
+        assert!(v.x() != 0.0 && v.y() != 0.0 && v.z() != 0.0);
+        let mut m = Mat4::identity();
+        let mut inv = Mat4::identity();
+        for i in 0..3 {
+            m.m[i][i] = v[i];
+            inv.m[i][i] = 1.0 / v[i];
+        }
+        self.then(&Transform {
+            matrix: m,
+            inverse: inv,
+        })
+    }
+
+    // counterclockwise by degrees around axis, looking against it
+    pub fn rotate(&self, axis: Vec3, degrees: f32) -> Transform {
+        let a = axis.unit_vector();
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let m = Mat4 {
+            m: [
+                [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+                [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+                [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+        // rotations are orthogonal
+        self.then(&Transform {
+            matrix: m,
+            inverse: m.transpose(),
+        })
+    }
+
+    pub fn rotate_x(&self, degrees: f32) -> Transform {
+        self.rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    pub fn rotate_y(&self, degrees: f32) -> Transform {
+        self.rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    pub fn rotate_z(&self, degrees: f32) -> Transform {
+        self.rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        self.matrix.point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.vector(v)
+    }
+
+    // by the inverse transpose, so that normals stay perpendicular to the
+    // surface under non-uniform scaling; not normalized
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().vector(n)
+    }
+
+    // box around the transformed corners of bbox
+    pub fn bbox(&self, bbox: &Aabb) -> Aabb {
+        let mut corners = vec![];
+        for i in 0..8 {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    bbox.min[axis]
+                } else {
+                    bbox.max[axis]
+                }
+            };
+            corners.push(self.point(Vec3::new(pick(0), pick(1), pick(2))));
+        }
+        Aabb::from_points(&corners)
+    }
+
+    // the factor by which lengths change if the transform preserves angles
+    pub fn uniform_scale(&self) -> Option<f32> {
+        let axes = [
+            self.vector(Vec3::new(1.0, 0.0, 0.0)),
+            self.vector(Vec3::new(0.0, 1.0, 0.0)),
+            self.vector(Vec3::new(0.0, 0.0, 1.0)),
+        ];
+        let s = axes[0].length();
+        let tolerance = 1e-4 * s;
+        let similar = axes.iter().all(|a| (a.length() - s).abs() <= tolerance)
+            && axes[0].dot(axes[1]).abs() <= tolerance * s
+            && axes[1].dot(axes[2]).abs() <= tolerance * s
+            && axes[2].dot(axes[0]).abs() <= tolerance * s;
+        if similar {
+            Some(s)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transform::*;
+    use float_eq::assert_float_eq;
+
+    fn assert_vec3(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn inverse() {
+        let t = Transform::identity()
+            .scale(Vec3::new(2.0, 3.0, 0.5))
+            .rotate(Vec3::new(1.0, 2.0, 3.0), 40.0)
+            .translate(Vec3::new(1.0, -2.0, 5.0));
+        let m = *t.matrix() * *t.inverse().matrix();
+        for i in 0..4 {
+            for j in 0..4 {
+                let e = if i == j { 1.0 } else { 0.0 };
+                assert_float_eq!(m.m[i][j], e, abs <= 1e-5);
+            }
+        }
+        let inv = t.matrix().inverse().unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_float_eq!(inv.m[i][j], t.inverse().matrix().m[i][j], abs <= 1e-5);
+            }
+        }
+        let singular = Transform::from_matrix(Mat4 { m: [[0.0; 4]; 4] });
+        assert!(singular.is_none());
+        let mut nan = Mat4::identity();
+        nan.m[1][2] = f32::NAN;
+        assert!(Transform::from_matrix(nan).is_none());
+        nan.m[1][2] = f32::INFINITY;
+        assert!(nan.inverse().is_none());
+    }
+
+    #[test]
+    fn order() {
+        let t = Transform::identity()
+            .rotate_z(90.0)
+            .translate(Vec3::new(1.0, 0.0, 0.0));
+        assert_vec3(t.point(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(1.0, 1.0, 0.0));
+        assert_vec3(t.vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+        let t = Transform::identity()
+            .translate(Vec3::new(1.0, 0.0, 0.0))
+            .rotate_z(90.0);
+        assert_vec3(t.point(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn normal() {
+        let t = Transform::identity().scale(Vec3::new(2.0, 1.0, 1.0));
+        // the plane x + y = 0 becomes x / 2 + y = 0
+        let n = t.normal(Vec3::new(1.0, 1.0, 0.0)).unit_vector();
+        let tangent = t.vector(Vec3::new(1.0, -1.0, 0.0));
+        assert_float_eq!(n.dot(tangent), 0.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn bbox() {
+        let b = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::one());
+        let t = Transform::identity()
+            .rotate_y(45.0)
+            .translate(Vec3::new(0.0, 5.0, 0.0));
+        let r = t.bbox(&b);
+        let s = 2.0f32.sqrt();
+        assert_vec3(r.min, Vec3::new(-s, 4.0, -s));
+        assert_vec3(r.max, Vec3::new(s, 6.0, s));
+    }
+
+    #[test]
+    fn uniform_scale() {
+        let t = Transform::identity()
+            .rotate_x(30.0)
+            .scale(Vec3::new(3.0, 3.0, 3.0));
+        assert_float_eq!(t.uniform_scale().unwrap(), 3.0, abs <= 1e-5);
+        let t = Transform::identity().scale(Vec3::new(1.0, 2.0, 1.0));
+        assert!(t.uniform_scale().is_none());
+    }
+}
@@ -1,14 +1,25 @@
+use crate::aabb::Aabb;
 use crate::hit::{HitRecord, Hittable};
+use crate::light::Light;
 use crate::material::MaterialKind;
+use crate::random::rand_uniform;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
-#[derive(Debug)]
+// texture coordinates of triangles that aren't given any
+pub(crate) const DEFAULT_UV: [[f32; 2]; 3] = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+
+#[derive(Clone, Debug)]
 pub struct Triangle {
     points: [Vec3; 3],
     material: MaterialKind,
     normal_vector: Vec3,
-    distance: f32,
+    // texture coordinates of the three points
+    uv: [[f32; 2]; 3],
+    // per point shading normals, the face normal is used if None
+    normals: Option<[Vec3; 3]>,
+    // whether rays hitting the side opposite to the face normal miss
+    cull_back_faces: bool,
 }
 
 impl Triangle {
@@ -16,18 +27,13 @@ impl Triangle {
         let ab = b - a;
         let ac = c - a;
         let n = ab.cross(ac).unit_vector();
-        let d = a * n;
-        let d = -(d.x() + d.y() + d.z());
-
-        // let n = Vec3::new(1.0, -1.0, 3.0);
-        // let d = 1.0;
-        // dbg!(n);
-        // dbg!(d);
         Triangle {
             points: [a, b, c],
             material: mat,
             normal_vector: n,
-            distance: d,
+            uv: DEFAULT_UV,
+            normals: None,
+            cull_back_faces: false,
         }
     }
 
@@ -35,11 +41,23 @@ impl Triangle {
         let [a, b, c] = self.points;
         let ab = b - a;
         let ac = c - a;
-        let n = ab.cross(ac).unit_vector();
-        let d = a * n;
-        let d = -(d.x() + d.y() + d.z());
-        self.normal_vector = n;
-        self.distance = d;
+        self.normal_vector = ab.cross(ac).unit_vector();
+    }
+
+    pub fn set_uv(&mut self, uv: [[f32; 2]; 3]) -> &mut Triangle {
+        self.uv = uv;
+        self
+    }
+
+    pub fn set_normals(&mut self, normals: [Vec3; 3]) -> &mut Triangle {
+        self.normals = Some(normals);
+        self
+    }
+
+    // the front face is the one a, b and c wind counterclockwise around
+    pub fn set_back_face_culling(&mut self, cull: bool) -> &mut Triangle {
+        self.cull_back_faces = cull;
+        self
     }
 
     pub fn move_x(&mut self, x: f32) -> &mut Triangle {
@@ -70,77 +88,344 @@ impl Triangle {
     }
 }
 
-impl Hittable for Triangle {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        // http://yamatyuu.net/other/point1/index.html
-        let d = self.normal_vector * r.origin();
-        let d = d.x() + d.y() + d.z() + self.distance;
-        let n = self.normal_vector * r.direction();
-        let n = n.x() + n.y() + n.z();
-        let t = -d / n;
-        // dbg!(t);
-        if t < t_min || t > t_max {
-            return None;
+// watertight intersection after Woop, Benthin and Wald, "Watertight
+// Ray/Triangle Intersection" (JCGT 2013): the triangle is sheared into the
+// space where the ray runs along z, so that rays through a shared edge or
+// vertex hit at least one of the triangles around it. Returns t and the
+// barycentric weights of b and c; rays parallel to the triangle miss
+pub(crate) fn intersect(
+    points: &[Vec3; 3],
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+    cull_back_faces: bool,
+) -> Option<(f32, f32, f32)> {
+    let d = r.direction();
+    let abs_d = [d.x().abs(), d.y().abs(), d.z().abs()];
+    let kz = if abs_d[0] > abs_d[1] {
+        if abs_d[0] > abs_d[2] {
+            0
+        } else {
+            2
         }
+    } else if abs_d[1] > abs_d[2] {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    // keeps the winding, so that the sign tells front from back
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
 
-        let p = t * r.direction() + r.origin();
-        // dbg!(p);
-
-        let [a, b, c] = self.points;
-        let ab = b - a;
-        let bp = p - b;
+    let [a, b, c] = points.map(|p| p - r.origin());
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
 
-        let bc = c - b;
-        let cp = p - c;
+    // scaled barycentric weights of a, b and c
+    let mut u = cx * by - cy * bx;
+    let mut v = ax * cy - ay * cx;
+    let mut w = bx * ay - by * ax;
+    // on an edge in single precision; decide it in double
+    if u == 0.0 || v == 0.0 || w == 0.0 {
+        let cross = |px: f32, py: f32, qx: f32, qy: f32| {
+            (px as f64 * qy as f64 - py as f64 * qx as f64) as f32
+        };
+        u = cross(cx, cy, bx, by);
+        v = cross(ax, ay, cx, cy);
+        w = cross(bx, by, ax, ay);
+    }
 
-        let ca = a - c;
-        let ap = p - a;
+    // front faces have all weights positive
+    let back = u < 0.0 || v < 0.0 || w < 0.0;
+    let front = u > 0.0 || v > 0.0 || w > 0.0;
+    if back && (front || cull_back_faces) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
 
-        let c1 = ab.cross(bp);
-        let c2 = bc.cross(cp);
-        let c3 = ca.cross(ap);
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    Some((t, v / det, w / det))
+}
 
-        if c1.dot(c2) > 0.0 && c1.dot(c3) > 0.0 {
-            // dbg!("hit");
-            let mut rec = HitRecord::new(self.material);
-            rec.t = t;
-            rec.p = p;
+// interpolated texture coordinates for barycentric weights of b and c
+pub(crate) fn uv_at(uv: [[f32; 2]; 3], wb: f32, wc: f32) -> (f32, f32) {
+    let [ta, tb, tc] = uv;
+    let wa = 1.0 - wb - wc;
+    (
+        wa * ta[0] + wb * tb[0] + wc * tc[0],
+        wa * ta[1] + wb * tb[1] + wc * tc[1],
+    )
+}
 
-            // dbg!(self.normal_vector.dot(r.direction().unit_vector()));
-            rec.normal = if self.normal_vector.dot(r.direction().unit_vector()) < 0.0 {
-                self.normal_vector
+// interpolated shading normal for barycentric weights of b and c, the face
+// normal without vertex normals or where they cancel out
+pub(crate) fn normal_at(normals: Option<[Vec3; 3]>, face_normal: Vec3, wb: f32, wc: f32) -> Vec3 {
+    match normals {
+        Some([na, nb, nc]) => {
+            let n = (1.0 - wb - wc) * na + wb * nb + wc * nc;
+            if n.squared_length() > 0.0 {
+                n.unit_vector()
             } else {
-                self.normal_vector * -1.0
-            };
-            Some(rec)
-        } else {
-            None
+                face_normal
+            }
+        }
+        None => face_normal,
+    }
+}
+
+// shading normals are turned to the side of the face normal, which vertex
+// normals disagreeing with the winding may not be on, then face the ray with it
+pub(crate) fn shading_normal(normal: Vec3, face_normal: Vec3, r: &Ray) -> Vec3 {
+    let normal = if normal.dot(face_normal) < 0.0 {
+        -normal
+    } else {
+        normal
+    };
+    if face_normal.dot(r.direction()) < 0.0 {
+        normal
+    } else {
+        -normal
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t, wb, wc) = intersect(&self.points, r, t_min, t_max, self.cull_back_faces)?;
+        let mut rec = HitRecord::new(&self.material);
+        rec.t = t;
+        rec.p = r.point_at_parameter(t);
+        let (u, v) = uv_at(self.uv, wb, wc);
+        rec.u = u;
+        rec.v = v;
+
+        let normal = normal_at(self.normals, self.normal_vector, wb, wc);
+        rec.normal = shading_normal(normal, self.normal_vector, r);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.points).padded(0.0001))
+    }
+
+    fn lights(&self) -> Vec<Box<dyn Light + Send + Sync>> {
+        match self.material {
+            MaterialKind::DiffuseLight(_) => vec![Box::new(self.clone())],
+            _ => vec![],
         }
     }
 }
 
+impl Light for Triangle {
+    fn sample(&self) -> HitRecord<'_> {
+        let [a, b, c] = self.points;
+        let su = rand_uniform().sqrt();
+        let b0 = 1.0 - su;
+        let b1 = rand_uniform() * su;
+        let mut rec = HitRecord::new(&self.material);
+        rec.p = b0 * a + b1 * b + (1.0 - b0 - b1) * c;
+        rec.normal = self.normal_vector;
+        let (u, v) = uv_at(self.uv, b1, 1.0 - b0 - b1);
+        rec.u = u;
+        rec.v = v;
+        rec
+    }
+
+    fn area(&self) -> f32 {
+        let [a, b, c] = self.points;
+        0.5 * (b - a).cross(c - a).length()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{material::Metal, triangle::*};
+    use float_eq::assert_float_eq;
+
+    fn metal() -> MaterialKind {
+        MaterialKind::Metal(Metal::new(Vec3::new(1.0, 1.0, 1.0), 0.1))
+    }
+
     #[test]
-    fn test() {
+    fn hit() {
         let a = Vec3::new(0.0, 0.0, 0.0);
         let b = Vec3::new(2.0, 0.0, 0.0);
         let c = Vec3::new(0.0, 3.0, 0.0);
+        let t = Triangle::new(a, b, c, metal());
+
+        let r = Ray::new(Vec3::new(0.5, 1.5, 10.0), Vec3::new(0.0, 0.0, -2.0));
+        let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 5.0, abs <= 1e-6);
+        assert_float_eq!(rec.p.x(), 0.5, abs <= 1e-6);
+        assert_float_eq!(rec.p.y(), 1.5, abs <= 1e-6);
+        assert_float_eq!(rec.normal.z(), 1.0, abs <= 1e-6);
+        assert!(t.hit(&r, 0.0, 4.0).is_none());
+        assert!(t.hit(&r, 6.0, f32::MAX).is_none());
+        // the range is open at both ends
+        assert!(t.hit(&r, 5.0, f32::MAX).is_none());
+        assert!(t.hit(&r, 0.0, 5.0).is_none());
+
+        let (_, wb, wc) = intersect(&t.points, &r, 0.0, f32::MAX, false).unwrap();
+        assert_float_eq!(wb, 0.25, abs <= 1e-6);
+        assert_float_eq!(wc, 0.5, abs <= 1e-6);
+
+        // beside it, and from a steep angle across an axis it doesn't span
+        let r = Ray::new(Vec3::new(10.0, 1.0, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(t.hit(&r, 0.0, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(-1.0, 1.0, 1.0), Vec3::new(2.0, 0.1, -1.0));
+        let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+        assert_float_eq!(rec.p.x(), 1.0, abs <= 1e-6);
+    }
 
+    #[test]
+    fn parallel() {
+        let t = Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            metal(),
+        );
+        // in the plane of the triangle, through it and beside it
+        for o in [Vec3::new(-1.0, 0.5, 0.0), Vec3::new(-1.0, 0.5, 1.0)].iter() {
+            let r = Ray::new(*o, Vec3::new(1.0, 0.0, 0.0));
+            assert!(t.hit(&r, 0.0, f32::MAX).is_none());
+        }
+        let degenerate = [
+            Vec3::zero(),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(2.0, 2.0, 0.0),
+        ];
+        let r = Ray::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(intersect(&degenerate, &r, 0.0, f32::MAX, false).is_none());
+    }
+
+    #[test]
+    fn shared_edge() {
+        // a flat quad split along its diagonal; rays at the diagonal from
+        // all sides must hit one of the halves
+        let p = [
+            Vec3::new(-0.3, -0.7, 0.25),
+            Vec3::new(1.1, -0.2, 0.25),
+            Vec3::new(0.9, 1.3, 0.25),
+            Vec3::new(-0.4, 0.8, 0.25),
+        ];
+        let halves = [[p[0], p[1], p[2]], [p[0], p[2], p[3]]];
+        let rng = crate::random::SeededRng::new(3);
+        for _ in 0..20000 {
+            let s = rng.uniform();
+            let target = p[0] + s * (p[2] - p[0]);
+            let origin = target + 3.0 * rng.unit_vector();
+            let r = Ray::new(origin, target - origin);
+            let hits = halves
+                .iter()
+                .filter(|h| intersect(h, &r, 0.0, f32::MAX, false).is_some())
+                .count();
+            assert!(hits >= 1, "{:?} {:?}", origin, target);
+        }
+    }
+
+    #[test]
+    fn shared_vertex() {
+        // a fan of triangles around the origin, hit right at the center
+        let rim: Vec<Vec3> = (0..7)
+            .map(|k| {
+                let a = k as f32 / 7.0 * 2.0 * std::f32::consts::PI;
+                Vec3::new(a.cos(), a.sin(), 0.0)
+            })
+            .collect();
+        let fan: Vec<[Vec3; 3]> = (0..7)
+            .map(|k| [Vec3::zero(), rim[k], rim[(k + 1) % 7]])
+            .collect();
+        let rng = crate::random::SeededRng::new(5);
+        for _ in 0..5000 {
+            let origin = 2.0 * rng.unit_vector();
+            let r = Ray::new(origin, -origin);
+            let hit = fan
+                .iter()
+                .any(|t| intersect(t, &r, 0.0, f32::MAX, false).is_some());
+            assert!(hit, "{:?}", origin);
+        }
+    }
+
+    #[test]
+    fn back_face_culling() {
+        let mut t = Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            metal(),
+        );
+        let front = Ray::new(Vec3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let back = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.3, 0.0, 1.0));
+        assert!(t.hit(&front, 0.0, f32::MAX).is_some());
+        assert!(t.hit(&back, 0.0, f32::MAX).is_some());
+        t.set_back_face_culling(true);
+        assert!(t.hit(&front, 0.0, f32::MAX).is_some());
+        assert!(t.hit(&back, 0.0, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn uv() {
+        let a = Vec3::new(0.0, 0.0, 0.0);
+        let b = Vec3::new(2.0, 0.0, 0.0);
+        let c = Vec3::new(0.0, 2.0, 0.0);
+        let m = MaterialKind::Metal(Metal::new(Vec3::new(1.0, 1.0, 1.0), 0.1));
+        let mut t = Triangle::new(a, b, c, m);
+        let d = Vec3::new(0.0, 0.0, -1.0);
+
+        let r = Ray::new(Vec3::new(0.5, 1.0, 1.0), d);
+        let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+        assert_float_eq!(rec.u, 0.25, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-6);
+
+        t.set_uv([[1.0, 1.0], [0.0, 1.0], [1.0, 0.0]]);
+        let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+        assert_float_eq!(rec.u, 0.75, abs <= 1e-6);
+        assert_float_eq!(rec.v, 0.5, abs <= 1e-6);
+    }
+
+    #[test]
+    fn interpolated_normal() {
+        let a = Vec3::new(0.0, 0.0, 0.0);
+        let b = Vec3::new(2.0, 0.0, 0.0);
+        let c = Vec3::new(0.0, 2.0, 0.0);
         let m = MaterialKind::Metal(Metal::new(Vec3::new(1.0, 1.0, 1.0), 0.1));
-        let t = Triangle::new(a, b, c, m);
-        // let o = Vec3::new(1.0, 1.0, 10.0);
-        let o = Vec3::new(10.0, 1.0, 10.0);
-        let d = Vec3::new(0.0, 0.0, -1.0).unit_vector();
-        let r = Ray::new(o, d);
-        let res = t.hit(&r, 0.0, std::f32::MAX);
-        dbg!(res);
-        // match res {
-        //     Some(hr) => hr.p,
-        //     None => {
-        //         unreachable!();
-        //     }
-        // }
+        let mut t = Triangle::new(a, b, c, m);
+        let na = Vec3::new(0.0, 0.0, 1.0);
+        let nb = Vec3::new(1.0, 0.0, 1.0).unit_vector();
+        t.set_normals([na, nb, na]);
+
+        let r = Ray::new(Vec3::new(1.0, 0.1, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+        // na and nc are equal and b has weight 0.5
+        let expected = (0.5 * na + 0.5 * nb).unit_vector();
+        assert_float_eq!(rec.normal.x(), expected.x(), abs <= 1e-5);
+        assert_float_eq!(rec.normal.z(), expected.z(), abs <= 1e-5);
+
+        // from behind
+        let r = Ray::new(Vec3::new(1.0, 0.1, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+        assert_float_eq!(rec.normal.z(), -expected.z(), abs <= 1e-5);
+
+        // vertex normals against the winding still face the ray
+        t.set_normals([-na, -nb, -na]);
+        for &z in [1.0, -1.0].iter() {
+            let r = Ray::new(Vec3::new(1.0, 0.1, z), Vec3::new(0.0, 0.0, -z));
+            let rec = t.hit(&r, 0.0, f32::MAX).unwrap();
+            assert!(rec.normal.dot(r.direction()) < 0.0);
+            assert_float_eq!(rec.normal.x(), z * expected.x(), abs <= 1e-5);
+        }
     }
 }
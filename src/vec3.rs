@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
 
 #[derive(Debug, Copy, Clone)]
 pub struct Vec3 {
@@ -29,13 +29,13 @@ impl Vec3 {
     }
 
     pub fn r(&self) -> u8 {
-        (self.e[0] as f32 * 255.99) as u8
+        (self.e[0] * 255.99) as u8
     }
     pub fn g(&self) -> u8 {
-        (self.e[1] as f32 * 255.99) as u8
+        (self.e[1] * 255.99) as u8
     }
     pub fn b(&self) -> u8 {
-        (self.e[2] as f32 * 255.99) as u8
+        (self.e[2] * 255.99) as u8
     }
 
     pub fn length(&self) -> f32 {
@@ -61,6 +61,42 @@ impl Vec3 {
     pub fn unit_vector(&self) -> Vec3 {
         *self / self.length()
     }
+
+    // two unit vectors completing this unit vector to a right handed orthonormal basis
+    // (Duff et al., "Building an Orthonormal Basis, Revisited")
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let [x, y, z] = self.e;
+        let sign = 1.0f32.copysign(z);
+        let a = -1.0 / (sign + z);
+        let b = x * y * a;
+        (
+            Vec3::new(1.0 + sign * x * x * a, sign * b, -sign * x),
+            Vec3::new(b, sign + y * y * a, -y),
+        )
+    }
+
+    pub fn min(&self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.e[0].min(other.e[0]),
+            self.e[1].min(other.e[1]),
+            self.e[2].min(other.e[2]),
+        )
+    }
+
+    pub fn max(&self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.e[0].max(other.e[0]),
+            self.e[1].max(other.e[1]),
+            self.e[2].max(other.e[2]),
+        )
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f32;
+    fn index(&self, i: usize) -> &f32 {
+        &self.e[i]
+    }
 }
 
 impl Add for Vec3 {
@@ -265,6 +301,47 @@ mod tests {
         assert_float_eq!(u.length(), 1.0, abs <= ABS_DIFF_LIMIT);
     }
 
+    #[test]
+    fn min_max() {
+        let a = Vec3::new(1.0, 5.0, -3.0);
+        let b = Vec3::new(2.0, -1.0, -4.0);
+        let c = a.min(b);
+        assert_eq!(c.x(), 1.0);
+        assert_eq!(c.y(), -1.0);
+        assert_eq!(c.z(), -4.0);
+        let c = a.max(b);
+        assert_eq!(c.x(), 2.0);
+        assert_eq!(c.y(), 5.0);
+        assert_eq!(c.z(), -3.0);
+    }
+
+    #[test]
+    fn orthonormal_basis() {
+        for n in [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 2.0, -3.0).unit_vector(),
+        ]
+        .iter()
+        {
+            let (t, b) = n.orthonormal_basis();
+            assert_float_eq!(t.length(), 1.0, abs <= 1e-6);
+            assert_float_eq!(b.length(), 1.0, abs <= 1e-6);
+            assert_float_eq!(t.dot(*n), 0.0, abs <= 1e-6);
+            assert_float_eq!(b.dot(*n), 0.0, abs <= 1e-6);
+            assert_float_eq!(t.dot(b), 0.0, abs <= 1e-6);
+            assert_float_eq!(t.cross(b).dot(*n), 1.0, abs <= 1e-6);
+        }
+    }
+
+    #[test]
+    fn index() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(a[0], 1.0);
+        assert_eq!(a[1], 2.0);
+        assert_eq!(a[2], 3.0);
+    }
+
     #[test]
     fn add_v3_v3() {
         let a = Vec3::new(1.0, 2.0, 3.0);
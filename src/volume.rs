@@ -0,0 +1,285 @@
+use crate::aabb::Aabb;
+use crate::box_shape::BoxShape;
+use crate::hit::{HitRecord, Hittable};
+use crate::material::MaterialKind;
+use crate::medium::Medium;
+use crate::random::rand_uniform;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+// densities at the voxels of a regular grid, x varying fastest, then y, then z
+#[derive(Clone, Debug)]
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    data: Vec<f32>,
+    max: f32,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl DensityGrid {
+    // panics unless data holds one non-negative value per voxel
+    pub fn new(resolution: [usize; 3], data: Vec<f32>) -> DensityGrid {
+        assert!(resolution.iter().all(|&n| n > 0));
+        assert_eq!(data.len(), resolution.iter().product::<usize>());
+        assert!(data.iter().all(|&d| d >= 0.0));
+        let max = data.iter().cloned().fold(0.0, f32::max);
+        DensityGrid {
+            resolution,
+            data,
+            max,
+        }
+    }
+
+    // a raw file with either one byte per voxel, mapped to [0, 1], or one
+    // little endian f32 per voxel, told apart by the file size
+    pub fn load<P: AsRef<Path>>(path: P, resolution: [usize; 3]) -> io::Result<DensityGrid> {
+        DensityGrid::from_bytes(&std::fs::read(path)?, resolution)
+    }
+
+    pub fn from_bytes(bytes: &[u8], resolution: [usize; 3]) -> io::Result<DensityGrid> {
+        let n = resolution.iter().product::<usize>();
+        if n == 0 {
+            return Err(invalid_data("empty resolution".to_string()));
+        }
+        let data: Vec<f32> = if bytes.len() == n {
+            bytes.iter().map(|&b| b as f32 / 255.0).collect()
+        } else if bytes.len() == 4 * n {
+            bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect()
+        } else {
+            return Err(invalid_data(format!(
+                "{} bytes for {} voxels, expected {} or {}",
+                bytes.len(),
+                n,
+                n,
+                4 * n
+            )));
+        };
+        // also rejects NaN
+        if !data.iter().all(|&d| d >= 0.0 && d.is_finite()) {
+            return Err(invalid_data("negative or non-finite density".to_string()));
+        }
+        Ok(DensityGrid::new(resolution, data))
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(z * ny + y) * nx + x]
+    }
+
+    // trilinear between the voxel centers, with p in [0, 1] over the grid;
+    // clamped to the outer voxels near the faces and 0 outside
+    pub fn lookup(&self, p: Vec3) -> f32 {
+        if (0..3).any(|a| p[a] < 0.0 || p[a] > 1.0) {
+            return 0.0;
+        }
+        let mut i0 = [0; 3];
+        let mut i1 = [0; 3];
+        let mut f = [0.0; 3];
+        for a in 0..3 {
+            let n = self.resolution[a];
+            let x = (p[a] * n as f32 - 0.5).max(0.0).min((n - 1) as f32);
+            i0[a] = x.floor() as usize;
+            i1[a] = (i0[a] + 1).min(n - 1);
+            f[a] = x - i0[a] as f32;
+        }
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let row = |y: usize, z: usize| lerp(self.voxel(i0[0], y, z), self.voxel(i1[0], y, z), f[0]);
+        let slice = |z: usize| lerp(row(i0[1], z), row(i1[1], z), f[1]);
+        lerp(slice(i0[2]), slice(i1[2]), f[2])
+    }
+}
+
+// medium of varying density given by a grid stretched over an axis aligned
+// box; distances are sampled by delta tracking and shadow rays are dimmed by
+// ratio tracking, both against the grid's maximum as the majorant
+pub struct GridMedium {
+    bounds: BoxShape,
+    bbox: Aabb,
+    grid: DensityGrid,
+    scale: f32,
+    phase: MaterialKind,
+}
+
+impl GridMedium {
+    // the grid values times scale are scattering events per unit length
+    pub fn new(a: Vec3, b: Vec3, grid: DensityGrid, scale: f32, phase: MaterialKind) -> GridMedium {
+        GridMedium {
+            bounds: BoxShape::new(a, b, phase.clone()),
+            bbox: Aabb::new(a, b),
+            grid,
+            scale,
+            phase,
+        }
+    }
+
+    fn density(&self, p: Vec3) -> f32 {
+        let q = p - self.bbox.min;
+        let extent = self.bbox.extent();
+        let local = Vec3::new(q.x() / extent.x(), q.y() / extent.y(), q.z() / extent.z());
+        self.scale * self.grid.lookup(local)
+    }
+
+    fn majorant(&self) -> f32 {
+        self.scale * self.grid.max()
+    }
+
+    // the step to the next tentative collision against the majorant, in
+    // units of the ray parameter
+    fn step(&self, r: &Ray) -> f32 {
+        -(1.0 - rand_uniform()).ln() / (self.majorant() * r.direction().length())
+    }
+}
+
+impl Medium for GridMedium {
+    fn phase(&self) -> &MaterialKind {
+        &self.phase
+    }
+
+    // tentative collisions are real with probability density / majorant
+    fn sample_distance(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        if self.majorant() <= 0.0 {
+            return None;
+        }
+        // nothing scatters outside the box, which also keeps tracking finite
+        // for rays that slipped past the boundary
+        let (mut t, t_max) = self.bbox.clip(r, t_min, t_max)?;
+        loop {
+            t += self.step(r);
+            if t >= t_max {
+                return None;
+            }
+            if rand_uniform() * self.majorant() < self.density(r.point_at_parameter(t)) {
+                return Some(t);
+            }
+        }
+    }
+
+    // unbiased estimate, weighting instead of terminating at each collision
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let (mut t, t_max) = match self.bbox.clip(r, t_min, t_max) {
+            Some(range) if self.majorant() > 0.0 => range,
+            _ => return 1.0,
+        };
+        let mut transmittance = 1.0;
+        loop {
+            t += self.step(r);
+            if t >= t_max || transmittance == 0.0 {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density(r.point_at_parameter(t)) / self.majorant();
+        }
+    }
+}
+
+impl fmt::Debug for GridMedium {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GridMedium")
+            .field("bbox", &self.bbox)
+            .field("resolution", &self.grid.resolution)
+            .field("scale", &self.scale)
+            .field("phase", &self.phase)
+            .finish()
+    }
+}
+
+impl Hittable for GridMedium {
+    // crossings of the box, as for ConstantMedium
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut rec = self.bounds.hit(r, t_min, t_max)?;
+        rec.material = &self.phase;
+        rec.medium = Some(self);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::Isotropic;
+    use crate::volume::*;
+    use float_eq::assert_float_eq;
+
+    #[test]
+    fn lookup() {
+        let g = DensityGrid::new([2, 1, 1], vec![0.0, 1.0]);
+        assert_float_eq!(g.max(), 1.0, abs <= 0.0);
+        // voxel centers, between them, and clamped near the faces
+        assert_float_eq!(g.lookup(Vec3::new(0.25, 0.5, 0.5)), 0.0, abs <= 1e-6);
+        assert_float_eq!(g.lookup(Vec3::new(0.75, 0.5, 0.5)), 1.0, abs <= 1e-6);
+        assert_float_eq!(g.lookup(Vec3::new(0.5, 0.2, 0.9)), 0.5, abs <= 1e-6);
+        assert_float_eq!(g.lookup(Vec3::new(0.1, 0.0, 1.0)), 0.0, abs <= 1e-6);
+        assert_float_eq!(g.lookup(Vec3::new(0.95, 0.5, 0.5)), 1.0, abs <= 1e-6);
+        assert_float_eq!(g.lookup(Vec3::new(1.5, 0.5, 0.5)), 0.0, abs <= 0.0);
+
+        let g = DensityGrid::new([2, 2, 2], (0..8).map(|i| i as f32).collect());
+        assert_float_eq!(g.lookup(Vec3::new(0.5, 0.5, 0.5)), 3.5, abs <= 1e-5);
+        assert_float_eq!(g.lookup(Vec3::new(0.25, 0.75, 0.25)), 2.0, abs <= 1e-5);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let g = DensityGrid::from_bytes(&[0, 255, 51, 102], [2, 2, 1]).unwrap();
+        assert_float_eq!(g.voxel(1, 0, 0), 1.0, abs <= 1e-6);
+        assert_float_eq!(g.voxel(0, 1, 0), 0.2, abs <= 1e-6);
+
+        let bytes: Vec<u8> = [0.5f32, 3.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes().to_vec())
+            .collect();
+        let g = DensityGrid::from_bytes(&bytes, [1, 1, 2]).unwrap();
+        assert_float_eq!(g.voxel(0, 0, 1), 3.0, abs <= 0.0);
+        assert_float_eq!(g.max(), 3.0, abs <= 0.0);
+
+        assert!(DensityGrid::from_bytes(&[0, 1, 2], [2, 1, 1]).is_err());
+        let negative = (-1.0f32).to_le_bytes();
+        assert!(DensityGrid::from_bytes(&negative, [1, 1, 1]).is_err());
+    }
+
+    #[test]
+    fn tracking() {
+        fastrand::seed(2);
+        // density rising from 0 to 1 between the voxel centers at x = 0.5
+        // and 1.5, so its integral across the box is 1
+        let grid = DensityGrid::new([2, 1, 1], vec![0.0, 1.0]);
+        let phase = MaterialKind::Isotropic(Isotropic::new(Vec3::one()));
+        let m = GridMedium::new(Vec3::zero(), Vec3::new(2.0, 1.0, 1.0), grid, 1.0, phase);
+        let r = Ray::new(Vec3::new(-1.0, 0.5, 0.5), Vec3::new(2.0, 0.0, 0.0));
+        let rec = m.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_float_eq!(rec.t, 0.5, abs <= 1e-6);
+        assert!(rec.medium.is_some());
+
+        let expected = (-1.0f32).exp();
+        let n = 100000;
+        let through = (0..n)
+            .filter(|_| m.sample_distance(&r, 0.5, 1.5).is_none())
+            .count();
+        assert_float_eq!(through as f32 / n as f32, expected, abs <= 0.005);
+        let mean = (0..n).map(|_| m.transmittance(&r, 0.5, 1.5)).sum::<f32>() / n as f32;
+        assert_float_eq!(mean, expected, abs <= 0.005);
+        // nothing scatters where the density is 0
+        for _ in 0..1000 {
+            if let Some(t) = m.sample_distance(&r, 0.5, 1.5) {
+                assert!(r.point_at_parameter(t).x() > 0.5);
+            }
+        }
+        // nor outside the box, however far the ray goes
+        assert_float_eq!(m.transmittance(&r, 1.5, f32::MAX), 1.0, abs <= 0.0);
+        assert!(m.sample_distance(&r, 1.5, f32::MAX).is_none());
+    }
+}